@@ -0,0 +1,84 @@
+//! Compares the event driven scheduler against evaluating every node every tick.
+//!
+//! Run with `cargo bench --bench scheduler`. The circuit is a set of long chains of not gates
+//! each driven by a switch, with only one switch being toggled, so most of the circuit is idle
+//! like it usually is in a larger design.
+
+use simple_electronics::{resources::SchedulerMode, Simulator};
+use std::time::Instant;
+
+const CHAINS: usize = 50;
+const CHAIN_LEN: usize = 100;
+const TICKS: usize = 2000;
+
+fn chain_script() -> String {
+    let mut wires = String::from("let WIRES = #{\n");
+    let mut nodes = String::from("let NODES = [\n");
+
+    for c in 0..CHAINS {
+        let y = c as f64 * 100.0;
+        nodes.push_str(&format!(
+            "    #{{ type: \"Switch\", name: \"s{}\", pos: #{{ x: 0.0, y: {:?} }}, inputs: [], outputs: [[\"w{}_0\"]] }},\n",
+            c, y, c
+        ));
+        for i in 0..CHAIN_LEN {
+            wires.push_str(&format!("    w{}_{}: #{{ bends: [] }},\n", c, i));
+            let output = if i + 1 < CHAIN_LEN {
+                format!("[\"w{}_{}\"]", c, i + 1)
+            } else {
+                "[]".to_string()
+            };
+            nodes.push_str(&format!(
+                "    #{{ type: \"Not\", pos: #{{ x: {:?}, y: {:?} }}, inputs: [[\"w{}_{}\"]], outputs: [{}] }},\n",
+                (i + 1) as f64 * 100.0,
+                y,
+                c,
+                i,
+                output
+            ));
+        }
+    }
+
+    wires.push_str("};\n");
+    nodes.push_str("];\n");
+    wires.push_str(&nodes);
+    wires
+}
+
+fn bench(mode: SchedulerMode, script: &str) -> f64 {
+    let mut sim = Simulator::new();
+    sim.world.insert(mode);
+    sim.load_script(script).unwrap();
+    // let the circuit settle first
+    sim.step(CHAIN_LEN * 2);
+
+    let start = Instant::now();
+    for i in 0..TICKS {
+        if i.is_multiple_of(CHAIN_LEN) {
+            sim.set_switch("s0", (i / CHAIN_LEN).is_multiple_of(2));
+        }
+        sim.step(1);
+    }
+    start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let script = chain_script();
+    println!("{} not gates, {} ticks", CHAINS * CHAIN_LEN, TICKS);
+
+    let sweep = bench(SchedulerMode::Sweep, &script);
+    println!(
+        "sweep:        {:>8.1} ms ({:.0} ticks/s)",
+        sweep * 1000.0,
+        TICKS as f64 / sweep
+    );
+
+    let event_driven = bench(SchedulerMode::EventDriven, &script);
+    println!(
+        "event driven: {:>8.1} ms ({:.0} ticks/s)",
+        event_driven * 1000.0,
+        TICKS as f64 / event_driven
+    );
+
+    println!("speedup: {:.1}x", sweep / event_driven);
+}
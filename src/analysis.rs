@@ -0,0 +1,153 @@
+use crate::components::{Connected, Connection, Node};
+use specs::prelude::*;
+use std::collections::HashMap;
+
+// Nodes are vertices and there's an edge from a node to every node reading a wire it drives.
+// A combinational loop is then a strongly connected component with more than one node in it,
+// or a node driving its own input, found with Tarjan's algorithm.
+
+struct NodeConnections {
+    node: Entity,
+    inputs: Vec<Entity>,
+    outputs: Vec<Entity>,
+}
+
+fn collect_nodes<N, const I: usize, const O: usize>(world: &World, out: &mut Vec<NodeConnections>)
+where
+    N: Node<I, O> + 'static,
+{
+    let nodes = world.read_storage::<Connected<N, I, O>>();
+    let entities = world.entities();
+
+    for (node, entity) in (&nodes, &entities).join() {
+        out.push(NodeConnections {
+            node: entity,
+            inputs: node.inputs.to_vec(),
+            // a loop through a latch or flip-flop is how sequential circuits work
+            outputs: if N::is_sequential() {
+                Vec::new()
+            } else {
+                node.outputs.to_vec()
+            },
+        });
+    }
+}
+
+/// The nodes driven by each node's outputs
+fn build_graph(world: &World) -> HashMap<Entity, Vec<Entity>> {
+    let mut nodes = Vec::new();
+
+    macro_rules! collect_all_nodes {
+        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+            $(
+                collect_nodes::<crate::nodes::$node, $i, $o>(world, &mut nodes);
+            )*
+        };
+    }
+
+    use crate::all_nodes;
+    all_nodes!(collect_all_nodes);
+
+    let connections = world.read_storage::<Connection>();
+    let wires_of = |connection: &Entity| {
+        connections
+            .get(*connection)
+            .map_or(&[][..], |c| c.wires.as_slice())
+    };
+
+    let mut sinks = HashMap::<Entity, Vec<Entity>>::new();
+    for node in nodes.iter() {
+        for wire in node.inputs.iter().flat_map(wires_of) {
+            sinks.entry(*wire).or_default().push(node.node);
+        }
+    }
+
+    nodes
+        .iter()
+        .map(|node| {
+            let mut successors = node
+                .outputs
+                .iter()
+                .flat_map(wires_of)
+                .filter_map(|wire| sinks.get(wire))
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>();
+            successors.sort();
+            successors.dedup();
+            (node.node, successors)
+        })
+        .collect()
+}
+
+/// Groups of nodes which feed back into themselves without anything to hold their state, so a
+/// change can chase itself around forever. Each group is sorted and the groups are sorted by
+/// their first node.
+pub fn find_combinational_loops(world: &World) -> Vec<Vec<Entity>> {
+    let graph = build_graph(world);
+
+    let mut vertices = graph.keys().copied().collect::<Vec<_>>();
+    vertices.sort();
+
+    // Tarjan's algorithm with an explicit stack, large circuits would overflow the call stack
+    let mut index = HashMap::<Entity, usize>::new();
+    let mut low_link = HashMap::<Entity, usize>::new();
+    let mut stack = Vec::new();
+    let mut on_stack = HashMap::<Entity, bool>::new();
+    let mut loops = Vec::new();
+
+    for root in vertices {
+        if index.contains_key(&root) {
+            continue;
+        }
+
+        // each frame is a vertex and how many of its successors have been visited
+        let mut call_stack = vec![(root, 0)];
+        while let Some(&(vertex, next)) = call_stack.last() {
+            if next == 0 {
+                index.insert(vertex, index.len());
+                low_link.insert(vertex, index[&vertex]);
+                stack.push(vertex);
+                on_stack.insert(vertex, true);
+            }
+
+            let successors = &graph[&vertex];
+            if let Some(successor) = successors.get(next).copied() {
+                call_stack.last_mut().unwrap().1 += 1;
+                if !index.contains_key(&successor) {
+                    call_stack.push((successor, 0));
+                } else if on_stack[&successor] {
+                    let low = low_link[&vertex].min(index[&successor]);
+                    low_link.insert(vertex, low);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some((parent, _)) = call_stack.last() {
+                let low = low_link[parent].min(low_link[&vertex]);
+                low_link.insert(*parent, low);
+            }
+
+            if low_link[&vertex] == index[&vertex] {
+                let mut component = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack.insert(member, false);
+                    component.push(member);
+                    if member == vertex {
+                        break;
+                    }
+                }
+
+                if component.len() > 1 || successors.contains(&vertex) {
+                    component.sort();
+                    loops.push(component);
+                }
+            }
+        }
+    }
+
+    loops.sort();
+    loops
+}
@@ -0,0 +1,232 @@
+use simple_electronics::simulator::SETTLE_LIMIT;
+use simple_electronics::{Bus, Signal, Simulator};
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: run_circuit <circuit.bin | script.rhai> [--commands <file>] [command...]
+
+Commands:
+    set <switch> <0|1>      set the state of a named switch, or hold down a button
+    set <dip> <value>       set the value of a named DIP switch, in decimal or hex like 0x3F
+    step <n>                run n ticks
+    settle                  run until no wire changes, failing if that takes more than
+                            10000 ticks
+    print [wire]            print the value of a named wire, or of all named wires
+    expect <wire> <value>   fail if the named wire doesn't have the value, which is
+                            0, 1, Z or X for a wire and hex like 0x3F for a bus
+
+Commands from a file are run before the ones on the command line, one per line.
+Lines starting with # are ignored.";
+
+enum Command {
+    Set(String, u32),
+    Step(usize),
+    Settle,
+    Print(Option<String>),
+    Expect(String, Expected),
+}
+
+enum Expected {
+    Signal(Signal),
+    // the digits of a bus value, uppercase and without leading zeros
+    Hex(String),
+}
+
+impl Expected {
+    fn parse(s: &str) -> Result<Expected, String> {
+        match s.strip_prefix("0x") {
+            Some(digits) if !digits.is_empty() => {
+                let digits = digits.to_uppercase();
+                if digits
+                    .chars()
+                    .all(|c| c.is_ascii_hexdigit() || c == 'Z' || c == 'X')
+                {
+                    Ok(Expected::Hex(digits.trim_start_matches('0').to_string()))
+                } else {
+                    Err(format!("invalid bus value \"{}\"", s))
+                }
+            }
+            _ => s.parse().map(Expected::Signal),
+        }
+    }
+
+    fn matches(&self, state: Bus) -> bool {
+        match self {
+            Expected::Signal(signal) => state.width() == 1 && state.bit(0) == *signal,
+            Expected::Hex(digits) => {
+                let state = state.to_string();
+                let state = state.strip_prefix("0x").unwrap_or(&state);
+                state.trim_start_matches('0') == digits
+            }
+        }
+    }
+}
+
+// switches take 0 or 1 and DIP switches any number
+fn parse_value(s: &str) -> Result<u32, String> {
+    let value = match s {
+        "true" | "on" | "high" => Ok(1),
+        "false" | "off" | "low" => Ok(0),
+        _ => match s.strip_prefix("0x") {
+            Some(digits) => u32::from_str_radix(digits, 16),
+            None => s.parse(),
+        },
+    };
+    value.map_err(|_| format!("expected a number, got \"{}\"", s))
+}
+
+const COMMANDS: &[&str] = &["set", "step", "settle", "print", "expect"];
+
+fn parse_commands(words: &[String]) -> Result<Vec<Command>, String> {
+    let mut words = words.iter().peekable();
+    let mut commands = Vec::new();
+
+    fn arg<'a>(
+        words: &mut impl Iterator<Item = &'a String>,
+        command: &str,
+    ) -> Result<&'a String, String> {
+        words
+            .next()
+            .ok_or_else(|| format!("missing argument for {}", command))
+    }
+
+    while let Some(word) = words.next() {
+        let command = match word.as_str() {
+            "set" => {
+                let name = arg(&mut words, "set")?.clone();
+                Command::Set(name, parse_value(arg(&mut words, "set")?)?)
+            }
+            "step" => {
+                let n = arg(&mut words, "step")?;
+                Command::Step(
+                    n.parse()
+                        .map_err(|_| format!("invalid number of ticks \"{}\"", n))?,
+                )
+            }
+            "settle" => Command::Settle,
+            // the wire name is optional so the next word is only taken if it isn't a command
+            "print" => Command::Print(words.next_if(|w| !COMMANDS.contains(&w.as_str())).cloned()),
+            "expect" => {
+                let name = arg(&mut words, "expect")?.clone();
+                Command::Expect(name, Expected::parse(arg(&mut words, "expect")?)?)
+            }
+            other => return Err(format!("unknown command \"{}\"", other)),
+        };
+        commands.push(command);
+    }
+
+    Ok(commands)
+}
+
+fn load(sim: &mut Simulator, path: &str) -> Result<(), String> {
+    if path.ends_with(".rhai") {
+        let script = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        sim.load_script(&script).map_err(|e| e.to_string())
+    } else {
+        sim.load_file(path).map_err(|e| e.to_string())
+    }
+}
+
+fn run() -> Result<bool, String> {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() || args[0] == "-h" || args[0] == "--help" {
+        println!("{}", USAGE);
+        return Ok(true);
+    }
+    let circuit_path = args.remove(0);
+
+    let mut words = Vec::new();
+    if let Some(i) = args.iter().position(|arg| arg == "--commands") {
+        let path = args
+            .get(i + 1)
+            .cloned()
+            .ok_or("missing file for --commands")?;
+        args.drain(i..=i + 1);
+
+        let file = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+        file.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(str::split_whitespace)
+            .for_each(|word| words.push(word.to_string()));
+    }
+    words.extend(args);
+
+    let commands = parse_commands(&words)?;
+
+    let mut sim = Simulator::new();
+    load(&mut sim, &circuit_path).map_err(|e| format!("{}: {}", circuit_path, e))?;
+
+    let wire_state = |sim: &Simulator, name: &str| {
+        sim.wire_state(name)
+            .ok_or_else(|| format!("no wire named \"{}\"", name))
+    };
+
+    let mut passed = true;
+    for command in commands {
+        match command {
+            Command::Set(name, value) => {
+                let found = sim.set_number(&name, value)
+                    || (value <= 1 && sim.set_switch(&name, value == 1));
+                if !found {
+                    return Err(format!(
+                        "no switch, button or DIP switch named \"{}\"",
+                        name
+                    ));
+                }
+            }
+            Command::Step(n) => sim.step(n),
+            Command::Settle => {
+                if sim.run_until_stable(SETTLE_LIMIT).is_none() {
+                    println!(
+                        "FAIL tick {}: still changing after {} ticks",
+                        sim.ticks(),
+                        SETTLE_LIMIT
+                    );
+                    passed = false;
+                }
+            }
+            Command::Print(Some(name)) => {
+                println!("{} = {}", name, wire_state(&sim, &name)?);
+            }
+            Command::Print(None) => {
+                let mut wires = sim.named_wires();
+                wires.sort_by(|(a, _), (b, _)| a.cmp(b));
+                println!("tick {}:", sim.ticks());
+                for (name, state) in wires {
+                    println!("    {} = {}", name, state);
+                }
+            }
+            Command::Expect(name, expected) => {
+                let state = wire_state(&sim, &name)?;
+                if !expected.matches(state) {
+                    let expected = match expected {
+                        Expected::Signal(signal) => signal.to_string(),
+                        Expected::Hex(digits) => format!("0x{}", digits),
+                    };
+                    println!(
+                        "FAIL tick {}: expected {} = {}, got {}",
+                        sim.ticks(),
+                        name,
+                        expected,
+                        state
+                    );
+                    passed = false;
+                }
+            }
+        }
+    }
+
+    Ok(passed)
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(2);
+        }
+    }
+}
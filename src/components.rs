@@ -1,13 +1,46 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashSet;
 
-use macroquad::prelude::Vec2;
+use glam::Vec2;
 use specs::{prelude::*, Component};
 
+pub mod bus;
 pub mod nodes;
-
-pub trait Node<const I: usize, const O: usize>: Default {
-    fn calculate_state(&self, inputs: [bool; I]) -> [bool; O];
+pub mod signal;
+
+pub use bus::Bus;
+pub use signal::Signal;
+
+pub trait Node<const I: usize, const O: usize>: Default + Serialize + DeserializeOwned {
+    /// Nodes which remember something between ticks (latches, flip-flops) update it here. It can
+    /// be called again with the same inputs, e.g. whenever every node is re-evaluated, so state
+    /// should only change on a change of input like a clock edge.
+    fn calculate_state(&mut self, inputs: [Signal; I]) -> [Signal; O];
+    /// Nodes with bus connections override this, the default works on bit 0 of every input
+    fn calculate_bus_state(&mut self, inputs: [Bus; I]) -> [Bus; O] {
+        self.calculate_state(inputs.map(|bus| bus.bit(0)))
+            .map(Bus::from)
+    }
+    /// Puts any state held by the node back to how it starts, called by "Restart Sim". Settings
+    /// chosen by the user, like a switch being on, are kept.
+    fn reset(&mut self) {}
+    /// Nodes which hold state break combinational loops, their outputs only change when they
+    /// decide to (e.g. on a clock edge). Their state is also saved for stepping back.
+    fn is_sequential() -> bool {
+        false
+    }
+    /// Ticks from an input changing to the output changing, can be overridden per node with
+    /// the Delay component
+    fn delay() -> u32 {
+        1
+    }
+    /// Width of the bus expected on each connection, 0 accepts any width
+    fn input_widths() -> [usize; I] {
+        [1; I]
+    }
+    fn output_widths() -> [usize; O] {
+        [1; O]
+    }
     fn input_offsets() -> [Vec2; I] {
         [Vec2::new(0.0, 0.0); I]
     }
@@ -30,12 +63,16 @@ impl<N, const I: usize, const O: usize> Connected<N, I, O>
 where
     N: Node<I, O> + 'static,
 {
-    pub fn calculate_state(&self, inputs: [bool; I]) -> [bool; O] {
+    pub fn calculate_state(&mut self, inputs: [Signal; I]) -> [Signal; O] {
         self.node.calculate_state(inputs)
     }
+
+    pub fn calculate_bus_state(&mut self, inputs: [Bus; I]) -> [Bus; O] {
+        self.node.calculate_bus_state(inputs)
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum Orientation {
     Up,
     Down,
@@ -43,9 +80,10 @@ pub enum Orientation {
     Right,
 }
 
-#[derive(Component, Clone, Copy)]
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
 pub struct Pos {
     pub orientation: Orientation,
+    #[serde(with = "crate::save_load::vec2")]
     pub pos: Vec2,
 }
 
@@ -72,13 +110,15 @@ impl Pos {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConnectionTy {
     Input,
     Output,
 }
 
+// flagged so the scheduler knows when to rebuild its fanout index
 #[derive(Clone, Component)]
+#[storage(FlaggedStorage)]
 pub struct Connection {
     pub wires: Vec<Entity>,
     pub ty: ConnectionTy,
@@ -89,6 +129,9 @@ pub struct Connection {
 pub struct CompoundNode {
     pub inner: HashSet<Entity>,
     pub name: String,
+    /// Connections on the outside of a placed compound node leading to its inner circuit
+    pub inputs: Vec<Entity>,
+    pub outputs: Vec<Entity>,
 }
 
 #[derive(Clone, Component)]
@@ -99,5 +142,34 @@ pub struct InnerNode {
 #[derive(Copy, Clone, Component)]
 pub struct NodeMarker;
 
+/// Propagation delay of a single node in ticks, replacing the one for its type
+#[derive(Copy, Clone, Component, Serialize, Deserialize)]
+pub struct Delay(pub u32);
+
+/// Key toggling a switch or holding down a button, an uppercase letter or a digit
+#[derive(Copy, Clone, Component, Serialize, Deserialize)]
+pub struct KeyBinding(pub char);
+
+impl KeyBinding {
+    /// None for keys which can't be bound, P is left out since it pauses the simulation
+    pub fn new(key: char) -> Option<KeyBinding> {
+        let key = key.to_ascii_uppercase();
+        (key.is_ascii_alphanumeric() && key != 'P').then_some(KeyBinding(key))
+    }
+}
+
+/// Name of a wire or node, used to refer to it from scripts and the circuit runner
+#[derive(Clone, Component, Serialize, Deserialize)]
+pub struct Name(pub String);
+
 #[derive(Copy, Clone, Component)]
 pub struct CurrentScope;
+
+/// Set by ElectroSys on nodes whose inputs can't be read properly
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Component)]
+pub enum NodeWarning {
+    UnconnectedInput,
+    ConflictingInput,
+    /// An input is driven by a bus of a different width than the node expects
+    WidthMismatch,
+}
@@ -0,0 +1,115 @@
+use super::Signal;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_BUS_WIDTH: usize = 32;
+
+/// The value carried by a wire, one signal per bit. Ordinary wires are one bit wide.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Bus {
+    width: u8,
+    bits: [Signal; MAX_BUS_WIDTH],
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Bus::from(Signal::default())
+    }
+}
+
+impl Bus {
+    /// A bus with every bit set to the signal
+    pub fn new(width: usize, signal: Signal) -> Self {
+        assert!(
+            (1..=MAX_BUS_WIDTH).contains(&width),
+            "Invalid bus width {}",
+            width
+        );
+        Bus {
+            width: width as u8,
+            bits: [signal; MAX_BUS_WIDTH],
+        }
+    }
+
+    pub fn from_bits(bits: &[Signal]) -> Self {
+        let mut bus = Bus::new(bits.len(), Signal::Z);
+        bus.bits[..bits.len()].copy_from_slice(bits);
+        bus
+    }
+
+    pub fn from_u64(value: u64, width: usize) -> Self {
+        let mut bus = Bus::new(width, Signal::Low);
+        (0..width).for_each(|i| bus.bits[i] = Signal::from(value >> i & 1 == 1));
+        bus
+    }
+
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    /// Bit 0 is the least significant, bits past the width read as Z
+    pub fn bit(&self, i: usize) -> Signal {
+        if i < self.width() {
+            self.bits[i]
+        } else {
+            Signal::Z
+        }
+    }
+
+    pub fn set_bit(&mut self, i: usize, signal: Signal) {
+        let width = self.width();
+        self.bits[..width][i] = signal;
+    }
+
+    pub fn bits(&self) -> &[Signal] {
+        &self.bits[..self.width()]
+    }
+
+    /// The value of the bus if every bit is 0 or 1
+    pub fn to_u64(&self) -> Option<u64> {
+        self.bits()
+            .iter()
+            .enumerate()
+            .try_fold(0, |acc, (i, bit)| Some(acc | (bit.to_bool()? as u64) << i))
+    }
+}
+
+// the bits past the width aren't part of the value
+impl PartialEq for Bus {
+    fn eq(&self, other: &Bus) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for Bus {}
+
+impl From<Signal> for Bus {
+    fn from(signal: Signal) -> Self {
+        Bus::new(1, signal)
+    }
+}
+
+// one bit buses print as their signal, wider ones in hex with a Z or X for each digit which
+// isn't fully known
+impl std::fmt::Display for Bus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.width() == 1 {
+            return write!(f, "{}", self.bits[0]);
+        }
+
+        write!(f, "0x")?;
+        for digit in (0..self.width().div_ceil(4)).rev() {
+            let bits = &self.bits()[digit * 4..self.width().min(digit * 4 + 4)];
+            let value = bits
+                .iter()
+                .enumerate()
+                .try_fold(0, |acc, (i, bit)| Some(acc | (bit.to_bool()? as u32) << i));
+
+            match value {
+                Some(value) => write!(f, "{:X}", value)?,
+                None if bits.iter().all(|bit| *bit == Signal::Z) => write!(f, "Z")?,
+                None => write!(f, "X")?,
+            }
+        }
+        Ok(())
+    }
+}
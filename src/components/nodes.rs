@@ -1,26 +1,59 @@
-use super::Node;
+use super::bus::MAX_BUS_WIDTH;
+use super::{Bus, Connected, Node, Signal};
+use crate::systems::simulation_systems::ClockSys;
 use crate::systems::simulation_systems::ElectroSys;
+use crate::systems::simulation_systems::HistorySys;
+use crate::systems::simulation_systems::NodeHistorySys;
+use crate::systems::simulation_systems::OscillationSys;
+use crate::systems::simulation_systems::ResolveDriversSys;
+use crate::systems::simulation_systems::ScheduleSys;
+use crate::systems::simulation_systems::TimeWheelSys;
 use crate::systems::simulation_systems::WireSys;
-use macroquad::prelude::Vec2;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
 use specs::{prelude::*, Component};
 
-#[derive(Component, Default, Clone)]
+#[derive(Component, Default, Clone, Serialize, Deserialize)]
 pub struct Wire {
-    pub input_state: bool,
-    pub output_state: bool,
+    pub input_state: Bus,
+    pub output_state: Bus,
     pub changed_input: bool,
+    // the last state driven onto the wire by each output connection it's attached to,
+    // outputs at Z don't take part in deciding the wire's state
+    #[serde(skip)]
+    pub drivers: Vec<(Entity, Bus)>,
+    // set when drivers disagree under DriverPolicy::Error, telling a short circuit apart from
+    // an X that was only passed along
+    #[serde(skip)]
+    pub conflict: bool,
+    #[serde(with = "crate::save_load::vec2")]
     pub start_point: Vec2,
+    #[serde(with = "crate::save_load::vec2")]
     pub end_point: Vec2,
+    #[serde(with = "crate::save_load::vec2_vec")]
     pub points: Vec<Vec2>,
 }
 
 impl Node<1, 1> for Wire {
-    fn calculate_state(&self, i: [bool; 1]) -> [bool; 1] {
+    fn calculate_state(&mut self, i: [Signal; 1]) -> [Signal; 1] {
         i
     }
+
+    // connection nodes pass buses through whole
+    fn calculate_bus_state(&mut self, i: [Bus; 1]) -> [Bus; 1] {
+        i
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [0]
+    }
+
+    fn output_widths() -> [usize; 1] {
+        [0]
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum NodeTy {
     Wire,
     OnNode,
@@ -33,28 +66,105 @@ pub enum NodeTy {
     XorNode,
     XnorNode,
     SwitchNode,
+    TriStateNode,
+    Splitter4Node,
+    Splitter8Node,
+    Merger4Node,
+    Merger8Node,
+    ClockNode,
+    SrLatchNode,
+    DLatchNode,
+    DFlipFlopNode,
+    JkFlipFlopNode,
+    TFlipFlopNode,
+    RomNode,
+    RamNode,
+    LedNode,
+    SevenSegmentNode,
+    HexDigitNode,
+    LedMatrixNode,
+    ButtonNode,
+    DipSwitchNode,
+    And3Node,
+    And4Node,
+    And5Node,
+    And6Node,
+    And7Node,
+    And8Node,
+    Or3Node,
+    Or4Node,
+    Or5Node,
+    Or6Node,
+    Or7Node,
+    Or8Node,
+    Nand3Node,
+    Nand4Node,
+    Nand5Node,
+    Nand6Node,
+    Nand7Node,
+    Nand8Node,
+    Nor3Node,
+    Nor4Node,
+    Nor5Node,
+    Nor6Node,
+    Nor7Node,
+    Nor8Node,
+    Xor3Node,
+    Xor4Node,
+    Xor5Node,
+    Xor6Node,
+    Xor7Node,
+    Xor8Node,
+    Xnor3Node,
+    Xnor4Node,
+    Xnor5Node,
+    Xnor6Node,
+    Xnor7Node,
+    Xnor8Node,
+    HalfAdderNode,
+    FullAdderNode,
+    AdderNode,
+    ComparatorNode,
+    Mux2Node,
+    Mux4Node,
+    Mux8Node,
+    Demux2Node,
+    Demux4Node,
+    Demux8Node,
+    Decoder4Node,
+    Decoder8Node,
+    Encoder4Node,
+    Encoder8Node,
+    Register4Node,
+    Register8Node,
+    Counter4Node,
+    Counter8Node,
+    ShiftRegister4Node,
+    ShiftRegister8Node,
+    InputPortNode,
+    OutputPortNode,
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct OnNode;
 impl Node<0, 1> for OnNode {
-    fn calculate_state(&self, _: [bool; 0]) -> [bool; 1] {
-        [true]
+    fn calculate_state(&mut self, _: [Signal; 0]) -> [Signal; 1] {
+        [Signal::High]
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct OffNode;
 impl Node<0, 1> for OffNode {
-    fn calculate_state(&self, _: [bool; 0]) -> [bool; 1] {
-        [false]
+    fn calculate_state(&mut self, _: [Signal; 0]) -> [Signal; 1] {
+        [Signal::Low]
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct NotNode;
 impl Node<1, 1> for NotNode {
-    fn calculate_state(&self, input: [bool; 1]) -> [bool; 1] {
+    fn calculate_state(&mut self, input: [Signal; 1]) -> [Signal; 1] {
         [!input[0]]
     }
 
@@ -67,15 +177,33 @@ impl Node<1, 1> for NotNode {
     }
 }
 
-#[derive(Default)]
-pub struct AndNode;
-impl Node<2, 1> for AndNode {
-    fn calculate_state(&self, input: [bool; 2]) -> [bool; 1] {
-        [input[0] && input[1]]
+/// Most inputs a gate can have. A node's pins are fixed by its type, so each count from 2 up to
+/// this is a separate node type (`And3Node` and so on) in `all_nodes` and `NodeTy`, and going
+/// higher means adding types for the new counts there.
+pub const MAX_GATE_INPUTS: usize = 8;
+
+/// Height spanned by the inputs of a gate, 40 for two inputs and 20 between each input past that
+pub fn gate_height(inputs: usize) -> f32 {
+    (inputs.saturating_sub(1) as f32 * 20.0).max(40.0)
+}
+
+// inputs of gates are spread evenly over the gate's height with input 0 at the bottom
+fn gate_input_offsets<const N: usize>() -> [Vec2; N] {
+    let height = gate_height(N);
+    let spacing = height / (N.max(2) - 1) as f32;
+    std::array::from_fn(|i| Vec2::new(-35.0, i as f32 * spacing - height / 2.0))
+}
+
+/// An N input and gate
+#[derive(Default, Serialize, Deserialize)]
+pub struct AndNode<const N: usize = 2> {}
+impl<const N: usize> Node<N, 1> for AndNode<N> {
+    fn calculate_state(&mut self, input: [Signal; N]) -> [Signal; 1] {
+        [input.iter().copied().reduce(|a, b| a & b).unwrap()]
     }
 
-    fn input_offsets() -> [Vec2; 2] {
-        [Vec2::new(-35.0, -20.0), Vec2::new(-35.0, 20.0)]
+    fn input_offsets() -> [Vec2; N] {
+        gate_input_offsets()
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -83,15 +211,16 @@ impl Node<2, 1> for AndNode {
     }
 }
 
-#[derive(Default)]
-pub struct OrNode;
-impl Node<2, 1> for OrNode {
-    fn calculate_state(&self, input: [bool; 2]) -> [bool; 1] {
-        [input[0] || input[1]]
+/// An N input or gate
+#[derive(Default, Serialize, Deserialize)]
+pub struct OrNode<const N: usize = 2> {}
+impl<const N: usize> Node<N, 1> for OrNode<N> {
+    fn calculate_state(&mut self, input: [Signal; N]) -> [Signal; 1] {
+        [input.iter().copied().reduce(|a, b| a | b).unwrap()]
     }
 
-    fn input_offsets() -> [Vec2; 2] {
-        [Vec2::new(-35.0, -20.0), Vec2::new(-35.0, 20.0)]
+    fn input_offsets() -> [Vec2; N] {
+        gate_input_offsets()
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -99,15 +228,16 @@ impl Node<2, 1> for OrNode {
     }
 }
 
-#[derive(Default)]
-pub struct NandNode;
-impl Node<2, 1> for NandNode {
-    fn calculate_state(&self, input: [bool; 2]) -> [bool; 1] {
-        [!(input[0] && input[1])]
+/// An N input nand gate
+#[derive(Default, Serialize, Deserialize)]
+pub struct NandNode<const N: usize = 2> {}
+impl<const N: usize> Node<N, 1> for NandNode<N> {
+    fn calculate_state(&mut self, input: [Signal; N]) -> [Signal; 1] {
+        [!input.iter().copied().reduce(|a, b| a & b).unwrap()]
     }
 
-    fn input_offsets() -> [Vec2; 2] {
-        [Vec2::new(-35.0, -20.0), Vec2::new(-35.0, 20.0)]
+    fn input_offsets() -> [Vec2; N] {
+        gate_input_offsets()
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -115,15 +245,16 @@ impl Node<2, 1> for NandNode {
     }
 }
 
-#[derive(Default)]
-pub struct NorNode;
-impl Node<2, 1> for NorNode {
-    fn calculate_state(&self, input: [bool; 2]) -> [bool; 1] {
-        [!(input[0] || input[1])]
+/// An N input nor gate
+#[derive(Default, Serialize, Deserialize)]
+pub struct NorNode<const N: usize = 2> {}
+impl<const N: usize> Node<N, 1> for NorNode<N> {
+    fn calculate_state(&mut self, input: [Signal; N]) -> [Signal; 1] {
+        [!input.iter().copied().reduce(|a, b| a | b).unwrap()]
     }
 
-    fn input_offsets() -> [Vec2; 2] {
-        [Vec2::new(-35.0, -20.0), Vec2::new(-35.0, 20.0)]
+    fn input_offsets() -> [Vec2; N] {
+        gate_input_offsets()
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -131,15 +262,16 @@ impl Node<2, 1> for NorNode {
     }
 }
 
-#[derive(Default)]
-pub struct XorNode;
-impl Node<2, 1> for XorNode {
-    fn calculate_state(&self, input: [bool; 2]) -> [bool; 1] {
-        [input[0] ^ input[1]]
+/// An N input xor gate, high when an odd number of inputs are high
+#[derive(Default, Serialize, Deserialize)]
+pub struct XorNode<const N: usize = 2> {}
+impl<const N: usize> Node<N, 1> for XorNode<N> {
+    fn calculate_state(&mut self, input: [Signal; N]) -> [Signal; 1] {
+        [input.iter().copied().reduce(|a, b| a ^ b).unwrap()]
     }
 
-    fn input_offsets() -> [Vec2; 2] {
-        [Vec2::new(-35.0, -20.0), Vec2::new(-35.0, 20.0)]
+    fn input_offsets() -> [Vec2; N] {
+        gate_input_offsets()
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -147,15 +279,16 @@ impl Node<2, 1> for XorNode {
     }
 }
 
-#[derive(Default)]
-pub struct XnorNode;
-impl Node<2, 1> for XnorNode {
-    fn calculate_state(&self, input: [bool; 2]) -> [bool; 1] {
-        [!(input[0] ^ input[1])]
+/// An N input xnor gate, high when an even number of inputs are high
+#[derive(Default, Serialize, Deserialize)]
+pub struct XnorNode<const N: usize = 2> {}
+impl<const N: usize> Node<N, 1> for XnorNode<N> {
+    fn calculate_state(&mut self, input: [Signal; N]) -> [Signal; 1] {
+        [!input.iter().copied().reduce(|a, b| a ^ b).unwrap()]
     }
 
-    fn input_offsets() -> [Vec2; 2] {
-        [Vec2::new(-35.0, -20.0), Vec2::new(-35.0, 20.0)]
+    fn input_offsets() -> [Vec2; N] {
+        gate_input_offsets()
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -163,14 +296,14 @@ impl Node<2, 1> for XnorNode {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct SwitchNode {
     pub state: bool,
 }
 
 impl Node<0, 1> for SwitchNode {
-    fn calculate_state(&self, _input: [bool; 0]) -> [bool; 1] {
-        [self.state]
+    fn calculate_state(&mut self, _input: [Signal; 0]) -> [Signal; 1] {
+        [Signal::from(self.state)]
     }
 
     fn output_offsets() -> [Vec2; 1] {
@@ -178,33 +311,1648 @@ impl Node<0, 1> for SwitchNode {
     }
 }
 
-#[macro_export]
-macro_rules! all_nodes {
-    ($macro:ident) => {
-        $macro!(
-            [OnNode, 0, 1],
-            [OffNode, 0, 1],
-            [Wire, 1, 1],
-            [NotNode, 1, 1],
-            [AndNode, 2, 1],
-            [OrNode, 2, 1],
-            [NandNode, 2, 1],
-            [NorNode, 2, 1],
-            [XorNode, 2, 1],
-            [XnorNode, 2, 1],
-            [SwitchNode, 0, 1],
-        )
+/// High only while it's held down, with the mouse or its key
+#[derive(Default, Serialize, Deserialize)]
+pub struct ButtonNode {
+    // set by ButtonSys every frame
+    #[serde(skip)]
+    pub pressed: bool,
+}
+
+impl Node<0, 1> for ButtonNode {
+    fn calculate_state(&mut self, _input: [Signal; 0]) -> [Signal; 1] {
+        [Signal::from(self.pressed)]
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(35.0, 0.0)]
+    }
+}
+
+/// Number of switches on a DIP switch node
+pub const DIP_SWITCH_BITS: usize = 8;
+
+/// A row of switches set together by typing in a number, each bit of the number driving
+/// its own output with bit 0 at the top
+#[derive(Default, Serialize, Deserialize)]
+pub struct DipSwitchNode {
+    pub value: u32,
+}
+
+impl Node<0, DIP_SWITCH_BITS> for DipSwitchNode {
+    fn calculate_state(&mut self, _input: [Signal; 0]) -> [Signal; DIP_SWITCH_BITS] {
+        std::array::from_fn(|i| Signal::from(self.value >> i & 1 == 1))
+    }
+
+    fn output_offsets() -> [Vec2; DIP_SWITCH_BITS] {
+        std::array::from_fn(|i| bit_offset(35.0, i, DIP_SWITCH_BITS))
+    }
+}
+
+/// Square wave source, high for the first `duty` fraction of every `period` ticks. The first
+/// period starts at tick `phase`.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockNode {
+    pub period: u32,
+    pub phase: u32,
+    pub duty: f64,
+    // both set by ClockSys from the number of ticks run
+    #[serde(skip)]
+    pub state: bool,
+    #[serde(skip)]
+    pub position: u32,
+}
+
+impl Default for ClockNode {
+    fn default() -> Self {
+        ClockNode {
+            period: 20,
+            phase: 0,
+            duty: 0.5,
+            state: false,
+            position: 0,
+        }
+    }
+}
+
+impl ClockNode {
+    /// Ticks the output is high each period
+    pub fn high_ticks(&self) -> u32 {
+        (self.period as f64 * self.duty.clamp(0.0, 1.0)).round() as u32
+    }
+
+    /// Moves the clock to the given tick
+    pub fn update(&mut self, tick: u64) {
+        let period = self.period.max(1) as u64;
+        self.position = ((tick + period - self.phase as u64 % period) % period) as u32;
+        self.state = self.position < self.high_ticks();
+    }
+}
+
+impl Node<0, 1> for ClockNode {
+    fn calculate_state(&mut self, _input: [Signal; 0]) -> [Signal; 1] {
+        [Signal::from(self.state)]
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(35.0, 0.0)]
+    }
+}
+
+/// Drives its output with the data input while enable is high, otherwise leaves it at Z
+#[derive(Default, Serialize, Deserialize)]
+pub struct TriStateNode;
+impl Node<2, 1> for TriStateNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 1] {
+        let [data, enable] = input;
+        match enable.read() {
+            Signal::High => [data.read()],
+            Signal::Low => [Signal::Z],
+            _ => [Signal::X],
+        }
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        [Vec2::new(-30.0, 0.0), Vec2::new(0.0, -25.0)]
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(30.0, 0.0)]
+    }
+}
+
+// Latches and flip-flops keep their state in `q`, which both outputs (Q and not Q) are taken
+// from. Unknown inputs make the state X unless it couldn't have changed either way.
+
+/// Whether the clock went from low to high, None if it might have because an X or Z was involved
+fn rising_edge(last: Signal, clock: Signal) -> Option<bool> {
+    match (last.read(), clock.read()) {
+        (Signal::Low, Signal::High) => Some(true),
+        (Signal::High, _) | (_, Signal::Low) => Some(false),
+        _ => None,
+    }
+}
+
+/// The state after a clock edge which may or may not have happened
+fn clock_in(q: Signal, edge: Option<bool>, next: Signal) -> Signal {
+    match edge {
+        Some(true) => next,
+        Some(false) => q,
+        None if next == q => q,
+        None => Signal::X,
+    }
+}
+
+fn latch_offsets<const I: usize>() -> [Vec2; I] {
+    let mut offsets = [Vec2::new(0.0, 0.0); I];
+    offsets.iter_mut().enumerate().for_each(|(i, offset)| {
+        *offset = Vec2::new(-40.0, 20.0 - i as f32 * 40.0 / (I - 1) as f32)
+    });
+    offsets
+}
+
+fn latch_output_offsets() -> [Vec2; 2] {
+    [Vec2::new(40.0, 20.0), Vec2::new(40.0, -20.0)]
+}
+
+/// Set (S) and reset (R) inputs, setting both makes the state X
+#[derive(Default, Serialize, Deserialize)]
+pub struct SrLatchNode {
+    pub q: Signal,
+}
+impl Node<2, 2> for SrLatchNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 2] {
+        let [s, r] = input;
+        self.q = match (s.read(), r.read()) {
+            (Signal::Low, Signal::Low) => self.q,
+            (Signal::High, Signal::Low) => Signal::High,
+            (Signal::Low, Signal::High) => Signal::Low,
+            (Signal::High, Signal::High) => Signal::X,
+            (Signal::X, Signal::Low) if self.q == Signal::High => self.q,
+            (Signal::Low, Signal::X) if self.q == Signal::Low => self.q,
+            _ => Signal::X,
+        };
+        [self.q, !self.q]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// Follows the data input (D) while enable (E) is high
+#[derive(Default, Serialize, Deserialize)]
+pub struct DLatchNode {
+    pub q: Signal,
+}
+impl Node<2, 2> for DLatchNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 2] {
+        let [d, enable] = input;
+        let enabled = enable.to_bool();
+        self.q = clock_in(self.q, enabled, d.read());
+        [self.q, !self.q]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// Takes the data input (D) on the rising edge of the clock
+#[derive(Default, Serialize, Deserialize)]
+pub struct DFlipFlopNode {
+    pub q: Signal,
+    pub last_clock: Signal,
+}
+impl Node<2, 2> for DFlipFlopNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 2] {
+        let [d, clock] = input;
+        self.q = clock_in(self.q, rising_edge(self.last_clock, clock), d.read());
+        self.last_clock = clock;
+        [self.q, !self.q]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// On the rising edge of the clock J sets, K resets and both together toggle
+#[derive(Default, Serialize, Deserialize)]
+pub struct JkFlipFlopNode {
+    pub q: Signal,
+    pub last_clock: Signal,
+}
+impl Node<3, 2> for JkFlipFlopNode {
+    fn calculate_state(&mut self, input: [Signal; 3]) -> [Signal; 2] {
+        let [j, clock, k] = input;
+        let next = (j & !self.q) | (!k & self.q);
+        self.q = clock_in(self.q, rising_edge(self.last_clock, clock), next);
+        self.last_clock = clock;
+        [self.q, !self.q]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_offsets() -> [Vec2; 3] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// Toggles on the rising edge of the clock while T is high
+#[derive(Default, Serialize, Deserialize)]
+pub struct TFlipFlopNode {
+    pub q: Signal,
+    pub last_clock: Signal,
+}
+impl Node<2, 2> for TFlipFlopNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 2] {
+        let [t, clock] = input;
+        let next = self.q ^ t;
+        self.q = clock_in(self.q, rising_edge(self.last_clock, clock), next);
+        self.last_clock = clock;
+        [self.q, !self.q]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+// Registers, counters and shift registers hold W bits as the known bits in `value` and a mask of
+// the `unknown` ones. On the rising edge of the clock (>) reset (R) clears them to 0, otherwise
+// they load their next value if enable (EN) is high.
+
+fn register_value(value: u32, unknown: u32, width: usize) -> Bus {
+    let mut bus = Bus::from_u64(value as u64, width);
+    (0..width)
+        .filter(|i| unknown >> i & 1 == 1)
+        .for_each(|i| bus.set_bit(i, Signal::X));
+    bus
+}
+
+/// The high bits of a bus and a mask of its unknown bits
+fn register_bits(bus: Bus) -> (u32, u32) {
+    let mask = |signal: Option<bool>| {
+        bus.bits()
+            .iter()
+            .enumerate()
+            .filter(|(_, bit)| bit.to_bool() == signal)
+            .fold(0, |mask, (i, _)| mask | 1 << i)
     };
+    (mask(Some(true)), mask(None))
 }
 
-pub fn add_node_systems<'a, 'b>(builder: DispatcherBuilder<'a, 'b>) -> DispatcherBuilder<'a, 'b> {
-    macro_rules! add_systems {
-        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
-            builder
-                .with(WireSys, "wire_sys", &[])
+/// `high` or `low` depending on the signal, if it's unknown the bits where they differ are X
+fn choose(signal: Signal, high: Bus, low: Bus) -> Bus {
+    match signal.read() {
+        Signal::High => high,
+        Signal::Low => low,
+        _ => {
+            let bits = high.bits().iter().zip(low.bits());
+            let bits = bits.map(|(h, l)| if h == l { *h } else { Signal::X });
+            Bus::from_bits(&bits.collect::<Vec<_>>())
+        }
+    }
+}
+
+/// The value after a clock edge, which might not have happened, with the reset and enable inputs
+fn clock_in_register(
+    value: Bus,
+    edge: Option<bool>,
+    enable: Signal,
+    reset: Signal,
+    next: Bus,
+) -> Bus {
+    let edge = edge.map_or(Signal::X, Signal::from);
+    let cleared = Bus::new(value.width(), Signal::Low);
+    choose(
+        edge,
+        choose(reset, cleared, choose(enable, next, value)),
+        value,
+    )
+}
+
+/// Loads the data input (D) on the clock edge
+#[derive(Default, Serialize, Deserialize)]
+pub struct RegisterNode<const W: usize> {
+    pub value: u32,
+    pub unknown: u32,
+    pub last_clock: Signal,
+}
+
+impl<const W: usize> RegisterNode<W> {
+    pub fn value(&self) -> Bus {
+        register_value(self.value, self.unknown, W)
+    }
+}
+
+impl<const W: usize> Node<4, 1> for RegisterNode<W> {
+    fn calculate_state(&mut self, input: [Signal; 4]) -> [Signal; 1] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 4]) -> [Bus; 1] {
+        let [data, enable, clock, reset] = input;
+        let edge = rising_edge(self.last_clock, clock.bit(0));
+        self.last_clock = clock.bit(0);
+
+        // a narrower data bus leaves the top bits unknown
+        let data = Bus::from_bits(&(0..W).map(|i| data.bit(i).read()).collect::<Vec<_>>());
+        let value = clock_in_register(self.value(), edge, enable.bit(0), reset.bit(0), data);
+        (self.value, self.unknown) = register_bits(value);
+        [value]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_widths() -> [usize; 4] {
+        [W, 1, 1, 1]
+    }
+
+    fn output_widths() -> [usize; 1] {
+        [W]
+    }
+
+    fn input_offsets() -> [Vec2; 4] {
+        std::array::from_fn(|i| bit_offset(-50.0, i, 4))
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(50.0, 0.0)]
+    }
+}
+
+/// Counts up on the clock edge while the UP input is high and down while it's low, wrapping
+/// around. The carry (C) is high while enabled at the last count before wrapping, for chaining
+/// counters.
+#[derive(Default, Serialize, Deserialize)]
+pub struct CounterNode<const W: usize> {
+    pub value: u32,
+    pub unknown: u32,
+    pub last_clock: Signal,
+}
+
+impl<const W: usize> CounterNode<W> {
+    pub fn value(&self) -> Bus {
+        register_value(self.value, self.unknown, W)
+    }
+}
+
+impl<const W: usize> Node<4, 2> for CounterNode<W> {
+    fn calculate_state(&mut self, input: [Signal; 4]) -> [Signal; 2] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 4]) -> [Bus; 2] {
+        let [enable, up, clock, reset] = input.map(|bus| bus.bit(0));
+        let edge = rising_edge(self.last_clock, clock);
+        self.last_clock = clock;
+
+        let max = (1 << W) - 1;
+        let next = match self.value().to_u64() {
+            Some(count) => choose(
+                up,
+                Bus::from_u64((count + 1) & max, W),
+                Bus::from_u64(count.wrapping_sub(1) & max, W),
+            ),
+            None => Bus::new(W, Signal::X),
+        };
+        let value = clock_in_register(self.value(), edge, enable, reset, next);
+        (self.value, self.unknown) = register_bits(value);
+
+        let last = match value.to_u64() {
+            Some(count) => choose(
+                up,
+                Bus::from(Signal::from(count == max)),
+                Bus::from(Signal::from(count == 0)),
+            )
+            .bit(0),
+            None => Signal::X,
+        };
+        [value, Bus::from(enable & last)]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn output_widths() -> [usize; 2] {
+        [W, 1]
+    }
+
+    fn input_offsets() -> [Vec2; 4] {
+        std::array::from_fn(|i| bit_offset(-50.0, i, 4))
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        std::array::from_fn(|i| bit_offset(50.0, i, 2))
+    }
+}
+
+/// Shifts its bits up one on the clock edge, bit 0 takes the data input (D)
+#[derive(Default, Serialize, Deserialize)]
+pub struct ShiftRegisterNode<const W: usize> {
+    pub value: u32,
+    pub unknown: u32,
+    pub last_clock: Signal,
+}
+
+impl<const W: usize> ShiftRegisterNode<W> {
+    pub fn value(&self) -> Bus {
+        register_value(self.value, self.unknown, W)
+    }
+}
+
+impl<const W: usize> Node<4, 1> for ShiftRegisterNode<W> {
+    fn calculate_state(&mut self, input: [Signal; 4]) -> [Signal; 1] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 4]) -> [Bus; 1] {
+        let [data, enable, clock, reset] = input.map(|bus| bus.bit(0));
+        let edge = rising_edge(self.last_clock, clock);
+        self.last_clock = clock;
+
+        let old = self.value();
+        let mut shifted = Bus::new(W, data.read());
+        (1..W).for_each(|i| shifted.set_bit(i, old.bit(i - 1)));
+        let value = clock_in_register(old, edge, enable, reset, shifted);
+        (self.value, self.unknown) = register_bits(value);
+        [value]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn output_widths() -> [usize; 1] {
+        [W]
+    }
+
+    fn input_offsets() -> [Vec2; 4] {
+        std::array::from_fn(|i| bit_offset(-50.0, i, 4))
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(50.0, 0.0)]
+    }
+}
+
+// Memory nodes hold `1 << address_width` words of `data_width` bits. Words past the end of
+// `contents` read as 0, so it only needs to be as long as what's been loaded or written.
+
+/// Widest address a memory node takes, 64K words
+pub const MAX_ADDRESS_WIDTH: u32 = 16;
+
+/// The parts of ROM and RAM nodes shown and edited by the memory inspector
+pub trait Memory {
+    const NAME: &'static str;
+    fn address_width(&self) -> u32;
+    fn address_width_mut(&mut self) -> &mut u32;
+    fn data_width(&self) -> u32;
+    fn data_width_mut(&mut self) -> &mut u32;
+    /// Replaces the contents, leaving every word known
+    fn load(&mut self, contents: Vec<u32>);
+    /// Number of words stored, the rest of the memory reads as 0
+    fn stored_words(&self) -> usize;
+    /// The word stored at an address, which may have unknown bits
+    fn word(&self, address: usize) -> Bus;
+}
+
+/// Number of words in a memory with the address width
+pub fn memory_size(address_width: u32) -> usize {
+    1 << address_width.min(MAX_ADDRESS_WIDTH)
+}
+
+fn memory_word(contents: &[u32], address: usize, data_width: u32) -> Bus {
+    let value = contents.get(address).copied().unwrap_or(0);
+    Bus::from_u64(value as u64, (data_width as usize).clamp(1, MAX_BUS_WIDTH))
+}
+
+/// The address on the bus if it's known and inside the memory
+fn memory_address(address: Bus, address_width: u32) -> Option<usize> {
+    let address = address.to_u64()? as usize;
+    (address < memory_size(address_width)).then_some(address)
+}
+
+/// Read only memory, the output is the word at the address input
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RomNode {
+    pub address_width: u32,
+    pub data_width: u32,
+    pub contents: Vec<u32>,
+}
+
+impl Default for RomNode {
+    fn default() -> Self {
+        RomNode {
+            address_width: 8,
+            data_width: 8,
+            contents: Vec::new(),
+        }
+    }
+}
+
+impl Memory for RomNode {
+    const NAME: &'static str = "ROM";
+
+    fn address_width(&self) -> u32 {
+        self.address_width
+    }
+
+    fn address_width_mut(&mut self) -> &mut u32 {
+        &mut self.address_width
+    }
+
+    fn data_width(&self) -> u32 {
+        self.data_width
+    }
+
+    fn data_width_mut(&mut self) -> &mut u32 {
+        &mut self.data_width
+    }
+
+    fn load(&mut self, contents: Vec<u32>) {
+        self.contents = contents;
+    }
+
+    fn stored_words(&self) -> usize {
+        self.contents.len()
+    }
+
+    fn word(&self, address: usize) -> Bus {
+        memory_word(&self.contents, address, self.data_width)
+    }
+}
+
+impl Node<1, 1> for RomNode {
+    fn calculate_state(&mut self, input: [Signal; 1]) -> [Signal; 1] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 1]) -> [Bus; 1] {
+        let width = (self.data_width as usize).clamp(1, MAX_BUS_WIDTH);
+        match memory_address(input[0], self.address_width) {
+            Some(address) => [self.word(address)],
+            None => [Bus::new(width, Signal::X)],
+        }
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [0]
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-50.0, 0.0)]
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(50.0, 0.0)]
+    }
+}
+
+/// Memory with address (A), data (D), write enable (WE) and clock inputs. The output is the
+/// word at the address, which takes the data input on the rising edge of the clock while
+/// write enable is high. A write to an unknown address is lost. Restarting keeps the contents,
+/// the same as when the circuit is saved.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RamNode {
+    pub address_width: u32,
+    pub data_width: u32,
+    pub contents: Vec<u32>,
+    /// Bits of each word which were written while unknown, missing words have none
+    pub unknown: Vec<u32>,
+    pub last_clock: Signal,
+}
+
+impl Default for RamNode {
+    fn default() -> Self {
+        RamNode {
+            address_width: 8,
+            data_width: 8,
+            contents: Vec::new(),
+            unknown: Vec::new(),
+            last_clock: Signal::default(),
+        }
+    }
+}
+
+impl RamNode {
+    fn store(&mut self, address: usize, word: Bus) {
+        let mask = |signal: Option<bool>| {
+            word.bits()
+                .iter()
+                .enumerate()
+                .filter(|(_, bit)| bit.to_bool() == signal)
+                .fold(0, |mask, (i, _)| mask | 1 << i)
+        };
+        let value = mask(Some(true));
+        let unknown = mask(None);
+
+        if self.contents.len() <= address {
+            self.contents.resize(address + 1, 0);
+        }
+        self.contents[address] = value;
+
+        if unknown != 0 && self.unknown.len() <= address {
+            self.unknown.resize(address + 1, 0);
+        }
+        if let Some(mask) = self.unknown.get_mut(address) {
+            *mask = unknown;
+        }
+    }
+}
+
+impl Memory for RamNode {
+    const NAME: &'static str = "RAM";
+
+    fn address_width(&self) -> u32 {
+        self.address_width
+    }
+
+    fn address_width_mut(&mut self) -> &mut u32 {
+        &mut self.address_width
+    }
+
+    fn data_width(&self) -> u32 {
+        self.data_width
+    }
+
+    fn data_width_mut(&mut self) -> &mut u32 {
+        &mut self.data_width
+    }
+
+    fn load(&mut self, contents: Vec<u32>) {
+        self.contents = contents;
+        self.unknown.clear();
+    }
+
+    fn stored_words(&self) -> usize {
+        self.contents.len()
+    }
+
+    fn word(&self, address: usize) -> Bus {
+        let mut word = memory_word(&self.contents, address, self.data_width);
+        let unknown = self.unknown.get(address).copied().unwrap_or(0);
+        (0..word.width())
+            .filter(|i| unknown >> i & 1 == 1)
+            .for_each(|i| word.set_bit(i, Signal::X));
+        word
+    }
+}
+
+impl Node<4, 1> for RamNode {
+    fn calculate_state(&mut self, input: [Signal; 4]) -> [Signal; 1] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 4]) -> [Bus; 1] {
+        let [address, data, write_enable, clock] = input;
+        let width = (self.data_width as usize).clamp(1, MAX_BUS_WIDTH);
+        let address = memory_address(address, self.address_width);
+
+        let edge = rising_edge(self.last_clock, clock.bit(0));
+        self.last_clock = clock.bit(0);
+        // None if there might have been a write
+        let write = match (edge, write_enable.bit(0).read()) {
+            (Some(false), _) | (_, Signal::Low) => Some(false),
+            (Some(true), Signal::High) => Some(true),
+            _ => None,
+        };
+
+        if let (Some(address), Some(true) | None) = (address, write) {
+            // a narrower data bus leaves the top bits unknown
+            let mut word = Bus::new(width, Signal::X);
+            (0..width).for_each(|i| word.set_bit(i, data.bit(i).read()));
+            let old = self.word(address);
+            let word = match write {
+                Some(true) => word,
+                _ if word == old => old,
+                _ => Bus::new(width, Signal::X),
+            };
+            self.store(address, word);
+        }
+
+        match address {
+            Some(address) => [self.word(address)],
+            None => [Bus::new(width, Signal::X)],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_clock = Signal::default();
+    }
+
+    fn is_sequential() -> bool {
+        true
+    }
+
+    fn input_widths() -> [usize; 4] {
+        [0, 0, 1, 1]
+    }
+
+    fn input_offsets() -> [Vec2; 4] {
+        std::array::from_fn(|i| Vec2::new(-50.0, 30.0 - i as f32 * 20.0))
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(50.0, 0.0)]
+    }
+}
+
+// Output nodes have no outputs of their own, they're drawn from the state of their inputs.
+// Clicking one changes the color it lights up in.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LedColor {
+    #[default]
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    White,
+}
+
+impl LedColor {
+    pub fn next(self) -> Self {
+        match self {
+            LedColor::Red => LedColor::Green,
+            LedColor::Green => LedColor::Blue,
+            LedColor::Blue => LedColor::Yellow,
+            LedColor::Yellow => LedColor::White,
+            LedColor::White => LedColor::Red,
+        }
+    }
+}
+
+/// Output nodes which light up in a color chosen by the user
+pub trait Light {
+    fn color(&self) -> LedColor;
+    fn set_color(&mut self, color: LedColor);
+}
+
+/// Lights up while its input is high
+#[derive(Default, Serialize, Deserialize)]
+pub struct LedNode {
+    pub color: LedColor,
+}
+
+impl Light for LedNode {
+    fn color(&self) -> LedColor {
+        self.color
+    }
+
+    fn set_color(&mut self, color: LedColor) {
+        self.color = color;
+    }
+}
+
+impl Node<1, 0> for LedNode {
+    fn calculate_state(&mut self, _input: [Signal; 1]) -> [Signal; 0] {
+        []
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-35.0, 0.0)]
+    }
+}
+
+/// A digit with an input for each of the segments a to g and the decimal point, in that order
+#[derive(Default, Serialize, Deserialize)]
+pub struct SevenSegmentNode {
+    pub color: LedColor,
+}
+
+impl Light for SevenSegmentNode {
+    fn color(&self) -> LedColor {
+        self.color
+    }
+
+    fn set_color(&mut self, color: LedColor) {
+        self.color = color;
+    }
+}
+
+impl Node<8, 0> for SevenSegmentNode {
+    fn calculate_state(&mut self, _input: [Signal; 8]) -> [Signal; 0] {
+        []
+    }
+
+    fn input_offsets() -> [Vec2; 8] {
+        std::array::from_fn(|i| Vec2::new(-50.0, 70.0 - i as f32 * 20.0))
+    }
+}
+
+// segments a to g of each hex digit, segment a in bit 0
+const HEX_SEGMENTS: [u8; 16] = [
+    0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+/// Shows the value of a 4 bit bus as a hex digit
+#[derive(Default, Serialize, Deserialize)]
+pub struct HexDigitNode {
+    pub color: LedColor,
+}
+
+impl HexDigitNode {
+    /// The state of the segments a to g and the decimal point for the input, every segment
+    /// is X if the value isn't known
+    pub fn segments(input: Bus) -> [Signal; 8] {
+        match input.to_u64() {
+            Some(value) => {
+                let segments = HEX_SEGMENTS[value as usize & 0xF];
+                std::array::from_fn(|i| Signal::from(segments >> i & 1 == 1))
+            }
+            None => [Signal::X; 8],
+        }
+    }
+}
+
+impl Light for HexDigitNode {
+    fn color(&self) -> LedColor {
+        self.color
+    }
+
+    fn set_color(&mut self, color: LedColor) {
+        self.color = color;
+    }
+}
+
+impl Node<1, 0> for HexDigitNode {
+    fn calculate_state(&mut self, _input: [Signal; 1]) -> [Signal; 0] {
+        []
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [4]
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-50.0, 0.0)]
+    }
+}
+
+/// Side of the LED matrix in LEDs
+pub const LED_MATRIX_SIZE: usize = 8;
+
+/// A square of LEDs with a bus input for each row, bit 0 lighting the leftmost column
+#[derive(Default, Serialize, Deserialize)]
+pub struct LedMatrixNode {
+    pub color: LedColor,
+}
+
+impl Light for LedMatrixNode {
+    fn color(&self) -> LedColor {
+        self.color
+    }
+
+    fn set_color(&mut self, color: LedColor) {
+        self.color = color;
+    }
+}
+
+impl Node<LED_MATRIX_SIZE, 0> for LedMatrixNode {
+    fn calculate_state(&mut self, _input: [Signal; LED_MATRIX_SIZE]) -> [Signal; 0] {
+        []
+    }
+
+    fn input_widths() -> [usize; LED_MATRIX_SIZE] {
+        [LED_MATRIX_SIZE; LED_MATRIX_SIZE]
+    }
+
+    fn input_offsets() -> [Vec2; LED_MATRIX_SIZE] {
+        std::array::from_fn(|i| Vec2::new(-100.0, 70.0 - i as f32 * 20.0))
+    }
+}
+
+// pins of splitters and mergers are stacked with bit 0 at the top
+fn bit_offset(x: f32, bit: usize, width: usize) -> Vec2 {
+    Vec2::new(x, ((width - 1) as f32 / 2.0 - bit as f32) * 20.0)
+}
+
+/// Splits a W bit bus into its bits
+#[derive(Default, Serialize, Deserialize)]
+pub struct SplitterNode<const W: usize> {}
+impl<const W: usize> Node<1, W> for SplitterNode<W> {
+    fn calculate_state(&mut self, input: [Signal; 1]) -> [Signal; W] {
+        self.calculate_bus_state([Bus::from(input[0])])
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 1]) -> [Bus; W] {
+        std::array::from_fn(|i| Bus::from(input[0].bit(i)))
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [W]
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-15.0, 0.0)]
+    }
+
+    fn output_offsets() -> [Vec2; W] {
+        std::array::from_fn(|i| bit_offset(15.0, i, W))
+    }
+}
+
+/// Combines W single bit inputs into a bus
+#[derive(Default, Serialize, Deserialize)]
+pub struct MergerNode<const W: usize> {}
+impl<const W: usize> Node<W, 1> for MergerNode<W> {
+    fn calculate_state(&mut self, input: [Signal; W]) -> [Signal; 1] {
+        [self.calculate_bus_state(input.map(Bus::from))[0].bit(0)]
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; W]) -> [Bus; 1] {
+        [Bus::from_bits(&input.map(|bus| bus.bit(0)))]
+    }
+
+    fn output_widths() -> [usize; 1] {
+        [W]
+    }
+
+    fn input_offsets() -> [Vec2; W] {
+        std::array::from_fn(|i| bit_offset(-15.0, i, W))
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(15.0, 0.0)]
+    }
+}
+
+// Arithmetic and routing blocks. Values on their buses are unsigned and can be any width, an
+// unknown bit makes whichever outputs it could affect X.
+
+/// Adds A and B giving the sum (S) and carry (C)
+#[derive(Default, Serialize, Deserialize)]
+pub struct HalfAdderNode;
+impl Node<2, 2> for HalfAdderNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 2] {
+        let [a, b] = input;
+        [a ^ b, a & b]
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// The sum and carry out of adding two bits and a carry in
+fn full_add(a: Signal, b: Signal, carry: Signal) -> (Signal, Signal) {
+    let half = a ^ b;
+    (half ^ carry, (a & b) | (half & carry))
+}
+
+/// Adds A, B and a carry in (C) giving the sum (S) and carry out (C)
+#[derive(Default, Serialize, Deserialize)]
+pub struct FullAdderNode;
+impl Node<3, 2> for FullAdderNode {
+    fn calculate_state(&mut self, input: [Signal; 3]) -> [Signal; 2] {
+        let [a, b, carry] = input;
+        let (sum, carry) = full_add(a, b, carry);
+        [sum, carry]
+    }
+
+    fn input_offsets() -> [Vec2; 3] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// Adds buses A and B and a carry in (C). The sum (S) is as wide as the wider of A and B, with
+/// the carry out (C) of the top bit as its own output.
+#[derive(Default, Serialize, Deserialize)]
+pub struct AdderNode;
+impl Node<3, 2> for AdderNode {
+    fn calculate_state(&mut self, input: [Signal; 3]) -> [Signal; 2] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 3]) -> [Bus; 2] {
+        let [a, b, carry_in] = input;
+        // the narrower operand is padded with 0s
+        let operand_bit = |bus: Bus, i| {
+            if i < bus.width() {
+                bus.bit(i)
+            } else {
+                Signal::Low
+            }
+        };
+
+        let mut sum = Bus::new(a.width().max(b.width()), Signal::X);
+        let mut carry = carry_in.bit(0);
+        for i in 0..sum.width() {
+            let (bit, carry_out) = full_add(operand_bit(a, i), operand_bit(b, i), carry);
+            sum.set_bit(i, bit);
+            carry = carry_out;
+        }
+        [sum, Bus::from(carry)]
+    }
+
+    fn input_widths() -> [usize; 3] {
+        [0, 0, 1]
+    }
+
+    fn output_widths() -> [usize; 2] {
+        [0, 1]
+    }
+
+    fn input_offsets() -> [Vec2; 3] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// Compares buses A and B, exactly one of the outputs A < B, A = B and A > B is high
+#[derive(Default, Serialize, Deserialize)]
+pub struct ComparatorNode;
+impl Node<2, 3> for ComparatorNode {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; 3] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 2]) -> [Bus; 3] {
+        match (input[0].to_u64(), input[1].to_u64()) {
+            (Some(a), Some(b)) => [a < b, a == b, a > b].map(|out| Bus::from(Signal::from(out))),
+            _ => [Bus::from(Signal::X); 3],
+        }
+    }
+
+    fn input_widths() -> [usize; 2] {
+        [0, 0]
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; 3] {
+        std::array::from_fn(|i| bit_offset(40.0, i, 3))
+    }
+}
+
+/// Width of a select input picking one of `count` pins, which is a power of two
+pub fn select_width(count: usize) -> usize {
+    count.trailing_zeros().max(1) as usize
+}
+
+/// The pin picked by a select input, None if it's unknown or past the last pin
+fn selected(select: Bus, count: usize) -> Option<usize> {
+    let select = select.to_u64()? as usize;
+    (select < count).then_some(select)
+}
+
+/// Passes on whichever of its D data inputs the select input (S) after them picks
+#[derive(Default, Serialize, Deserialize)]
+pub struct MuxNode<const D: usize> {}
+
+// a mux has D + 1 pins, which stable Rust can't compute from D in a const generic, so each
+// data input count gets its own impl
+macro_rules! impl_mux_node {
+    ($d:expr, $i:expr) => {
+        impl Node<$i, 1> for MuxNode<$d> {
+            fn calculate_state(&mut self, input: [Signal; $i]) -> [Signal; 1] {
+                [self.calculate_bus_state(input.map(Bus::from))[0].bit(0)]
+            }
+
+            fn calculate_bus_state(&mut self, input: [Bus; $i]) -> [Bus; 1] {
+                let (data, select) = input.split_at($d);
+                [mux(data, select[0])]
+            }
+
+            fn input_widths() -> [usize; $i] {
+                std::array::from_fn(|i| if i == $d { select_width($d) } else { 0 })
+            }
+
+            fn input_offsets() -> [Vec2; $i] {
+                std::array::from_fn(|i| bit_offset(-40.0, i, $i))
+            }
+
+            fn output_offsets() -> [Vec2; 1] {
+                [Vec2::new(40.0, 0.0)]
+            }
+        }
+    };
+}
+
+impl_mux_node!(2, 3);
+impl_mux_node!(4, 5);
+impl_mux_node!(8, 9);
+
+fn mux(data: &[Bus], select: Bus) -> Bus {
+    match selected(select, data.len()) {
+        Some(i) => data[i],
+        None => {
+            let width = data.iter().map(Bus::width).max().unwrap();
+            Bus::new(width, Signal::X)
+        }
+    }
+}
+
+/// Sends the data input (D) to the output picked by the select input (S), the rest are 0
+#[derive(Default, Serialize, Deserialize)]
+pub struct DemuxNode<const O: usize> {}
+impl<const O: usize> Node<2, O> for DemuxNode<O> {
+    fn calculate_state(&mut self, input: [Signal; 2]) -> [Signal; O] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 2]) -> [Bus; O] {
+        let [data, select] = input;
+        match selected(select, O) {
+            Some(out) => std::array::from_fn(|i| {
+                if i == out {
+                    data
+                } else {
+                    Bus::new(data.width(), Signal::Low)
+                }
+            }),
+            None => [Bus::new(data.width(), Signal::X); O],
+        }
+    }
+
+    fn input_widths() -> [usize; 2] {
+        [0, select_width(O)]
+    }
+
+    fn output_widths() -> [usize; O] {
+        [0; O]
+    }
+
+    fn input_offsets() -> [Vec2; 2] {
+        latch_offsets()
+    }
+
+    fn output_offsets() -> [Vec2; O] {
+        std::array::from_fn(|i| bit_offset(40.0, i, O))
+    }
+}
+
+/// Sets the output picked by the select input (S) high and the rest low
+#[derive(Default, Serialize, Deserialize)]
+pub struct DecoderNode<const O: usize> {}
+impl<const O: usize> Node<1, O> for DecoderNode<O> {
+    fn calculate_state(&mut self, input: [Signal; 1]) -> [Signal; O] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 1]) -> [Bus; O] {
+        let out = selected(input[0], O);
+        std::array::from_fn(|i| match out {
+            Some(out) => Bus::from(Signal::from(i == out)),
+            None => Bus::from(Signal::X),
+        })
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [select_width(O)]
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-40.0, 0.0)]
+    }
+
+    fn output_offsets() -> [Vec2; O] {
+        std::array::from_fn(|i| bit_offset(40.0, i, O))
+    }
+}
+
+/// A priority encoder, the output (Y) is the number of the highest input that's high and V is
+/// high if any of them are
+#[derive(Default, Serialize, Deserialize)]
+pub struct EncoderNode<const I: usize> {}
+impl<const I: usize> Node<I, 2> for EncoderNode<I> {
+    fn calculate_state(&mut self, input: [Signal; I]) -> [Signal; 2] {
+        self.calculate_bus_state(input.map(Bus::from))
+            .map(|bus| bus.bit(0))
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; I]) -> [Bus; 2] {
+        let width = select_width(I);
+        let valid = input
+            .iter()
+            .fold(Signal::Low, |valid, bus| valid | bus.bit(0));
+        // an unknown input above the highest one that's high might be the highest
+        let highest = input
+            .iter()
+            .rposition(|bus| bus.bit(0).read() != Signal::Low);
+        let number = match highest {
+            Some(i) if input[i].bit(0).is_high() => Bus::from_u64(i as u64, width),
+            Some(_) => Bus::new(width, Signal::X),
+            None => Bus::new(width, Signal::Low),
+        };
+        [number, Bus::from(valid)]
+    }
+
+    fn output_widths() -> [usize; 2] {
+        [select_width(I), 1]
+    }
+
+    fn input_offsets() -> [Vec2; I] {
+        std::array::from_fn(|i| bit_offset(-40.0, i, I))
+    }
+
+    fn output_offsets() -> [Vec2; 2] {
+        latch_output_offsets()
+    }
+}
+
+/// The name and place in order of a compound node's input or output port
+pub trait Port {
+    const NAME: &'static str;
+    fn name_mut(&mut self) -> &mut String;
+    /// Pins are ordered by this and then from the top down
+    fn order(&self) -> u32;
+    fn order_mut(&mut self) -> &mut u32;
+}
+
+/// Passes a signal into a compound node. Placed inside one being created, its input becomes one
+/// of the compound node's input pins.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputPortNode {
+    pub name: String,
+    pub order: u32,
+}
+
+/// Passes a signal out of a compound node. Placed inside one being created, its output becomes
+/// one of the compound node's output pins.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputPortNode {
+    pub name: String,
+    pub order: u32,
+}
+
+impl Port for InputPortNode {
+    const NAME: &'static str = "Input Port";
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+
+    fn order(&self) -> u32 {
+        self.order
+    }
+
+    fn order_mut(&mut self) -> &mut u32 {
+        &mut self.order
+    }
+}
+
+// like connection nodes, buses go through whole
+impl Node<1, 1> for InputPortNode {
+    fn calculate_state(&mut self, input: [Signal; 1]) -> [Signal; 1] {
+        input
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 1]) -> [Bus; 1] {
+        input
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [0]
+    }
+
+    fn output_widths() -> [usize; 1] {
+        [0]
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-40.0, 0.0)]
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(40.0, 0.0)]
+    }
+}
+
+impl Port for OutputPortNode {
+    const NAME: &'static str = "Output Port";
+
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
+
+    fn order(&self) -> u32 {
+        self.order
+    }
+
+    fn order_mut(&mut self) -> &mut u32 {
+        &mut self.order
+    }
+}
+
+impl Node<1, 1> for OutputPortNode {
+    fn calculate_state(&mut self, input: [Signal; 1]) -> [Signal; 1] {
+        input
+    }
+
+    fn calculate_bus_state(&mut self, input: [Bus; 1]) -> [Bus; 1] {
+        input
+    }
+
+    fn input_widths() -> [usize; 1] {
+        [0]
+    }
+
+    fn output_widths() -> [usize; 1] {
+        [0]
+    }
+
+    fn input_offsets() -> [Vec2; 1] {
+        [Vec2::new(-40.0, 0.0)]
+    }
+
+    fn output_offsets() -> [Vec2; 1] {
+        [Vec2::new(40.0, 0.0)]
+    }
+}
+
+// all_nodes needs a plain identifier for every node type, so each gate input count past the
+// default of 2 gets an alias, up to MAX_GATE_INPUTS
+pub type And3Node = AndNode<3>;
+pub type And4Node = AndNode<4>;
+pub type And5Node = AndNode<5>;
+pub type And6Node = AndNode<6>;
+pub type And7Node = AndNode<7>;
+pub type And8Node = AndNode<8>;
+pub type Or3Node = OrNode<3>;
+pub type Or4Node = OrNode<4>;
+pub type Or5Node = OrNode<5>;
+pub type Or6Node = OrNode<6>;
+pub type Or7Node = OrNode<7>;
+pub type Or8Node = OrNode<8>;
+pub type Nand3Node = NandNode<3>;
+pub type Nand4Node = NandNode<4>;
+pub type Nand5Node = NandNode<5>;
+pub type Nand6Node = NandNode<6>;
+pub type Nand7Node = NandNode<7>;
+pub type Nand8Node = NandNode<8>;
+pub type Nor3Node = NorNode<3>;
+pub type Nor4Node = NorNode<4>;
+pub type Nor5Node = NorNode<5>;
+pub type Nor6Node = NorNode<6>;
+pub type Nor7Node = NorNode<7>;
+pub type Nor8Node = NorNode<8>;
+pub type Xor3Node = XorNode<3>;
+pub type Xor4Node = XorNode<4>;
+pub type Xor5Node = XorNode<5>;
+pub type Xor6Node = XorNode<6>;
+pub type Xor7Node = XorNode<7>;
+pub type Xor8Node = XorNode<8>;
+pub type Xnor3Node = XnorNode<3>;
+pub type Xnor4Node = XnorNode<4>;
+pub type Xnor5Node = XnorNode<5>;
+pub type Xnor6Node = XnorNode<6>;
+pub type Xnor7Node = XnorNode<7>;
+pub type Xnor8Node = XnorNode<8>;
+pub type Splitter4Node = SplitterNode<4>;
+pub type Splitter8Node = SplitterNode<8>;
+pub type Merger4Node = MergerNode<4>;
+pub type Merger8Node = MergerNode<8>;
+pub type Mux2Node = MuxNode<2>;
+pub type Mux4Node = MuxNode<4>;
+pub type Mux8Node = MuxNode<8>;
+pub type Demux2Node = DemuxNode<2>;
+pub type Demux4Node = DemuxNode<4>;
+pub type Demux8Node = DemuxNode<8>;
+pub type Decoder4Node = DecoderNode<4>;
+pub type Decoder8Node = DecoderNode<8>;
+pub type Encoder4Node = EncoderNode<4>;
+pub type Encoder8Node = EncoderNode<8>;
+pub type Register4Node = RegisterNode<4>;
+pub type Register8Node = RegisterNode<8>;
+pub type Counter4Node = CounterNode<4>;
+pub type Counter8Node = CounterNode<8>;
+pub type ShiftRegister4Node = ShiftRegisterNode<4>;
+pub type ShiftRegister8Node = ShiftRegisterNode<8>;
+
+#[macro_export]
+macro_rules! all_nodes {
+    ($macro:ident) => {
+        $macro!(
+            [OnNode, 0, 1],
+            [OffNode, 0, 1],
+            [Wire, 1, 1],
+            [NotNode, 1, 1],
+            [AndNode, 2, 1],
+            [OrNode, 2, 1],
+            [NandNode, 2, 1],
+            [NorNode, 2, 1],
+            [XorNode, 2, 1],
+            [XnorNode, 2, 1],
+            [And3Node, 3, 1],
+            [And4Node, 4, 1],
+            [And5Node, 5, 1],
+            [And6Node, 6, 1],
+            [And7Node, 7, 1],
+            [And8Node, 8, 1],
+            [Or3Node, 3, 1],
+            [Or4Node, 4, 1],
+            [Or5Node, 5, 1],
+            [Or6Node, 6, 1],
+            [Or7Node, 7, 1],
+            [Or8Node, 8, 1],
+            [Nand3Node, 3, 1],
+            [Nand4Node, 4, 1],
+            [Nand5Node, 5, 1],
+            [Nand6Node, 6, 1],
+            [Nand7Node, 7, 1],
+            [Nand8Node, 8, 1],
+            [Nor3Node, 3, 1],
+            [Nor4Node, 4, 1],
+            [Nor5Node, 5, 1],
+            [Nor6Node, 6, 1],
+            [Nor7Node, 7, 1],
+            [Nor8Node, 8, 1],
+            [Xor3Node, 3, 1],
+            [Xor4Node, 4, 1],
+            [Xor5Node, 5, 1],
+            [Xor6Node, 6, 1],
+            [Xor7Node, 7, 1],
+            [Xor8Node, 8, 1],
+            [Xnor3Node, 3, 1],
+            [Xnor4Node, 4, 1],
+            [Xnor5Node, 5, 1],
+            [Xnor6Node, 6, 1],
+            [Xnor7Node, 7, 1],
+            [Xnor8Node, 8, 1],
+            [SwitchNode, 0, 1],
+            [ClockNode, 0, 1],
+            [SrLatchNode, 2, 2],
+            [DLatchNode, 2, 2],
+            [DFlipFlopNode, 2, 2],
+            [JkFlipFlopNode, 3, 2],
+            [TFlipFlopNode, 2, 2],
+            [Register4Node, 4, 1],
+            [Register8Node, 4, 1],
+            [Counter4Node, 4, 2],
+            [Counter8Node, 4, 2],
+            [ShiftRegister4Node, 4, 1],
+            [ShiftRegister8Node, 4, 1],
+            [RomNode, 1, 1],
+            [RamNode, 4, 1],
+            [LedNode, 1, 0],
+            [SevenSegmentNode, 8, 0],
+            [HexDigitNode, 1, 0],
+            [LedMatrixNode, 8, 0],
+            [ButtonNode, 0, 1],
+            [DipSwitchNode, 0, 8],
+            [TriStateNode, 2, 1],
+            [Splitter4Node, 1, 4],
+            [Splitter8Node, 1, 8],
+            [Merger4Node, 4, 1],
+            [Merger8Node, 8, 1],
+            [HalfAdderNode, 2, 2],
+            [FullAdderNode, 3, 2],
+            [AdderNode, 3, 2],
+            [ComparatorNode, 2, 3],
+            [Mux2Node, 3, 1],
+            [Mux4Node, 5, 1],
+            [Mux8Node, 9, 1],
+            [Demux2Node, 2, 2],
+            [Demux4Node, 2, 4],
+            [Demux8Node, 2, 8],
+            [Decoder4Node, 1, 4],
+            [Decoder8Node, 1, 8],
+            [Encoder4Node, 4, 2],
+            [Encoder8Node, 8, 2],
+            [InputPortNode, 1, 1],
+            [OutputPortNode, 1, 1],
+        )
+    };
+}
+
+impl NodeTy {
+    /// The delay of nodes of this type when neither the node nor the delay table sets one
+    pub fn default_delay(self) -> u32 {
+        macro_rules! default_delays {
+            ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+                match self {
+                    $(NodeTy::$node => <$node as Node<$i, $o>>::delay(),)*
+                }
+            };
+        }
+
+        all_nodes!(default_delays)
+    }
+
+    /// The type of the node on the entity, if it has one
+    pub fn of(world: &World, entity: Entity) -> Option<NodeTy> {
+        macro_rules! find_ty {
+            ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+                $(
+                    if world.read_storage::<Connected<$node, $i, $o>>().contains(entity) {
+                        return Some(NodeTy::$node);
+                    }
+                )*
+            };
+        }
+
+        all_nodes!(find_ty);
+        None
+    }
+}
+
+pub fn add_node_systems<'a, 'b>(builder: DispatcherBuilder<'a, 'b>) -> DispatcherBuilder<'a, 'b> {
+    macro_rules! add_systems {
+        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+            builder
+                .with(HistorySys, "history_sys", &[])
+                $(
+                    .with(NodeHistorySys::<$node, $i, $o>::default(), "", &["history_sys"])
+                )*
+                .with(ScheduleSys::default(), "schedule_sys", &["history_sys"])
+                .with(WireSys, "wire_sys", &["schedule_sys"])
+                .with(ClockSys, "clock_sys", &["history_sys"])
                 $(
-                    .with(ElectroSys::<$node, $i, $o>::default(), stringify!($node), &["wire_sys"])
+                    .with(
+                        ElectroSys::<$node, $i, $o>::new(NodeTy::$node),
+                        stringify!($node),
+                        &["wire_sys", "clock_sys"],
+                    )
                 )*
+                .with_barrier()
+                .with(TimeWheelSys, "time_wheel_sys", &[])
+                .with(ResolveDriversSys, "resolve_drivers_sys", &["time_wheel_sys"])
+                .with(OscillationSys, "oscillation_sys", &["resolve_drivers_sys"])
         };
     }
 
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+/// The value carried by a wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Signal {
+    #[default]
+    Low,
+    High,
+    /// High impedance, nothing is driving the wire
+    Z,
+    /// Unknown, e.g. drivers which disagree or a gate reading an undriven input
+    X,
+}
+
+impl Signal {
+    pub fn to_bool(self) -> Option<bool> {
+        match self {
+            Signal::Low => Some(false),
+            Signal::High => Some(true),
+            Signal::Z | Signal::X => None,
+        }
+    }
+
+    pub fn is_high(self) -> bool {
+        self == Signal::High
+    }
+
+    /// Whether something is driving the signal, i.e. it isn't Z
+    pub fn is_driven(self) -> bool {
+        self != Signal::Z
+    }
+
+    /// The signal as seen by a gate input, which can't tell Z apart from an unknown value
+    pub fn read(self) -> Signal {
+        match self {
+            Signal::Z => Signal::X,
+            s => s,
+        }
+    }
+}
+
+impl From<bool> for Signal {
+    fn from(b: bool) -> Self {
+        if b {
+            Signal::High
+        } else {
+            Signal::Low
+        }
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let c = match self {
+            Signal::Low => '0',
+            Signal::High => '1',
+            Signal::Z => 'Z',
+            Signal::X => 'X',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+impl std::str::FromStr for Signal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "true" | "on" | "high" => Ok(Signal::High),
+            "0" | "false" | "off" | "low" => Ok(Signal::Low),
+            "z" | "Z" => Ok(Signal::Z),
+            "x" | "X" => Ok(Signal::X),
+            _ => Err(format!("expected 0, 1, Z or X, got \"{}\"", s)),
+        }
+    }
+}
+
+impl Not for Signal {
+    type Output = Signal;
+
+    fn not(self) -> Signal {
+        match self.read() {
+            Signal::Low => Signal::High,
+            Signal::High => Signal::Low,
+            _ => Signal::X,
+        }
+    }
+}
+
+// a known controlling value wins over an unknown one, e.g. 0 & X is still 0
+impl BitAnd for Signal {
+    type Output = Signal;
+
+    fn bitand(self, rhs: Signal) -> Signal {
+        match (self.read(), rhs.read()) {
+            (Signal::Low, _) | (_, Signal::Low) => Signal::Low,
+            (Signal::High, Signal::High) => Signal::High,
+            _ => Signal::X,
+        }
+    }
+}
+
+impl BitOr for Signal {
+    type Output = Signal;
+
+    fn bitor(self, rhs: Signal) -> Signal {
+        match (self.read(), rhs.read()) {
+            (Signal::High, _) | (_, Signal::High) => Signal::High,
+            (Signal::Low, Signal::Low) => Signal::Low,
+            _ => Signal::X,
+        }
+    }
+}
+
+impl BitXor for Signal {
+    type Output = Signal;
+
+    fn bitxor(self, rhs: Signal) -> Signal {
+        match (self.to_bool(), rhs.to_bool()) {
+            (Some(a), Some(b)) => Signal::from(a ^ b),
+            _ => Signal::X,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::components::nodes::{InputPortNode, OutputPortNode, Port};
+use crate::components::{CompoundNode, Connected, InnerNode, Node, NodeMarker, Pos};
+use crate::resources::{CompoundNodeLibrary, CreatingCompoundNode};
+use crate::save_load::{self, SaveError, SavedEntity};
+use crate::systems::update_current_scope_sys::UpdateCurrentScopeSys;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+// A compound node is a circuit saved into the library under a name, which can then be placed
+// as a single block. Saving one stores its inner entities the same way as a circuit file, and
+// placing it loads a copy of them inside the block. The copy has no positions so it's never
+// drawn or clicked, but its nodes are simulated along with everything else.
+//
+// The block's pins come from the input and output port nodes at the top level of the compound
+// node, ordered by each port's `order` and then from the top down. The port's outer connection
+// is moved onto the outside of the block, so wires to a pin lead straight through the port.
+
+#[derive(Debug)]
+pub enum CompoundNodeError {
+    MissingName,
+    NoPins,
+    UnknownCompoundNode(String),
+    Save(SaveError),
+}
+
+impl std::fmt::Display for CompoundNodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompoundNodeError::MissingName => write!(f, "Compound nodes need a name"),
+            CompoundNodeError::NoPins => {
+                write!(f, "Compound node has no pins, add an input or output port")
+            }
+            CompoundNodeError::UnknownCompoundNode(name) => {
+                write!(f, "No compound node named \"{}\"", name)
+            }
+            CompoundNodeError::Save(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompoundNodeError {}
+
+impl From<SaveError> for CompoundNodeError {
+    fn from(e: SaveError) -> Self {
+        CompoundNodeError::Save(e)
+    }
+}
+
+/// The inner circuit of a saved compound node. Entities at the top level of the compound node
+/// have no `inner_node`, those inside compound nodes placed in it keep theirs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompoundNodeTemplate {
+    pub entities: Vec<SavedEntity>,
+    /// Ids of the connections used as input and output pins, in order
+    pub inputs: Vec<u32>,
+    pub outputs: Vec<u32>,
+}
+
+/// Ids of the outer connections of the ports directly inside the compound node, in pin order
+fn port_pins<N>(world: &World, parent: Entity, outer: fn(&Connected<N, 1, 1>) -> Entity) -> Vec<u32>
+where
+    N: Node<1, 1> + Port + 'static,
+{
+    let ports = world.read_storage::<Connected<N, 1, 1>>();
+    let inner_nodes = world.read_storage::<InnerNode>();
+    let positions = world.read_storage::<Pos>();
+
+    let mut pins = (&ports, &inner_nodes, &positions)
+        .join()
+        .filter(|(_, inner_node, _)| inner_node.parent == parent)
+        .map(|(port, _, Pos { pos, .. })| (port.node.order(), *pos, outer(port).id()))
+        .collect::<Vec<_>>();
+    // world space is y up
+    pins.sort_by(|(a_order, a, _), (b_order, b, _)| {
+        a_order
+            .cmp(b_order)
+            .then(b.y.total_cmp(&a.y))
+            .then(a.x.total_cmp(&b.x))
+    });
+    pins.into_iter().map(|(_, _, id)| id).collect()
+}
+
+/// Offsets of the pins on one side of a placed compound node, from the top down
+pub fn pin_offsets(x: f32, count: usize) -> impl Iterator<Item = Vec2> {
+    (0..count).map(move |i| Vec2::new(x, ((count as f32 - 1.0) / 2.0 - i as f32) * 20.0))
+}
+
+/// Half the width of a placed compound node, its pins are just outside
+pub const COMPOUND_NODE_HALF_WIDTH: f32 = 40.0;
+
+/// The entity and everything inside it, however deeply
+fn with_descendants(world: &World, entity: Entity) -> HashSet<Entity> {
+    let inner_nodes = world.read_storage::<InnerNode>();
+    let entities = world.entities();
+
+    let mut found = HashSet::new();
+    found.insert(entity);
+    loop {
+        let before = found.len();
+        for (inner_node, inner) in (&inner_nodes, &entities).join() {
+            if found.contains(&inner_node.parent) {
+                found.insert(inner);
+            }
+        }
+        if found.len() == before {
+            return found;
+        }
+    }
+}
+
+/// Moves the compound node being created into the library, replacing any saved with the same
+/// name, and returns how many input and output pins it has
+pub fn save_compound_node(
+    world: &mut World,
+    entity: Entity,
+    name: &str,
+) -> Result<(usize, usize), CompoundNodeError> {
+    if name.trim().is_empty() {
+        return Err(CompoundNodeError::MissingName);
+    }
+
+    let inner = with_descendants(world, entity);
+    let mut saved = save_load::save_entities(world, |e| e != entity && inner.contains(&e))?;
+    saved
+        .iter_mut()
+        .filter(|saved| saved.inner_node == Some(entity.id()))
+        .for_each(|saved| saved.inner_node = None);
+
+    let template = CompoundNodeTemplate {
+        entities: saved,
+        inputs: port_pins::<InputPortNode>(world, entity, |port| port.inputs[0]),
+        outputs: port_pins::<OutputPortNode>(world, entity, |port| port.outputs[0]),
+    };
+    if template.inputs.is_empty() && template.outputs.is_empty() {
+        return Err(CompoundNodeError::NoPins);
+    }
+    let pins = (template.inputs.len(), template.outputs.len());
+
+    world
+        .fetch_mut::<CompoundNodeLibrary>()
+        .0
+        .insert(name.to_string(), template);
+    world
+        .delete_entities(&inner.into_iter().collect::<Vec<_>>())
+        .unwrap();
+    world.maintain();
+    world.insert(CreatingCompoundNode(None));
+    UpdateCurrentScopeSys.run_now(world);
+
+    Ok(pins)
+}
+
+/// Places a copy of the compound node from the library, returning the block's entity
+pub fn place_compound_node(
+    world: &mut World,
+    name: &str,
+    pos: Pos,
+) -> Result<Entity, CompoundNodeError> {
+    let template = world
+        .fetch::<CompoundNodeLibrary>()
+        .0
+        .get(name)
+        .cloned()
+        .ok_or_else(|| CompoundNodeError::UnknownCompoundNode(name.to_string()))?;
+
+    // the library comes from a file, so make sure the pins exist before creating anything
+    let pin = template
+        .inputs
+        .iter()
+        .chain(template.outputs.iter())
+        .find(|id| !template.entities.iter().any(|saved| saved.id == **id));
+    if let Some(id) = pin {
+        return Err(SaveError::MissingEntity(*id).into());
+    }
+    let entity_map = save_load::load_entities(world, &template.entities)?;
+
+    // placed inside the compound node being created, if there is one
+    let parent = world
+        .fetch::<CreatingCompoundNode>()
+        .0
+        .as_ref()
+        .map(|data| data.entity);
+    let mut block = world.create_entity().with(pos).with(NodeMarker);
+    if let Some(parent) = parent {
+        block = block.with(InnerNode { parent });
+    }
+    let block = block.build();
+
+    // every pin was checked to be in the template above
+    let pins = |ids: &[u32]| ids.iter().map(|id| entity_map[id]).collect::<Vec<_>>();
+    let inputs = pins(&template.inputs);
+    let outputs = pins(&template.outputs);
+
+    {
+        let mut positions = world.write_storage::<Pos>();
+        let mut inner_nodes = world.write_storage::<InnerNode>();
+
+        for entity in entity_map.values() {
+            positions.remove(*entity);
+            if !inner_nodes.contains(*entity) {
+                inner_nodes
+                    .insert(*entity, InnerNode { parent: block })
+                    .unwrap();
+            }
+        }
+
+        let sides = [
+            (&inputs, -COMPOUND_NODE_HALF_WIDTH - 10.0),
+            (&outputs, COMPOUND_NODE_HALF_WIDTH + 10.0),
+        ];
+        for (pins, x) in sides {
+            for (pin, offset) in pins.iter().zip(pin_offsets(x, pins.len())) {
+                positions
+                    .insert(*pin, Pos::from_vec_unrounded(pos.pos + offset))
+                    .unwrap();
+                // the pins are on the outside, alongside the block
+                match parent {
+                    Some(parent) => inner_nodes.insert(*pin, InnerNode { parent }).unwrap(),
+                    None => inner_nodes.remove(*pin),
+                };
+            }
+        }
+    }
+
+    world
+        .write_storage::<CompoundNode>()
+        .insert(
+            block,
+            CompoundNode {
+                inner: entity_map.values().copied().collect(),
+                name: name.to_string(),
+                inputs,
+                outputs,
+            },
+        )
+        .unwrap();
+    UpdateCurrentScopeSys.run_now(world);
+
+    Ok(block)
+}
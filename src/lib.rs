@@ -0,0 +1,18 @@
+pub mod analysis;
+pub mod components;
+pub mod compound;
+pub mod memory_file;
+pub mod resources;
+pub mod save_load;
+pub mod scripting;
+pub mod simulator;
+#[cfg(feature = "gui")]
+pub mod svg;
+pub mod systems;
+#[cfg(feature = "gui")]
+pub mod ui;
+
+pub use components::{nodes, nodes::Wire, Bus, Connected, Pos, Signal};
+pub use resources::{CompoundNodeData, UiSignal};
+pub use simulator::Simulator;
+pub use systems::simulation_systems::ResetSys;
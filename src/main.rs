@@ -1,36 +1,25 @@
-use crate::nodes::Wire;
-use crate::resources::CompoundNodeData;
-use crate::resources::UiSignal;
 use macroquad::prelude::*;
-use resources::CameraRes;
+use simple_electronics::resources::{self, CompoundNodeData, UiSignal};
+use simple_electronics::simulator::RunProgress;
+use simple_electronics::ui::resources::{CameraRes, Textures};
+use simple_electronics::{
+    analysis, components, compound, save_load, scripting, svg, systems, ui, Simulator,
+};
 use specs::prelude::*;
 
-mod components;
-mod resources;
-mod scripting;
-mod svg;
-mod systems;
-mod ui;
-
-use components::{
-    nodes::{self, add_node_systems},
-    InnerNode,
-};
-use components::{Connected, Pos};
 use systems::draw_systems::add_draw_system;
-use systems::simulation_systems::*;
+
+/// Seconds of each frame spent running ticks, anything that doesn't fit waits for the next frame
+const TICK_BUDGET: f64 = 1.0 / 120.0;
+
+/// Number of ticks which can be stepped back through
+const HISTORY_LENGTH: usize = 256;
 
 #[macroquad::main("SIMple Electronics")]
 async fn main() {
-    let mut world = World::new();
+    let mut sim = Simulator::new();
 
-    world.insert(resources::TickProgress(0.0));
-
-    let mut dispatcher = {
-        let mut builder = DispatcherBuilder::new();
-        builder = add_node_systems(builder);
-        builder.build()
-    };
+    sim.world.insert(resources::TickProgress(0.0));
 
     let mut draw_dispatcher = {
         let mut builder =
@@ -41,7 +30,7 @@ async fn main() {
 
     let mq_ctx = unsafe { get_internal_gl() }.quad_context;
 
-    let mut textures = resources::Textures::default();
+    let mut textures = Textures::default();
 
     let not_svg = svg::texture_from_file("resources/not_gate.svg", 50, 45, mq_ctx).await;
     textures.0.insert("NOT_GATE".to_owned(), not_svg);
@@ -64,25 +53,30 @@ async fn main() {
     let xnor_svg = svg::texture_from_file("resources/xnor_gate.svg", 225, 175, mq_ctx).await;
     textures.0.insert("XNOR_GATE".to_owned(), xnor_svg);
 
-    world.insert(textures);
-
-    dispatcher.setup(&mut world);
-    draw_dispatcher.setup(&mut world);
-    world.insert(resources::UiSignals(Vec::new()));
-    world.insert(resources::GridMode::default());
-
-    world.insert(resources::Tick(0));
-    world.insert(resources::TickFrames(60));
-    world.insert(resources::CameraRes::default());
-    world.insert(resources::RhaiEngine::default());
-    world.insert(resources::RhaiScope::default());
-    world.insert(resources::CreatingCompoundNode::default());
-    world.register::<components::CompoundNode>();
-    world.register::<components::NodeMarker>();
-    specs::System::setup(
-        &mut systems::update_current_scope_sys::UpdateCurrentScopeSys,
-        &mut world,
-    );
+    sim.world.insert(textures);
+
+    draw_dispatcher.setup(&mut sim.world);
+    sim.world.insert(resources::UiSignals(Vec::new()));
+    sim.world.insert(resources::GridMode::default());
+
+    sim.world.insert(resources::Tick(0));
+    sim.world.insert(resources::TickFrames(60));
+    sim.world.insert(resources::SpeedMode::default());
+    sim.world.insert(resources::TicksPerSecond::default());
+    sim.world.insert(resources::RunLength::default());
+    sim.world.insert(resources::GateInputs::default());
+    sim.world.insert(resources::Paused::default());
+    sim.world.insert(resources::CombinationalLoops::default());
+    sim.world.insert(resources::SelectedClock::default());
+    sim.world.insert(resources::MemoryInspector::default());
+    sim.world.insert(resources::SelectedDipSwitch::default());
+    sim.world.insert(resources::SelectedPort::default());
+    sim.world.insert(resources::SelectedDelay::default());
+    sim.world.insert(resources::History::new(HISTORY_LENGTH));
+    sim.world.insert(CameraRes::default());
+    sim.world.insert(resources::CircuitPath::default());
+    sim.world.insert(resources::ScriptPath::default());
+    sim.world.insert(resources::StatusText::default());
 
     let mut prev_mouse_pos = {
         let (mx, my) = mouse_position();
@@ -91,83 +85,184 @@ async fn main() {
 
     let mut last_fps = [60i32; 256];
 
-    // let script: String = macroquad::file::load_string("test_scripts/basic_circuit.rhai")
-    //     .await
-    //     .unwrap();
-
-    // scripting::run_circuit_create_sys(script, &world);
-    // world.maintain();
+    let mut pending_run = None;
+    // ticks owed when running at a set number of ticks per second
+    let mut tick_debt = 0.0;
 
     loop {
         clear_background(BLACK);
-        let i = world.fetch::<resources::Tick>().0;
+        let i = sim.world.fetch::<resources::Tick>().0;
         last_fps[i % last_fps.len()] = get_fps();
 
         // let tick_frames: usize = (last_fps.iter().sum::<i32>() / last_fps.len() as i32) as usize;
-        let tick_frames = world.fetch::<resources::TickFrames>().0;
+        let tick_frames = sim.world.fetch::<resources::TickFrames>().0;
+
+        let speed_mode = *sim.world.fetch::<resources::SpeedMode>();
+        let paused = sim.world.fetch::<resources::Paused>().0;
+        let tick_progress = match (pending_run, speed_mode) {
+            (None, resources::SpeedMode::FramesPerTick) if !paused => {
+                (i % tick_frames) as f64 / tick_frames as f64
+            }
+            // too many ticks a frame to animate them
+            _ => 0.0,
+        };
+        sim.world.insert(resources::TickProgress(tick_progress));
 
-        world.insert(resources::TickProgress(
-            (i % tick_frames) as f64 / tick_frames as f64,
-        ));
+        let frame_start = get_time();
+        let in_budget = || get_time() - frame_start < TICK_BUDGET;
 
-        // if i > last_fps.len() && i % tick_frames == 0 {
-        if i % tick_frames == 0 {
-            dispatcher.dispatch_seq(&world);
+        match pending_run {
+            Some(run) => match sim.continue_run(run, in_budget) {
+                RunProgress::Running(run) => pending_run = Some(run),
+                RunProgress::Finished(status) => {
+                    pending_run = None;
+                    sim.world.insert(resources::StatusText(Some(status)));
+                }
+            },
+            None if paused => {}
+            None => match speed_mode {
+                // if i > last_fps.len() && i % tick_frames == 0 {
+                resources::SpeedMode::FramesPerTick => {
+                    if i.is_multiple_of(tick_frames) {
+                        sim.step(1);
+                    }
+                }
+                resources::SpeedMode::TicksPerSecond => {
+                    tick_debt +=
+                        get_frame_time() as f64 * sim.world.fetch::<resources::TicksPerSecond>().0;
+                    while tick_debt >= 1.0 && in_budget() {
+                        sim.step(1);
+                        tick_debt -= 1.0;
+                    }
+                    // ticks which didn't fit in the frame are dropped rather than piling up
+                    tick_debt = tick_debt.min(1.0);
+                }
+            },
         }
-        draw_dispatcher.dispatch_thread_local(&world);
+        draw_dispatcher.dispatch_thread_local(&sim.world);
 
-        world.fetch_mut::<resources::Tick>().incr();
-        world.maintain();
+        sim.world.fetch_mut::<resources::Tick>().incr();
+        sim.world.maintain();
 
         {
-            let signals_res = world.fetch::<resources::UiSignals>();
+            let signals_res = sim.world.fetch::<resources::UiSignals>();
             let ui_signals = signals_res.0.clone();
             std::mem::drop(signals_res);
 
             ui_signals.iter().for_each(|signal| match signal {
-                UiSignal::AddNode(ty) => world.insert(resources::UIState::AddingNode(*ty)),
-                UiSignal::Delete => world.insert(resources::UIState::Deleting),
+                UiSignal::Run(run) => pending_run = Some(*run),
+                UiSignal::FindLoops => {
+                    let loops = analysis::find_combinational_loops(&sim.world);
+                    let status = match loops.len() {
+                        0 => "No combinational loops".to_string(),
+                        1 => format!("Found a loop through {} nodes", loops[0].len()),
+                        n => format!("Found {} combinational loops", n),
+                    };
+                    sim.world.insert(resources::CombinationalLoops(loops));
+                    sim.world.insert(resources::StatusText(Some(status)));
+                }
+                UiSignal::StepBack => {
+                    pending_run = None;
+                    let status = if sim.step_back() {
+                        format!("Tick {}", sim.ticks())
+                    } else {
+                        "No earlier ticks to step back to".to_string()
+                    };
+                    sim.world.insert(resources::StatusText(Some(status)));
+                }
+                UiSignal::AddNode(ty) => sim.world.insert(resources::UIState::AddingNode(*ty)),
+                UiSignal::AddCompoundNode(name) => sim
+                    .world
+                    .insert(resources::UIState::AddingCompoundNode(name.clone())),
+                UiSignal::Delete => sim.world.insert(resources::UIState::Deleting),
+                UiSignal::RemoveAll => {
+                    pending_run = None;
+                    sim.clear();
+                }
+                UiSignal::BindKey => sim.world.insert(resources::UIState::BindingKey(None)),
+                UiSignal::SetDelay => sim.world.insert(resources::UIState::SettingDelay),
                 UiSignal::CreateNode => {
-                    world.insert(resources::UIState::Nothing);
-                    let compound_node = world
+                    sim.world.insert(resources::UIState::Nothing);
+                    let compound_node = sim
+                        .world
                         .create_entity()
                         .with(components::CompoundNode::default())
                         .build();
-                    world.insert(resources::CreatingCompoundNode(Some(CompoundNodeData {
-                        entity: compound_node,
-                        name: "".to_string(),
-                    })));
-                    systems::update_current_scope_sys::UpdateCurrentScopeSys.run_now(&world);
+                    sim.world
+                        .insert(resources::CreatingCompoundNode(Some(CompoundNodeData {
+                            entity: compound_node,
+                            name: "".to_string(),
+                        })));
+                    systems::update_current_scope_sys::UpdateCurrentScopeSys.run_now(&sim.world);
                 }
                 UiSignal::SaveCompoundNode => {
-                    let mut compound_node_data =
-                        world.fetch_mut::<resources::CreatingCompoundNode>();
-                    let data = compound_node_data.0.as_mut().unwrap();
-
-                    world
-                        .write_storage::<components::CompoundNode>()
-                        .get_mut(data.entity)
-                        .unwrap()
-                        .name = data.name.to_owned();
-
-                    {
-                        let entities = world.entities();
-                        let inner_nodes = world.read_storage::<InnerNode>();
-                        (&inner_nodes, &entities)
-                            .join()
-                            .filter(|(inner_node, _)| inner_node.parent == data.entity)
-                            .for_each(|(_, entity)| entities.delete(entity).unwrap());
+                    let data = sim
+                        .world
+                        .fetch::<resources::CreatingCompoundNode>()
+                        .0
+                        .as_ref()
+                        .map(|data| (data.entity, data.name.clone()));
+                    if let Some((entity, name)) = data {
+                        // on an error the compound node stays open to be fixed
+                        let status =
+                            match compound::save_compound_node(&mut sim.world, entity, &name) {
+                                Ok((inputs, outputs)) => format!(
+                                    "Saved compound node {} with {} inputs and {} outputs",
+                                    name, inputs, outputs
+                                ),
+                                Err(e) => format!("Failed to save compound node: {}", e),
+                            };
+                        sim.world.insert(resources::StatusText(Some(status)));
                     }
-
-                    std::mem::drop(compound_node_data);
-                    world.insert(resources::CreatingCompoundNode(None));
-
-                    systems::update_current_scope_sys::UpdateCurrentScopeSys.run_now(&world);
+                }
+                UiSignal::SaveCircuit => {
+                    let path = sim.world.fetch::<resources::CircuitPath>().0.clone();
+                    let status = match save_load::save_to_file(&sim.world, &path) {
+                        Ok(()) => format!("Saved circuit to {}", path),
+                        Err(e) => format!("Failed to save {}: {}", path, e),
+                    };
+                    sim.world.insert(resources::StatusText(Some(status)));
+                }
+                UiSignal::OpenCircuit => {
+                    let path = sim.world.fetch::<resources::CircuitPath>().0.clone();
+                    let status = match sim.load_file(&path) {
+                        Ok(()) => format!("Opened {}", path),
+                        Err(e) => format!("Failed to open {}: {}", path, e),
+                    };
+                    sim.world.insert(resources::StatusText(Some(status)));
+                }
+                UiSignal::ImportScript => {
+                    let path = sim.world.fetch::<resources::ScriptPath>().0.clone();
+                    let result = std::fs::read_to_string(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|script| {
+                            scripting::run_circuit_script(&script, &mut sim.world)
+                                .map_err(|e| e.to_string())
+                        });
+                    let status = match result {
+                        Ok(()) => format!("Imported {}", path),
+                        Err(e) => format!("Failed to import {}: {}", path, e),
+                    };
+                    sim.world.insert(resources::StatusText(Some(status)));
+                }
+                UiSignal::ExportScript => {
+                    let path = sim.world.fetch::<resources::ScriptPath>().0.clone();
+                    let result = scripting::export_circuit(&sim.world)
+                        .map_err(|e| e.to_string())
+                        .and_then(|script| {
+                            std::fs::write(&path, script).map_err(|e| e.to_string())
+                        });
+                    let status = match result {
+                        Ok(()) => format!("Exported circuit to {}", path),
+                        Err(e) => format!("Failed to export {}: {}", path, e),
+                    };
+                    sim.world.insert(resources::StatusText(Some(status)));
                 }
             });
-            world.insert(resources::UiSignals(Vec::new()));
+            sim.world.insert(resources::UiSignals(Vec::new()));
         }
 
+        let mut typing = false;
         egui_macroquad::ui(|egui_ctx| {
             use egui::{FontDefinitions, TextStyle};
             let mut fonts = FontDefinitions::default();
@@ -176,23 +271,27 @@ async fn main() {
             egui_ctx.set_fonts(fonts);
 
             egui::TopPanel::top("SIMple Electronics").show(egui_ctx, |ui| {
-                ui::top_panel::render_top_panel(ui, &mut world);
+                ui::top_panel::render_top_panel(ui, &mut sim.world);
             });
+            ui::memory_inspector::render_memory_inspector(egui_ctx, &mut sim.world);
+            typing = egui_ctx.wants_keyboard_input();
         });
 
         {
-            let camera = world.fetch::<CameraRes>().0;
-            world.insert(resources::MousePos(
+            let camera = sim.world.fetch::<CameraRes>().0;
+            sim.world.insert(resources::MousePos(
                 camera.screen_to_world(mouse_position().into()),
             ));
         }
 
+        ui::keyboard::handle_keys(&mut sim.world, typing);
+
         if is_mouse_button_pressed(MouseButton::Left) {
-            ui::mouse_click::handle_mouse_click(&mut world);
+            ui::mouse_click::handle_mouse_click(&mut sim.world);
         }
 
         if is_mouse_button_pressed(MouseButton::Right) {
-            ui::mouse_click::handle_mouse_right_click(&mut world);
+            ui::mouse_click::handle_mouse_right_click(&mut sim.world);
         }
 
         let new_mouse_pos = {
@@ -201,12 +300,13 @@ async fn main() {
         };
 
         if is_mouse_button_down(MouseButton::Middle) {
-            world.fetch_mut::<CameraRes>().0.offset += (new_mouse_pos - prev_mouse_pos) / 1000.0;
+            sim.world.fetch_mut::<CameraRes>().0.offset +=
+                (new_mouse_pos - prev_mouse_pos) / 1000.0;
         }
 
         {
             let mp: Vec2 = mouse_position().into();
-            let old_camera = world.fetch::<CameraRes>().0;
+            let old_camera = sim.world.fetch::<CameraRes>().0;
             let old_focus = old_camera.screen_to_world(mp);
 
             #[cfg(target_arch = "wasm32")]
@@ -216,15 +316,15 @@ async fn main() {
             let mwheel = macroquad::input::mouse_wheel().1;
 
             let zoom_fac = 1.0 + mwheel / 10.0;
-            world.fetch_mut::<CameraRes>().0.zoom *= zoom_fac;
-            let new_camera = world.fetch::<CameraRes>().0;
+            sim.world.fetch_mut::<CameraRes>().0.zoom *= zoom_fac;
+            let new_camera = sim.world.fetch::<CameraRes>().0;
             let new_focus = new_camera.screen_to_world(mp);
 
             let delta_focus = new_focus - old_focus;
-            world.fetch_mut::<CameraRes>().0.offset += delta_focus * new_camera.zoom;
+            sim.world.fetch_mut::<CameraRes>().0.offset += delta_focus * new_camera.zoom;
         }
 
-        macroquad::camera::set_camera(world.fetch::<CameraRes>().0);
+        macroquad::camera::set_camera(sim.world.fetch::<CameraRes>().0);
         prev_mouse_pos = new_mouse_pos;
 
         egui_macroquad::draw();
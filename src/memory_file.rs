@@ -0,0 +1,100 @@
+use std::path::Path;
+
+// Contents of ROM and RAM nodes can be loaded from two kinds of file:
+//      - hex text: words written in hex separated by whitespace, with an optional 0x prefix,
+//        where everything after a # on a line is a comment
+//      - raw binary (a .bin extension): each word is stored little endian in as many whole
+//        bytes as it takes to hold `data_width` bits
+
+#[derive(Debug)]
+pub enum MemoryFileError {
+    Io(std::io::Error),
+    InvalidWord {
+        line: usize,
+        word: String,
+    },
+    /// A word doesn't fit in the data width
+    WordTooWide {
+        line: usize,
+        word: String,
+    },
+}
+
+impl std::fmt::Display for MemoryFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryFileError::Io(e) => write!(f, "IO error: {}", e),
+            MemoryFileError::InvalidWord { line, word } => {
+                write!(f, "Invalid hex word \"{}\" on line {}", word, line)
+            }
+            MemoryFileError::WordTooWide { line, word } => {
+                write!(f, "Word \"{}\" on line {} is too wide", word, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemoryFileError {}
+
+impl From<std::io::Error> for MemoryFileError {
+    fn from(e: std::io::Error) -> Self {
+        MemoryFileError::Io(e)
+    }
+}
+
+/// Reads the words in a hex text file
+pub fn parse_hex(text: &str, data_width: usize) -> Result<Vec<u32>, MemoryFileError> {
+    let max = u32::MAX >> (32 - data_width.clamp(1, 32));
+    let mut words = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_words = line.split('#').next().unwrap().split_whitespace();
+        for word in line_words {
+            let digits = word.strip_prefix("0x").unwrap_or(word);
+            let value =
+                u64::from_str_radix(digits, 16).map_err(|_| MemoryFileError::InvalidWord {
+                    line: i + 1,
+                    word: word.to_string(),
+                })?;
+            if value > max as u64 {
+                return Err(MemoryFileError::WordTooWide {
+                    line: i + 1,
+                    word: word.to_string(),
+                });
+            }
+            words.push(value as u32);
+        }
+    }
+
+    Ok(words)
+}
+
+/// Reads the words in a raw binary file, a partial word at the end is padded with zeros and
+/// bits past the data width are dropped
+pub fn parse_binary(bytes: &[u8], data_width: usize) -> Vec<u32> {
+    let data_width = data_width.clamp(1, 32);
+    let max = u32::MAX >> (32 - data_width);
+    bytes
+        .chunks(data_width.div_ceil(8))
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |word, (i, byte)| word | (*byte as u32) << (i * 8))
+                & max
+        })
+        .collect()
+}
+
+/// Reads a hex or binary file, picking the format from the extension
+pub fn read_memory_file(
+    path: impl AsRef<Path>,
+    data_width: usize,
+) -> Result<Vec<u32>, MemoryFileError> {
+    let path = path.as_ref();
+    if path.extension().is_some_and(|ext| ext == "bin") {
+        Ok(parse_binary(&std::fs::read(path)?, data_width))
+    } else {
+        parse_hex(&std::fs::read_to_string(path)?, data_width)
+    }
+}
@@ -1,10 +1,10 @@
-use macroquad::prelude::screen_height;
-use macroquad::prelude::Vec2;
-use macroquad::texture::Texture2D;
-use macroquad::{camera::Camera2D, prelude::screen_width};
-use specs::Entity;
+use glam::Vec2;
+use specs::{BitSet, Entity};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 use crate::components::nodes::NodeTy;
+use crate::components::{Bus, Signal};
+use crate::compound::CompoundNodeTemplate;
 
 use rhai;
 
@@ -12,44 +12,94 @@ use rhai;
 pub struct TickProgress(pub f64);
 
 #[derive(Default)]
-pub struct Textures(pub std::collections::BTreeMap<String, Texture2D>);
+pub struct CreatingCompoundNode(pub Option<CompoundNodeData>);
 
+/// Compound nodes which have been saved and can be placed, by name
 #[derive(Default)]
-pub struct CreatingCompoundNode(pub Option<CompoundNodeData>);
+pub struct CompoundNodeLibrary(pub HashMap<String, CompoundNodeTemplate>);
+
+/// Delays chosen for whole node types, used by nodes without a `Delay` of their own in place of
+/// the type's default. Saved with the circuit.
+#[derive(Default, Clone)]
+pub struct NodeDelays(pub BTreeMap<NodeTy, u32>);
 
 pub struct CompoundNodeData {
     pub entity: Entity,
     pub name: String,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub enum UIState {
     AddingNode(NodeTy),
+    /// Placing a compound node from the library, by name
+    AddingCompoundNode(String),
     AddingWire {
         connection_entity: Entity,
         points: Vec<Vec2>,
     },
     Deleting,
+    /// Waiting for a switch or button to be clicked, then for the key to bind to it
+    BindingKey(Option<Entity>),
+    /// Waiting for a node to be clicked to show its delay
+    SettingDelay,
+    #[default]
     Nothing,
 }
 
-impl Default for UIState {
-    fn default() -> Self {
-        UIState::Nothing
-    }
-}
-
 #[derive(Clone)]
 pub enum UiSignal {
     AddNode(NodeTy),
+    AddCompoundNode(String),
     Delete,
+    RemoveAll,
+    BindKey,
+    SetDelay,
     CreateNode,
     SaveCompoundNode,
+    SaveCircuit,
+    OpenCircuit,
+    ImportScript,
+    ExportScript,
+    Run(PendingRun),
+    StepBack,
+    FindLoops,
 }
 
 #[derive(Default)]
 pub struct UiSignals(pub Vec<UiSignal>);
 
+/// The clock whose settings are shown in the top panel
+#[derive(Default)]
+pub struct SelectedClock(pub Option<Entity>);
+
+/// The DIP switch whose value is shown in the top panel
+#[derive(Default)]
+pub struct SelectedDipSwitch(pub Option<Entity>);
+
+/// The input or output port whose name and order are shown in the top panel
+#[derive(Default)]
+pub struct SelectedPort(pub Option<Entity>);
+
+/// The node whose delay, and the delay of its type, are shown in the top panel
+#[derive(Default)]
+pub struct SelectedDelay(pub Option<Entity>);
+
+/// The ROM or RAM node shown in the memory inspector window
+pub struct MemoryInspector {
+    pub selected: Option<Entity>,
+    /// File the contents are loaded from
+    pub path: String,
+}
+
+impl Default for MemoryInspector {
+    fn default() -> Self {
+        MemoryInspector {
+            selected: None,
+            path: "memory.hex".to_string(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 pub struct Tick(pub usize);
 
@@ -62,17 +112,314 @@ impl Tick {
 #[derive(Clone, Copy, Default)]
 pub struct TickFrames(pub usize);
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+/// How the GUI decides when to run a tick
+#[derive(Eq, PartialEq, Copy, Clone, Default)]
+pub enum SpeedMode {
+    /// One tick every `TickFrames` frames, animating the change along the wires in between
+    #[default]
+    FramesPerTick,
+    /// As many ticks each frame as it takes to keep up with `TicksPerSecond`
+    TicksPerSecond,
+}
+
+#[derive(Clone, Copy)]
+pub struct TicksPerSecond(pub f64);
+
+impl Default for TicksPerSecond {
+    fn default() -> Self {
+        TicksPerSecond(1000.0)
+    }
+}
+
+/// A run started from the UI, spread over as many frames as it takes
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum PendingRun {
+    /// Ticks left to run
+    Ticks(usize),
+    /// Runs until no wire changes, counting the ticks run so far
+    UntilStable(usize),
+    /// Runs until any wire changes, counting the ticks run so far
+    UntilChange(usize),
+}
+
+/// Whether the GUI has stopped running ticks by itself
+#[derive(Clone, Copy, Default)]
+pub struct Paused(pub bool);
+
+/// Number of inputs gates added from the nodes menu have
+#[derive(Clone, Copy)]
+pub struct GateInputs(pub usize);
+
+impl Default for GateInputs {
+    fn default() -> Self {
+        GateInputs(2)
+    }
+}
+
+/// Number of ticks run by the "Run Ticks" button
+#[derive(Clone, Copy)]
+pub struct RunLength(pub usize);
+
+impl Default for RunLength {
+    fn default() -> Self {
+        RunLength(1000)
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Default)]
 pub enum GridMode {
     Lines,
     Dots,
+    #[default]
     CrossHatches,
     Off,
 }
 
-impl Default for GridMode {
+/// How a wire driven by more than one output decides its state
+#[derive(Eq, PartialEq, Copy, Clone, Default)]
+pub enum DriverPolicy {
+    /// Conflicting drivers make the wire X
+    #[default]
+    Error,
+    WiredOr,
+    WiredAnd,
+}
+
+impl DriverPolicy {
+    /// Merges the signals of several sources into one, sources at Z are ignored and the result
+    /// is Z only when all of them are
+    pub fn resolve(self, signals: impl Iterator<Item = Signal>) -> Signal {
+        let mut signals = signals.filter(|s| s.is_driven());
+        let first = match signals.next() {
+            Some(first) => first,
+            None => return Signal::Z,
+        };
+
+        signals.fold(first, |acc, s| match self {
+            DriverPolicy::Error if acc == s => acc,
+            DriverPolicy::Error => Signal::X,
+            DriverPolicy::WiredOr => acc | s,
+            DriverPolicy::WiredAnd => acc & s,
+        })
+    }
+
+    /// Whether driven sources disagree on any bit while the policy treats that as an error
+    pub fn conflicts(self, buses: impl Iterator<Item = Bus> + Clone) -> bool {
+        if self != DriverPolicy::Error {
+            return false;
+        }
+
+        let width = buses.clone().map(|bus| bus.width()).max().unwrap_or(0);
+        (0..width).any(|i| {
+            let mut driven = buses
+                .clone()
+                .map(|bus| bus.bit(i))
+                .filter(|s| s.is_driven());
+            match driven.next() {
+                Some(first) => driven.any(|s| s != first),
+                None => false,
+            }
+        })
+    }
+
+    /// Resolves each bit separately, the result is as wide as the widest source
+    pub fn resolve_bus(self, buses: impl Iterator<Item = Bus> + Clone) -> Bus {
+        let width = match buses.clone().map(|bus| bus.width()).max() {
+            Some(width) => width,
+            None => return Bus::from(Signal::Z),
+        };
+
+        let mut result = Bus::new(width, Signal::Z);
+        (0..width)
+            .for_each(|i| result.set_bit(i, self.resolve(buses.clone().map(|bus| bus.bit(i)))));
+        result
+    }
+}
+
+/// How an input with several wires attached merges them
+#[derive(Eq, PartialEq, Copy, Clone, Default)]
+pub struct FanInPolicy(pub DriverPolicy);
+
+/// Which nodes ElectroSys evaluates every tick
+#[derive(Eq, PartialEq, Copy, Clone, Default)]
+pub enum SchedulerMode {
+    /// Only nodes whose input wires changed, plus nodes without inputs
+    #[default]
+    EventDriven,
+    /// Every node, every tick
+    Sweep,
+}
+
+/// Bookkeeping for the event driven scheduler, all sets hold entity ids
+pub struct Schedule {
+    /// Nodes to evaluate this tick
+    pub nodes: BitSet,
+    /// Wires whose drivers changed this tick
+    pub dirty_wires: BitSet,
+    /// Wires whose state changed at the end of the last tick
+    pub changed_wires: BitSet,
+    /// Set to evaluate every node and wire once, e.g. after a reset or when the circuit changes
+    pub all: bool,
+    /// Whether the state of any wire changed this tick
+    pub outputs_changed: bool,
+}
+
+impl Schedule {
+    pub fn evaluate_all(&self, mode: SchedulerMode) -> bool {
+        mode == SchedulerMode::Sweep || self.all
+    }
+}
+
+impl Default for Schedule {
     fn default() -> Self {
-        GridMode::CrossHatches
+        Schedule {
+            nodes: BitSet::new(),
+            dirty_wires: BitSet::new(),
+            changed_wires: BitSet::new(),
+            all: true,
+            outputs_changed: false,
+        }
+    }
+}
+
+/// Ids of the nodes reading from each wire, rebuilt whenever a connection changes
+#[derive(Default)]
+pub struct Fanout {
+    pub sinks: HashMap<u32, Vec<Entity>>,
+    pub rebuilding: bool,
+}
+
+/// Output changes of nodes with a delay longer than one tick, waiting to be driven onto their
+/// wires. Changes are kept in order so short pulses make it through (transport delay).
+#[derive(Clone)]
+pub struct TimeWheel {
+    /// Ticks run so far
+    pub now: u64,
+    // changes are stored in the slot for their tick modulo the number of slots, which is
+    // always more than the longest delay
+    slots: Vec<Vec<(u64, Entity, Bus)>>,
+    // the last value scheduled for each output connection, to skip scheduling unchanged values
+    last: HashMap<Entity, Bus>,
+}
+
+impl Default for TimeWheel {
+    fn default() -> Self {
+        TimeWheel {
+            now: 0,
+            slots: vec![Vec::new(); 16],
+            last: HashMap::new(),
+        }
+    }
+}
+
+impl TimeWheel {
+    /// Drives the output connection with the value after the delay, returning false if the
+    /// value is the same as the last one scheduled
+    pub fn schedule(&mut self, delay: u32, output: Entity, value: Bus) -> bool {
+        if self.last.get(&output) == Some(&value) {
+            return false;
+        }
+        self.last.insert(output, value);
+
+        let delay = delay as usize;
+        if delay >= self.slots.len() {
+            let len = (delay + 1).next_power_of_two();
+            let events = self.slots.drain(..).flatten().collect::<Vec<_>>();
+            self.slots = vec![Vec::new(); len];
+            events.into_iter().for_each(|event| self.insert(event));
+        }
+        self.insert((self.now + delay as u64, output, value));
+        true
+    }
+
+    /// Forgets the last value scheduled for the output connection, for when it's driven
+    /// without a delay so a later change back to that value still gets scheduled
+    pub fn forget(&mut self, output: Entity) {
+        self.last.remove(&output);
+    }
+
+    fn insert(&mut self, event: (u64, Entity, Bus)) {
+        let len = self.slots.len() as u64;
+        self.slots[(event.0 % len) as usize].push(event);
+    }
+
+    /// Takes the changes due this tick and moves on to the next one
+    pub fn advance(&mut self) -> Vec<(Entity, Bus)> {
+        let len = self.slots.len() as u64;
+        let due = std::mem::take(&mut self.slots[(self.now % len) as usize]);
+        self.now += 1;
+        due.into_iter()
+            .map(|(_, output, value)| (output, value))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Vec::is_empty)
+    }
+
+    pub fn clear(&mut self) {
+        *self = TimeWheel::default();
+    }
+}
+
+/// Ticks a circuit has to keep changing after its last input change to count as oscillating
+pub const OSCILLATION_TICKS: usize = 1000;
+
+/// Watches for circuits which never settle after a change to one of their inputs, like a ring
+/// of an odd number of not nodes
+#[derive(Default)]
+pub struct Oscillation {
+    /// Set when a node without inputs (e.g. a switch) changes its output during the tick
+    pub source_changed: bool,
+    /// Ticks in a row something has changed since a source last did
+    pub unsettled_ticks: usize,
+    /// Entity ids of the nodes reading wires which kept changing, empty unless oscillating
+    pub nodes: BitSet,
+}
+
+/// Nodes in combinational loops, found by the last loop check
+#[derive(Default)]
+pub struct CombinationalLoops(pub Vec<Vec<Entity>>);
+
+/// The state of a wire saved in a `Snapshot`
+pub struct WireSnapshot {
+    pub input_state: Bus,
+    pub output_state: Bus,
+    pub changed_input: bool,
+    pub drivers: Vec<(Entity, Bus)>,
+    pub conflict: bool,
+}
+
+/// Everything a tick changes, taken before the tick runs
+pub struct Snapshot {
+    pub wires: Vec<(Entity, WireSnapshot)>,
+    pub time_wheel: TimeWheel,
+    /// Serialized state of the sequential nodes
+    pub nodes: HashMap<Entity, Vec<u8>>,
+}
+
+/// Snapshots from before each of the last `capacity` ticks, used to step backwards.
+/// Nothing is recorded while the capacity is 0.
+#[derive(Default)]
+pub struct History {
+    pub capacity: usize,
+    pub snapshots: VecDeque<Snapshot>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a snapshot, dropping the oldest one once there are `capacity` of them
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
     }
 }
 
@@ -86,24 +433,37 @@ impl Default for CurrentModeText {
     }
 }
 
-pub struct CameraRes(pub Camera2D);
+pub struct CircuitPath(pub String);
 
-impl Default for CameraRes {
+impl Default for CircuitPath {
     fn default() -> Self {
-        CameraRes(Camera2D {
-            rotation: 0.0,
-            zoom: Vec2::new(2.0 / screen_width(), 2.0 / screen_height()),
-            target: Vec2::new(0.0, 0.0),
-            offset: Vec2::new(0.0, 0.0),
-            render_target: None,
-        })
+        CircuitPath("circuit.bin".to_string())
+    }
+}
+
+/// File scripts are imported from and exported to, kept apart from the saved circuit
+pub struct ScriptPath(pub String);
+
+impl Default for ScriptPath {
+    fn default() -> Self {
+        ScriptPath("circuit.rhai".to_string())
     }
 }
 
+/// Result of the last file operation, shown in the top panel
 #[derive(Default)]
-pub struct MousePos(pub Vec2);
+pub struct StatusText(pub Option<String>);
 
 #[derive(Default)]
+pub struct MousePos(pub Vec2);
+
 pub struct RhaiEngine(pub rhai::Engine);
+
+impl Default for RhaiEngine {
+    fn default() -> Self {
+        RhaiEngine(crate::scripting::new_engine())
+    }
+}
+
 #[derive(Default)]
 pub struct RhaiScope<'a>(pub rhai::Scope<'a>);
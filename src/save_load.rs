@@ -0,0 +1,693 @@
+use crate::components::Signal;
+use crate::components::{
+    nodes::{NodeTy, Wire},
+    CompoundNode, Connected, Connection, ConnectionTy, Delay, InnerNode, KeyBinding, Name, Node,
+    NodeMarker, Pos,
+};
+use crate::compound::CompoundNodeTemplate;
+use crate::resources::{CompoundNodeLibrary, CreatingCompoundNode, NodeDelays, UIState};
+use crate::systems::simulation_systems::ResetSys;
+use crate::systems::update_current_scope_sys::UpdateCurrentScopeSys;
+use glam::Vec2;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+
+// Entities can't be serialized directly, so every entity is written out with its id and all
+// references between entities (wires in a connection, connections of a node, etc.) are stored
+// as those ids. When loading, a new entity is created for each id first and then the references
+// are remapped.
+
+/// Bumped whenever the layout of `CircuitFile` changes, files from older versions are brought
+/// up to date by `read_circuit`
+pub const SAVE_VERSION: u32 = 8;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    UnsupportedVersion(u32),
+    MissingEntity(u32),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "IO error: {}", e),
+            SaveError::Encoding(e) => write!(f, "Invalid circuit file: {}", e),
+            SaveError::UnsupportedVersion(v) => write!(
+                f,
+                "Circuit file has unknown version {}, the newest is {}",
+                v, SAVE_VERSION
+            ),
+            SaveError::MissingEntity(id) => {
+                write!(f, "Circuit file references missing entity {}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SaveError {
+    fn from(e: bincode::Error) -> Self {
+        SaveError::Encoding(e)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedConnection {
+    pub wires: Vec<u32>,
+    pub ty: ConnectionTy,
+    pub index: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedNode {
+    pub ty: NodeTy,
+    // the node itself, encoded separately since every node type has a different layout
+    pub data: Vec<u8>,
+    pub inputs: Vec<u32>,
+    pub outputs: Vec<u32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedCompoundNode {
+    pub inner: Vec<u32>,
+    pub name: String,
+    pub inputs: Vec<u32>,
+    pub outputs: Vec<u32>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub id: u32,
+    pub pos: Option<Pos>,
+    pub wire: Option<Wire>,
+    pub connection: Option<SavedConnection>,
+    pub node: Option<SavedNode>,
+    pub node_marker: bool,
+    pub inner_node: Option<u32>,
+    pub compound_node: Option<SavedCompoundNode>,
+    pub name: Option<Name>,
+    pub delay: Option<Delay>,
+    pub key: Option<KeyBinding>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CircuitFile {
+    pub version: u32,
+    pub entities: Vec<SavedEntity>,
+    /// Compound nodes saved while building the circuit, so more of them can be placed
+    pub library: BTreeMap<String, CompoundNodeTemplate>,
+    /// Delays set for whole node types
+    pub delays: BTreeMap<NodeTy, u32>,
+}
+
+fn save_nodes<N, const I: usize, const O: usize>(
+    world: &World,
+    ty: NodeTy,
+    saved: &mut BTreeMap<u32, SavedNode>,
+) -> Result<(), SaveError>
+where
+    N: Node<I, O> + 'static,
+{
+    let nodes = world.read_storage::<Connected<N, I, O>>();
+    let entities = world.entities();
+
+    for (node, entity) in (&nodes, &entities).join() {
+        saved.insert(
+            entity.id(),
+            SavedNode {
+                ty,
+                data: bincode::serialize(&node.node)?,
+                inputs: node.inputs.iter().map(|e| e.id()).collect(),
+                outputs: node.outputs.iter().map(|e| e.id()).collect(),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+fn check_node<N, const I: usize, const O: usize>(
+    saved: &SavedNode,
+    check_ids: impl Fn(&[u32]) -> Result<(), SaveError>,
+) -> Result<(), SaveError>
+where
+    N: Node<I, O> + 'static,
+{
+    check_ids(&saved.inputs)?;
+    check_ids(&saved.outputs)?;
+
+    if saved.inputs.len() != I || saved.outputs.len() != O {
+        return Err(SaveError::Encoding(wrong_pin_count(saved.ty)));
+    }
+
+    bincode::deserialize::<N>(&saved.data)?;
+
+    Ok(())
+}
+
+fn load_node<N, const I: usize, const O: usize>(
+    world: &World,
+    entity: Entity,
+    saved: &SavedNode,
+    entity_map: &BTreeMap<u32, Entity>,
+) -> Result<(), SaveError>
+where
+    N: Node<I, O> + 'static,
+{
+    let remap_all = |ids: &[u32]| {
+        ids.iter()
+            .map(|id| remap(*id, entity_map))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let inputs: [Entity; I] = remap_all(&saved.inputs)?
+        .try_into()
+        .map_err(|_| SaveError::Encoding(wrong_pin_count(saved.ty)))?;
+    let outputs: [Entity; O] = remap_all(&saved.outputs)?
+        .try_into()
+        .map_err(|_| SaveError::Encoding(wrong_pin_count(saved.ty)))?;
+
+    world
+        .write_storage::<Connected<N, I, O>>()
+        .insert(
+            entity,
+            Connected {
+                node: bincode::deserialize::<N>(&saved.data)?,
+                inputs,
+                outputs,
+            },
+        )
+        .unwrap();
+
+    Ok(())
+}
+
+fn wrong_pin_count(ty: NodeTy) -> bincode::Error {
+    Box::new(bincode::ErrorKind::Custom(format!(
+        "wrong number of connections for {:?}",
+        ty
+    )))
+}
+
+fn remap(id: u32, entity_map: &BTreeMap<u32, Entity>) -> Result<Entity, SaveError> {
+    entity_map
+        .get(&id)
+        .copied()
+        .ok_or(SaveError::MissingEntity(id))
+}
+
+/// Saves the entities `include` picks, along with whatever they reference by id
+pub fn save_entities(
+    world: &World,
+    include: impl Fn(Entity) -> bool,
+) -> Result<Vec<SavedEntity>, SaveError> {
+    let mut nodes = BTreeMap::new();
+
+    macro_rules! save_all_nodes {
+        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+            $(
+                save_nodes::<crate::nodes::$node, $i, $o>(world, NodeTy::$node, &mut nodes)?;
+            )*
+        };
+    }
+
+    use crate::all_nodes;
+    all_nodes!(save_all_nodes);
+
+    let entities = world.entities();
+    let positions = world.read_storage::<Pos>();
+    let wires = world.read_storage::<Wire>();
+    let connections = world.read_storage::<Connection>();
+    let node_markers = world.read_storage::<NodeMarker>();
+    let inner_nodes = world.read_storage::<InnerNode>();
+    let compound_nodes = world.read_storage::<CompoundNode>();
+    let names = world.read_storage::<Name>();
+    let delays = world.read_storage::<Delay>();
+    let keys = world.read_storage::<KeyBinding>();
+    let ids = |entities: &[Entity]| entities.iter().map(|e| e.id()).collect();
+
+    Ok(entities
+        .join()
+        .filter(|entity| include(*entity))
+        .map(|entity| SavedEntity {
+            id: entity.id(),
+            pos: positions.get(entity).copied(),
+            wire: wires.get(entity).cloned(),
+            connection: connections.get(entity).map(|c| SavedConnection {
+                wires: ids(&c.wires),
+                ty: c.ty,
+                index: c.index,
+            }),
+            node: nodes.remove(&entity.id()),
+            node_marker: node_markers.get(entity).is_some(),
+            inner_node: inner_nodes.get(entity).map(|n| n.parent.id()),
+            compound_node: compound_nodes.get(entity).map(|c| SavedCompoundNode {
+                inner: c.inner.iter().map(|e| e.id()).collect(),
+                name: c.name.clone(),
+                inputs: ids(&c.inputs),
+                outputs: ids(&c.outputs),
+            }),
+            name: names.get(entity).cloned(),
+            delay: delays.get(entity).copied(),
+            key: keys.get(entity).copied(),
+        })
+        .collect())
+}
+
+pub fn save_circuit(world: &World) -> Result<CircuitFile, SaveError> {
+    let library = world.fetch::<CompoundNodeLibrary>();
+    let delays = world.fetch::<NodeDelays>();
+
+    Ok(CircuitFile {
+        version: SAVE_VERSION,
+        entities: save_entities(world, |_| true)?,
+        library: library
+            .0
+            .iter()
+            .map(|(name, template)| (name.clone(), template.clone()))
+            .collect(),
+        delays: delays.0.clone(),
+    })
+}
+
+/// Makes sure the entities can be loaded without touching the world, so a bad file can't leave
+/// half a circuit behind
+pub fn check_entities(saved_entities: &[SavedEntity]) -> Result<(), SaveError> {
+    let ids = saved_entities
+        .iter()
+        .map(|saved| saved.id)
+        .collect::<BTreeSet<u32>>();
+    let check_ids = |references: &[u32]| match references.iter().find(|id| !ids.contains(id)) {
+        Some(id) => Err(SaveError::MissingEntity(*id)),
+        None => Ok(()),
+    };
+
+    for saved in saved_entities.iter() {
+        if let Some(connection) = &saved.connection {
+            check_ids(&connection.wires)?;
+        }
+
+        if let Some(node) = &saved.node {
+            macro_rules! check_node_systems {
+                ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+                    match node.ty {
+                        $(NodeTy::$node => {
+                            check_node::<crate::nodes::$node, $i, $o>(node, check_ids)?
+                        })*
+                    }
+                };
+            }
+
+            use crate::all_nodes;
+            all_nodes!(check_node_systems);
+        }
+
+        if let Some(parent) = saved.inner_node {
+            check_ids(&[parent])?;
+        }
+
+        if let Some(compound_node) = &saved.compound_node {
+            check_ids(&compound_node.inner)?;
+            check_ids(&compound_node.inputs)?;
+            check_ids(&compound_node.outputs)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a new entity for each saved one, returning the entity made for each id
+pub fn load_entities(
+    world: &mut World,
+    saved_entities: &[SavedEntity],
+) -> Result<BTreeMap<u32, Entity>, SaveError> {
+    check_entities(saved_entities)?;
+    create_entities(world, saved_entities)
+}
+
+// the entities have to have been checked first
+fn create_entities(
+    world: &mut World,
+    saved_entities: &[SavedEntity],
+) -> Result<BTreeMap<u32, Entity>, SaveError> {
+    let entity_map = saved_entities
+        .iter()
+        .map(|saved| (saved.id, world.create_entity().build()))
+        .collect::<BTreeMap<u32, Entity>>();
+    let remap_all = |ids: &[u32]| {
+        ids.iter()
+            .map(|id| remap(*id, &entity_map))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    for saved in saved_entities.iter() {
+        let entity = entity_map[&saved.id];
+
+        if let Some(pos) = saved.pos {
+            world.write_storage::<Pos>().insert(entity, pos).unwrap();
+        }
+
+        if let Some(wire) = &saved.wire {
+            world
+                .write_storage::<Wire>()
+                .insert(entity, wire.clone())
+                .unwrap();
+        }
+
+        if let Some(connection) = &saved.connection {
+            world
+                .write_storage::<Connection>()
+                .insert(
+                    entity,
+                    Connection {
+                        wires: remap_all(&connection.wires)?,
+                        ty: connection.ty,
+                        index: connection.index,
+                    },
+                )
+                .unwrap();
+        }
+
+        if let Some(node) = &saved.node {
+            macro_rules! load_node_systems {
+                ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+                    match node.ty {
+                        $(NodeTy::$node => {
+                            load_node::<crate::nodes::$node, $i, $o>(world, entity, node, &entity_map)?
+                        })*
+                    }
+                };
+            }
+
+            use crate::all_nodes;
+            all_nodes!(load_node_systems);
+        }
+
+        if saved.node_marker {
+            world
+                .write_storage::<NodeMarker>()
+                .insert(entity, NodeMarker)
+                .unwrap();
+        }
+
+        if let Some(parent) = saved.inner_node {
+            let parent = remap(parent, &entity_map)?;
+            world
+                .write_storage::<InnerNode>()
+                .insert(entity, InnerNode { parent })
+                .unwrap();
+        }
+
+        if let Some(compound_node) = &saved.compound_node {
+            world
+                .write_storage::<CompoundNode>()
+                .insert(
+                    entity,
+                    CompoundNode {
+                        inner: remap_all(&compound_node.inner)?.into_iter().collect(),
+                        name: compound_node.name.clone(),
+                        inputs: remap_all(&compound_node.inputs)?,
+                        outputs: remap_all(&compound_node.outputs)?,
+                    },
+                )
+                .unwrap();
+        }
+
+        if let Some(name) = &saved.name {
+            world
+                .write_storage::<Name>()
+                .insert(entity, name.clone())
+                .unwrap();
+        }
+
+        if let Some(delay) = saved.delay {
+            world
+                .write_storage::<Delay>()
+                .insert(entity, delay)
+                .unwrap();
+        }
+
+        if let Some(key) = saved.key {
+            world
+                .write_storage::<KeyBinding>()
+                .insert(entity, key)
+                .unwrap();
+        }
+    }
+
+    Ok(entity_map)
+}
+
+/// Replaces everything in the world with the circuit
+pub fn load_circuit(world: &mut World, file: CircuitFile) -> Result<(), SaveError> {
+    if file.version != SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(file.version));
+    }
+
+    check_entities(&file.entities)?;
+
+    clear_circuit(world);
+    world.insert(CompoundNodeLibrary(file.library.into_iter().collect()));
+    world.insert(NodeDelays(file.delays));
+
+    create_entities(world, &file.entities)?;
+
+    ResetSys.run_now(world);
+    UpdateCurrentScopeSys.run_now(world);
+
+    Ok(())
+}
+
+/// Removes the whole circuit along with its compound node library and delay table
+pub fn clear_circuit(world: &mut World) {
+    world.delete_all();
+    world.maintain();
+    world.insert(CreatingCompoundNode(None));
+    world.insert(UIState::Nothing);
+    world.insert(CompoundNodeLibrary::default());
+    world.insert(NodeDelays::default());
+
+    // nothing about the old circuit's simulation (pending changes, history, detected
+    // oscillations) carries over
+    ResetSys.run_now(world);
+    UpdateCurrentScopeSys.run_now(world);
+}
+
+pub fn save_to_file(world: &World, path: &str) -> Result<(), SaveError> {
+    let file = save_circuit(world)?;
+    let bytes = bincode::serialize(&file)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+pub fn load_from_file(world: &mut World, path: &str) -> Result<(), SaveError> {
+    let bytes = std::fs::read(path)?;
+    load_circuit(world, read_circuit(&bytes)?)
+}
+
+/// Decodes a circuit file written by this or any earlier version
+pub fn read_circuit(bytes: &[u8]) -> Result<CircuitFile, SaveError> {
+    // every version starts with the version number
+    let version = bincode::deserialize::<u32>(bytes)?;
+
+    let entities = match version {
+        SAVE_VERSION => return Ok(bincode::deserialize(bytes)?),
+        // before the delay table
+        7 => {
+            let (_, entities, library): (u32, _, _) = bincode::deserialize(bytes)?;
+            return Ok(CircuitFile {
+                version: SAVE_VERSION,
+                entities,
+                library,
+                delays: BTreeMap::new(),
+            });
+        }
+        1 => read_old_entities(bytes, |e: SavedEntityV1<WireV1>| {
+            e.upgrade(None, None, None)
+        })?,
+        2 => read_old_entities(bytes, |(e, name): (SavedEntityV1<WireV1>, _)| {
+            e.upgrade(name, None, None)
+        })?,
+        3 => read_old_entities(bytes, |(e, name): (SavedEntityV1<WireV3>, _)| {
+            e.upgrade(name, None, None)
+        })?,
+        4 => read_old_entities(bytes, |(e, name): (SavedEntityV1<Wire>, _)| {
+            e.upgrade(name, None, None)
+        })?,
+        5 => read_old_entities(bytes, |(e, name, delay): (SavedEntityV1<Wire>, _, _)| {
+            e.upgrade(name, delay, None)
+        })?,
+        6 => read_old_entities(
+            bytes,
+            |(e, name, delay, key): (SavedEntityV1<Wire>, _, _, _)| e.upgrade(name, delay, key),
+        )?,
+        _ => return Err(SaveError::UnsupportedVersion(version)),
+    };
+
+    Ok(CircuitFile {
+        version: SAVE_VERSION,
+        entities,
+        library: BTreeMap::new(),
+        delays: BTreeMap::new(),
+    })
+}
+
+// Older versions only differ in the wire states and in which fields of `SavedEntity` existed,
+// new fields were always added at the end so those entities are decoded as tuples of the first
+// version's layout followed by the fields added since. Node data was never changed.
+
+fn read_old_entities<E: DeserializeOwned>(
+    bytes: &[u8],
+    upgrade: impl Fn(E) -> SavedEntity,
+) -> Result<Vec<SavedEntity>, SaveError> {
+    let (_, entities) = bincode::deserialize::<(u32, Vec<E>)>(bytes)?;
+    Ok(entities.into_iter().map(upgrade).collect())
+}
+
+// versions 1 and 2, before four-state signals
+#[derive(Serialize, Deserialize)]
+struct WireV1 {
+    input_state: bool,
+    output_state: bool,
+    changed_input: bool,
+    #[serde(with = "vec2")]
+    start_point: Vec2,
+    #[serde(with = "vec2")]
+    end_point: Vec2,
+    #[serde(with = "vec2_vec")]
+    points: Vec<Vec2>,
+}
+
+// version 3, before buses
+#[derive(Serialize, Deserialize)]
+struct WireV3 {
+    input_state: Signal,
+    output_state: Signal,
+    changed_input: bool,
+    #[serde(with = "vec2")]
+    start_point: Vec2,
+    #[serde(with = "vec2")]
+    end_point: Vec2,
+    #[serde(with = "vec2_vec")]
+    points: Vec<Vec2>,
+}
+
+impl From<WireV1> for Wire {
+    fn from(wire: WireV1) -> Self {
+        Wire::from(WireV3 {
+            input_state: wire.input_state.into(),
+            output_state: wire.output_state.into(),
+            changed_input: wire.changed_input,
+            start_point: wire.start_point,
+            end_point: wire.end_point,
+            points: wire.points,
+        })
+    }
+}
+
+impl From<WireV3> for Wire {
+    fn from(wire: WireV3) -> Self {
+        Wire {
+            input_state: wire.input_state.into(),
+            output_state: wire.output_state.into(),
+            changed_input: wire.changed_input,
+            start_point: wire.start_point,
+            end_point: wire.end_point,
+            points: wire.points,
+            ..Default::default()
+        }
+    }
+}
+
+// versions 1 to 6, before compound nodes had pins
+#[derive(Serialize, Deserialize)]
+struct SavedCompoundNodeV1 {
+    inner: Vec<u32>,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEntityV1<W> {
+    id: u32,
+    pos: Option<Pos>,
+    wire: Option<W>,
+    connection: Option<SavedConnection>,
+    node: Option<SavedNode>,
+    node_marker: bool,
+    inner_node: Option<u32>,
+    compound_node: Option<SavedCompoundNodeV1>,
+}
+
+impl<W: Into<Wire>> SavedEntityV1<W> {
+    fn upgrade(
+        self,
+        name: Option<Name>,
+        delay: Option<Delay>,
+        key: Option<KeyBinding>,
+    ) -> SavedEntity {
+        SavedEntity {
+            id: self.id,
+            pos: self.pos,
+            wire: self.wire.map(Into::into),
+            connection: self.connection,
+            node: self.node,
+            node_marker: self.node_marker,
+            inner_node: self.inner_node,
+            compound_node: self.compound_node.map(|c| SavedCompoundNode {
+                inner: c.inner,
+                name: c.name,
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+            }),
+            name,
+            delay,
+            key,
+        }
+    }
+}
+
+// glam is built without its serde support so points are stored as tuples
+pub mod vec2 {
+    use glam::Vec2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+        (v.x, v.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Vec2::new(x, y))
+    }
+}
+
+pub mod vec2_vec {
+    use glam::Vec2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[Vec2], serializer: S) -> Result<S::Ok, S::Error> {
+        v.iter()
+            .map(|p| (p.x, p.y))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec2>, D::Error> {
+        let points = Vec::<(f32, f32)>::deserialize(deserializer)?;
+        Ok(points.into_iter().map(|(x, y)| Vec2::new(x, y)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,198 @@
+use super::*;
+use crate::components::nodes::{add_node_systems, NotNode, SwitchNode};
+use crate::components::Bus;
+use crate::resources::MousePos;
+use crate::systems::place_node_sys::PlaceNodeSys;
+use crate::systems::update_current_scope_sys::UpdateCurrentScopeSys;
+use glam::Vec2;
+
+fn new_world() -> World {
+    let mut world = World::new();
+    add_node_systems(DispatcherBuilder::new())
+        .build()
+        .setup(&mut world);
+    System::setup(&mut PlaceNodeSys::<SwitchNode, 0, 1>::default(), &mut world);
+    System::setup(&mut PlaceNodeSys::<NotNode, 1, 1>::default(), &mut world);
+    System::setup(&mut UpdateCurrentScopeSys, &mut world);
+    System::setup(&mut ResetSys, &mut world);
+    world.register::<CompoundNode>();
+    world.register::<Name>();
+    world.register::<KeyBinding>();
+    world.insert(CompoundNodeLibrary::default());
+    world
+}
+
+fn place<N: Node<I, O> + 'static, const I: usize, const O: usize>(world: &World, pos: Vec2) {
+    *world.write_resource::<MousePos>() = MousePos(pos);
+    PlaceNodeSys::<N, I, O>::default().run_now(world);
+}
+
+// a switch wired up to a not node
+fn switch_and_not() -> World {
+    let mut world = new_world();
+    place::<SwitchNode, 0, 1>(&world, Vec2::new(0.0, 0.0));
+    place::<NotNode, 1, 1>(&world, Vec2::new(200.0, 0.0));
+    world.maintain();
+
+    let switch_output = (&world.read_storage::<Connected<SwitchNode, 0, 1>>())
+        .join()
+        .next()
+        .unwrap()
+        .outputs[0];
+    let not_input = (&world.read_storage::<Connected<NotNode, 1, 1>>())
+        .join()
+        .next()
+        .unwrap()
+        .inputs[0];
+    let wire = world
+        .create_entity()
+        .with(Wire {
+            points: vec![Vec2::new(0.0, 0.0), Vec2::new(200.0, 0.0)],
+            ..Wire::default()
+        })
+        .build();
+    let mut connections = world.write_storage::<Connection>();
+    connections.get_mut(switch_output).unwrap().wires.push(wire);
+    connections.get_mut(not_input).unwrap().wires.push(wire);
+    std::mem::drop(connections);
+
+    (&mut world.write_storage::<Connected<SwitchNode, 0, 1>>())
+        .join()
+        .for_each(|switch| switch.node.state = true);
+    world
+}
+
+#[test]
+fn save_and_load_round_trip() {
+    let world = switch_and_not();
+    let bytes = bincode::serialize(&save_circuit(&world).unwrap()).unwrap();
+
+    let mut loaded = new_world();
+    load_circuit(&mut loaded, bincode::deserialize(&bytes).unwrap()).unwrap();
+
+    let switches = loaded.read_storage::<Connected<SwitchNode, 0, 1>>();
+    let nots = loaded.read_storage::<Connected<NotNode, 1, 1>>();
+    let connections = loaded.read_storage::<Connection>();
+    let wires = loaded.read_storage::<Wire>();
+    let positions = loaded.read_storage::<Pos>();
+
+    let switch = (&switches).join().next().unwrap();
+    let not = (&nots).join().next().unwrap();
+    assert!(switch.node.state);
+
+    // the wire still joins the two connections
+    let output_wires = &connections.get(switch.outputs[0]).unwrap().wires;
+    let input_wires = &connections.get(not.inputs[0]).unwrap().wires;
+    assert_eq!(output_wires.len(), 1);
+    assert_eq!(output_wires, input_wires);
+    assert_eq!(wires.get(output_wires[0]).unwrap().points.len(), 2);
+
+    let not_pos = (&nots, &positions).join().next().unwrap().1.pos;
+    assert_eq!(not_pos, Vec2::new(200.0, 0.0));
+}
+
+#[test]
+fn save_and_load_through_a_file() {
+    let world = switch_and_not();
+    let path = std::env::temp_dir().join(format!("save_load_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    save_to_file(&world, path).unwrap();
+
+    let mut loaded = new_world();
+    load_from_file(&mut loaded, path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(
+        (&loaded.read_storage::<Connected<NotNode, 1, 1>>())
+            .join()
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn rejects_other_versions() {
+    let file = CircuitFile {
+        version: SAVE_VERSION + 1,
+        entities: Vec::new(),
+        library: BTreeMap::new(),
+        delays: BTreeMap::new(),
+    };
+    assert!(matches!(
+        load_circuit(&mut new_world(), file),
+        Err(SaveError::UnsupportedVersion(_))
+    ));
+}
+
+#[test]
+fn upgrades_version_1_files() {
+    let wire = SavedEntityV1 {
+        id: 3,
+        pos: None,
+        wire: Some(WireV1 {
+            input_state: true,
+            output_state: true,
+            changed_input: false,
+            start_point: Vec2::new(0., 0.),
+            end_point: Vec2::new(10., 0.),
+            points: vec![Vec2::new(5., 5.)],
+        }),
+        connection: None,
+        node: None,
+        node_marker: false,
+        inner_node: None,
+        compound_node: None,
+    };
+    let bytes = bincode::serialize(&(1u32, vec![wire])).unwrap();
+
+    let file = read_circuit(&bytes).unwrap();
+    assert_eq!(file.version, SAVE_VERSION);
+    let wire = file.entities[0].wire.as_ref().unwrap();
+    assert_eq!(wire.output_state, Bus::from(Signal::High));
+    assert_eq!(wire.points, vec![Vec2::new(5., 5.)]);
+}
+
+#[test]
+fn upgrades_version_7_files() {
+    let bytes = bincode::serialize(&(
+        7u32,
+        Vec::<SavedEntity>::new(),
+        BTreeMap::<String, CompoundNodeTemplate>::new(),
+    ))
+    .unwrap();
+
+    let file = read_circuit(&bytes).unwrap();
+    assert_eq!(file.version, SAVE_VERSION);
+    assert!(file.delays.is_empty());
+}
+
+#[test]
+fn rejects_files_from_the_future() {
+    let bytes = bincode::serialize(&(SAVE_VERSION + 1, Vec::<SavedEntity>::new())).unwrap();
+    assert!(matches!(
+        read_circuit(&bytes),
+        Err(SaveError::UnsupportedVersion(_))
+    ));
+}
+
+#[test]
+fn bad_files_leave_the_world_alone() {
+    let mut world = World::new();
+    world.register::<Pos>();
+    let existing = world.create_entity().build();
+
+    let file = CircuitFile {
+        version: SAVE_VERSION,
+        entities: vec![SavedEntity {
+            id: 0,
+            inner_node: Some(7),
+            ..Default::default()
+        }],
+        library: BTreeMap::new(),
+        delays: BTreeMap::new(),
+    };
+    assert!(matches!(
+        load_circuit(&mut world, file),
+        Err(SaveError::MissingEntity(7))
+    ));
+    assert!(world.is_alive(existing));
+}
@@ -1,15 +1,19 @@
+use crate::memory_file;
 use crate::nodes::NodeTy;
+use crate::systems::place_node_sys::PlaceNodeSys;
 use crate::{components::ConnectionTy, Pos};
-use macroquad::prelude::Vec2;
+use glam::Vec2;
 use rhai::Map;
-use std::{collections::BTreeMap, convert::TryInto, marker::PhantomData};
+use std::collections::BTreeMap;
 
-use rhai::{self, Array, Dynamic, Identifier};
+use rhai::{self, Array, Dynamic, Engine, EvalAltResult, INT};
 use specs::prelude::*;
 
 use crate::{
-    components::{nodes::Wire, Connected, Connection, Node, Orientation},
-    resources::{RhaiEngine, RhaiScope},
+    components::{
+        nodes::Wire, CompoundNode, Connected, Connection, Delay, InnerNode, KeyBinding, Name, Node,
+    },
+    resources::{CreatingCompoundNode, NodeDelays, RhaiEngine, RhaiScope},
 };
 
 // Ok so the logical graph structure is potentially cyclic and pretty wonky actually so it's
@@ -17,288 +21,920 @@ use crate::{
 //
 // This basically just seeks to make the Rhai code mirror the Rust circuit creation; it's not great
 // for humans to write but it's fine for auto serialization and deserialization
+//
+// A script defines two variables:
+//      - WIRES: a map of wire name to #{ bends: [#{ x, y }] }
+//      - NODES: an array of #{ type, pos: #{ x, y }, inputs: [[wire names]], outputs: [[wire names]] }
+//        with an optional `node` field holding the node's own data, e.g. #{ state: true } for a switch,
+//        and an optional `name` used to refer to the node from tools like the circuit runner
+//        or `key` (a letter or digit) which toggles a switch or holds down a button
+//      - gates with more than two inputs have the count after their type, e.g. "And4", up to
+//        "And8"
+//
+// and optionally a third:
+//      - DELAYS: a map of node type to the delay, in ticks, of every node of that type without a
+//        `delay` of its own, e.g. #{ Not: 2 }
+//
+// Scripts can call `read_memory(path, data_width)` to get the words in a memory file as an array,
+// e.g. for the `contents` of a ROM node
+
+#[derive(Debug)]
+pub enum CircuitScriptError {
+    Eval(Box<EvalAltResult>),
+    MissingField {
+        field: String,
+        context: String,
+    },
+    WrongType {
+        field: String,
+        context: String,
+    },
+    UnknownNodeType(String),
+    UnknownWire {
+        wire: String,
+        context: String,
+    },
+    TooManyConnections {
+        field: String,
+        context: String,
+        max: usize,
+    },
+    InvalidNodeData {
+        context: String,
+        error: Box<EvalAltResult>,
+    },
+    /// Scripts have no way to describe compound nodes
+    CompoundNodes,
+}
+
+impl std::fmt::Display for CircuitScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use CircuitScriptError::*;
+
+        match self {
+            Eval(e) => write!(f, "Script error: {}", e),
+            MissingField { field, context } => write!(f, "{} is missing `{}`", context, field),
+            WrongType { field, context } => {
+                write!(f, "`{}` of {} has the wrong type", field, context)
+            }
+            UnknownNodeType(ty) => write!(f, "Unknown node type \"{}\"", ty),
+            UnknownWire { wire, context } => {
+                write!(f, "{} uses undefined wire \"{}\"", context, wire)
+            }
+            TooManyConnections {
+                field,
+                context,
+                max,
+            } => write!(f, "{} has more than {} {}", context, max, field),
+            InvalidNodeData { context, error } => {
+                write!(f, "Invalid node data for {}: {}", context, error)
+            }
+            CompoundNodes => write!(
+                f,
+                "Circuits with compound nodes can't be exported as scripts"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CircuitScriptError {}
+
+impl From<Box<EvalAltResult>> for CircuitScriptError {
+    fn from(e: Box<EvalAltResult>) -> Self {
+        CircuitScriptError::Eval(e)
+    }
+}
+
+fn read_memory(path: &str, data_width: INT) -> Result<Array, Box<EvalAltResult>> {
+    let words = memory_file::read_memory_file(path, data_width.clamp(1, 32) as usize)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    Ok(words
+        .into_iter()
+        .map(|word| Dynamic::from(word as INT))
+        .collect())
+}
+
+/// A Rhai engine with the functions circuit scripts can use
+pub fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.register_fn("read_memory", read_memory);
+    engine
+}
+
+// names used for the `type` of a node in scripts
+const NODE_NAMES: &[(&str, NodeTy)] = &[
+    ("Connection", NodeTy::Wire),
+    ("On", NodeTy::OnNode),
+    ("Off", NodeTy::OffNode),
+    ("Not", NodeTy::NotNode),
+    ("And", NodeTy::AndNode),
+    ("Or", NodeTy::OrNode),
+    ("Nand", NodeTy::NandNode),
+    ("Nor", NodeTy::NorNode),
+    ("Xor", NodeTy::XorNode),
+    ("Xnor", NodeTy::XnorNode),
+    ("And3", NodeTy::And3Node),
+    ("And4", NodeTy::And4Node),
+    ("And5", NodeTy::And5Node),
+    ("And6", NodeTy::And6Node),
+    ("And7", NodeTy::And7Node),
+    ("And8", NodeTy::And8Node),
+    ("Or3", NodeTy::Or3Node),
+    ("Or4", NodeTy::Or4Node),
+    ("Or5", NodeTy::Or5Node),
+    ("Or6", NodeTy::Or6Node),
+    ("Or7", NodeTy::Or7Node),
+    ("Or8", NodeTy::Or8Node),
+    ("Nand3", NodeTy::Nand3Node),
+    ("Nand4", NodeTy::Nand4Node),
+    ("Nand5", NodeTy::Nand5Node),
+    ("Nand6", NodeTy::Nand6Node),
+    ("Nand7", NodeTy::Nand7Node),
+    ("Nand8", NodeTy::Nand8Node),
+    ("Nor3", NodeTy::Nor3Node),
+    ("Nor4", NodeTy::Nor4Node),
+    ("Nor5", NodeTy::Nor5Node),
+    ("Nor6", NodeTy::Nor6Node),
+    ("Nor7", NodeTy::Nor7Node),
+    ("Nor8", NodeTy::Nor8Node),
+    ("Xor3", NodeTy::Xor3Node),
+    ("Xor4", NodeTy::Xor4Node),
+    ("Xor5", NodeTy::Xor5Node),
+    ("Xor6", NodeTy::Xor6Node),
+    ("Xor7", NodeTy::Xor7Node),
+    ("Xor8", NodeTy::Xor8Node),
+    ("Xnor3", NodeTy::Xnor3Node),
+    ("Xnor4", NodeTy::Xnor4Node),
+    ("Xnor5", NodeTy::Xnor5Node),
+    ("Xnor6", NodeTy::Xnor6Node),
+    ("Xnor7", NodeTy::Xnor7Node),
+    ("Xnor8", NodeTy::Xnor8Node),
+    ("Switch", NodeTy::SwitchNode),
+    ("Clock", NodeTy::ClockNode),
+    ("SrLatch", NodeTy::SrLatchNode),
+    ("DLatch", NodeTy::DLatchNode),
+    ("DFlipFlop", NodeTy::DFlipFlopNode),
+    ("JkFlipFlop", NodeTy::JkFlipFlopNode),
+    ("TFlipFlop", NodeTy::TFlipFlopNode),
+    ("Register4", NodeTy::Register4Node),
+    ("Register8", NodeTy::Register8Node),
+    ("Counter4", NodeTy::Counter4Node),
+    ("Counter8", NodeTy::Counter8Node),
+    ("ShiftRegister4", NodeTy::ShiftRegister4Node),
+    ("ShiftRegister8", NodeTy::ShiftRegister8Node),
+    ("Rom", NodeTy::RomNode),
+    ("Ram", NodeTy::RamNode),
+    ("Led", NodeTy::LedNode),
+    ("SevenSegment", NodeTy::SevenSegmentNode),
+    ("HexDigit", NodeTy::HexDigitNode),
+    ("LedMatrix", NodeTy::LedMatrixNode),
+    ("Button", NodeTy::ButtonNode),
+    ("DipSwitch", NodeTy::DipSwitchNode),
+    ("TriState", NodeTy::TriStateNode),
+    ("Splitter4", NodeTy::Splitter4Node),
+    ("Splitter8", NodeTy::Splitter8Node),
+    ("Merger4", NodeTy::Merger4Node),
+    ("Merger8", NodeTy::Merger8Node),
+    ("HalfAdder", NodeTy::HalfAdderNode),
+    ("FullAdder", NodeTy::FullAdderNode),
+    ("Adder", NodeTy::AdderNode),
+    ("Comparator", NodeTy::ComparatorNode),
+    ("Mux2", NodeTy::Mux2Node),
+    ("Mux4", NodeTy::Mux4Node),
+    ("Mux8", NodeTy::Mux8Node),
+    ("Demux2", NodeTy::Demux2Node),
+    ("Demux4", NodeTy::Demux4Node),
+    ("Demux8", NodeTy::Demux8Node),
+    ("Decoder4", NodeTy::Decoder4Node),
+    ("Decoder8", NodeTy::Decoder8Node),
+    ("Encoder4", NodeTy::Encoder4Node),
+    ("Encoder8", NodeTy::Encoder8Node),
+    ("InputPort", NodeTy::InputPortNode),
+    ("OutputPort", NodeTy::OutputPortNode),
+];
+
+pub fn node_ty_from_name(name: &str) -> Option<NodeTy> {
+    NODE_NAMES
+        .iter()
+        .find(|(node_name, _)| *node_name == name)
+        .map(|(_, ty)| *ty)
+}
+
+pub fn node_ty_name(ty: NodeTy) -> &'static str {
+    NODE_NAMES
+        .iter()
+        .find(|(_, node_ty)| *node_ty == ty)
+        .map(|(name, _)| *name)
+        .unwrap()
+}
+
+struct RhaiNode {
+    pub ty: NodeTy,
+    pub context: String,
+    pub input_wires: Vec<Vec<String>>,
+    pub output_wires: Vec<Vec<String>>,
+    pub pos: Vec2,
+    pub data: Option<Dynamic>,
+    pub name: Option<String>,
+    pub delay: Option<u32>,
+    pub key: Option<KeyBinding>,
+}
+
+fn get_field<'a>(
+    map: &'a Map,
+    field: &str,
+    context: &str,
+) -> Result<&'a Dynamic, CircuitScriptError> {
+    map.get(field)
+        .ok_or_else(|| CircuitScriptError::MissingField {
+            field: field.to_string(),
+            context: context.to_string(),
+        })
+}
+
+fn wrong_type(field: &str, context: &str) -> CircuitScriptError {
+    CircuitScriptError::WrongType {
+        field: field.to_string(),
+        context: context.to_string(),
+    }
+}
+
+// numbers can be written as either ints or floats in the script
+fn get_number(map: &Map, field: &str, context: &str) -> Result<f32, CircuitScriptError> {
+    let value = get_field(map, field, context)?;
+
+    value
+        .as_float()
+        .map(|x| x as f32)
+        .or_else(|_| value.as_int().map(|x| x as f32))
+        .map_err(|_| wrong_type(field, context))
+}
+
+fn get_point(map: &Map, context: &str) -> Result<Vec2, CircuitScriptError> {
+    Ok(Vec2::new(
+        get_number(map, "x", context)?,
+        get_number(map, "y", context)?,
+    ))
+}
 
-pub fn create_circuit(world: &mut World) {
-    // 1. Create wires - done
-    // 2. Get RhaiNodes which contain a type, [[input wires]], [[output wires]] - done
-    // 3. Create Nodes and Connections
-    // 4. Profit
+fn parse_wire(name: &str, wire: &Dynamic) -> Result<Wire, CircuitScriptError> {
+    let context = format!("wire \"{}\"", name);
+    let wire = wire
+        .clone()
+        .try_cast::<Map>()
+        .ok_or_else(|| wrong_type(name, "WIRES"))?;
+
+    let bends = get_field(&wire, "bends", &context)?
+        .clone()
+        .try_cast::<Array>()
+        .ok_or_else(|| wrong_type("bends", &context))?
+        .iter()
+        .map(|point| {
+            let point = point
+                .clone()
+                .try_cast::<Map>()
+                .ok_or_else(|| wrong_type("bends", &context))?;
+            get_point(&point, &context)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Wire {
+        points: bends,
+        ..Wire::default()
+    })
+}
+
+fn parse_node(index: usize, node: &Dynamic) -> Result<RhaiNode, CircuitScriptError> {
+    let context = format!("node {}", index);
+    let node = node
+        .clone()
+        .try_cast::<Map>()
+        .ok_or_else(|| wrong_type(&index.to_string(), "NODES"))?;
+
+    let ty_name = get_field(&node, "type", &context)?
+        .clone()
+        .try_cast::<rhai::ImmutableString>()
+        .ok_or_else(|| wrong_type("type", &context))?;
+    let ty = node_ty_from_name(ty_name.as_str())
+        .ok_or_else(|| CircuitScriptError::UnknownNodeType(ty_name.to_string()))?;
+    let context = format!("node {} ({})", index, ty_name);
+
+    let process_array = |name: &str| -> Result<Vec<Vec<String>>, CircuitScriptError> {
+        let wrong_type = || wrong_type(name, &context);
+
+        get_field(&node, name, &context)?
+            .clone()
+            .try_cast::<Array>()
+            .ok_or_else(wrong_type)?
+            .iter()
+            .map(|connection| {
+                connection
+                    .clone()
+                    .try_cast::<Array>()
+                    .ok_or_else(wrong_type)?
+                    .iter()
+                    .map(|wire| {
+                        wire.clone()
+                            .try_cast::<rhai::ImmutableString>()
+                            .map(|wire| wire.to_string())
+                            .ok_or_else(wrong_type)
+                    })
+                    .collect::<Result<Vec<String>, _>>()
+            })
+            .collect()
+    };
+
+    let input_wires = process_array("inputs")?;
+    let output_wires = process_array("outputs")?;
+
+    let pos = get_field(&node, "pos", &context)?
+        .clone()
+        .try_cast::<Map>()
+        .ok_or_else(|| wrong_type("pos", &context))?;
+    let pos = get_point(&pos, &context)?;
+
+    let name = match node.get("name") {
+        Some(name) => Some(
+            name.clone()
+                .try_cast::<rhai::ImmutableString>()
+                .ok_or_else(|| wrong_type("name", &context))?
+                .to_string(),
+        ),
+        None => None,
+    };
+
+    let delay = match node.get("delay") {
+        Some(delay) => Some(
+            delay
+                .as_int()
+                .ok()
+                .filter(|delay| *delay >= 1)
+                .ok_or_else(|| wrong_type("delay", &context))? as u32,
+        ),
+        None => None,
+    };
+
+    let key = match node.get("key") {
+        Some(key) => {
+            let key = key
+                .clone()
+                .try_cast::<rhai::ImmutableString>()
+                .ok_or_else(|| wrong_type("key", &context))?;
+            let mut chars = key.chars();
+            match (chars.next().and_then(KeyBinding::new), chars.next()) {
+                (Some(key), None) => Some(key),
+                _ => return Err(wrong_type("key", &context)),
+            }
+        }
+        None => None,
+    };
+
+    Ok(RhaiNode {
+        ty,
+        input_wires,
+        output_wires,
+        pos,
+        data: node.get("node").cloned(),
+        name,
+        delay,
+        key,
+        context,
+    })
+}
+
+// adds the named wires to each connection and moves the wire ends onto the connection
+fn connect_wires(
+    world: &World,
+    connection_entities: &[Entity],
+    wire_names: &[Vec<String>],
+    wire_entities: &BTreeMap<String, Entity>,
+    context: &str,
+) -> Result<(), CircuitScriptError> {
+    let mut connections = world.write_storage::<Connection>();
+    let positions = world.read_storage::<Pos>();
+    let mut wires = world.write_storage::<Wire>();
+
+    for (connection_entity, names) in connection_entities.iter().zip(wire_names.iter()) {
+        let connection = connections.get_mut(*connection_entity).unwrap();
+        let pos = positions.get(*connection_entity).unwrap().pos;
+
+        for name in names.iter() {
+            let wire_entity =
+                *wire_entities
+                    .get(name)
+                    .ok_or_else(|| CircuitScriptError::UnknownWire {
+                        wire: name.clone(),
+                        context: context.to_string(),
+                    })?;
+
+            connection.wires.push(wire_entity);
+
+            let wire = wires.get_mut(wire_entity).unwrap();
+            match connection.ty {
+                ConnectionTy::Input => wire.end_point = pos,
+                ConnectionTy::Output => wire.start_point = pos,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// checks the node fits its connections and decodes its data
+fn parse_node_data<N, const I: usize, const O: usize>(
+    rhai_node: &RhaiNode,
+) -> Result<N, CircuitScriptError>
+where
+    N: Node<I, O> + 'static,
+{
+    let too_many = |field: &str, max: usize| CircuitScriptError::TooManyConnections {
+        field: field.to_string(),
+        context: rhai_node.context.clone(),
+        max,
+    };
+
+    if rhai_node.input_wires.len() > I {
+        return Err(too_many("inputs", I));
+    }
+    if rhai_node.output_wires.len() > O {
+        return Err(too_many("outputs", O));
+    }
+
+    let node = match &rhai_node.data {
+        Some(data) => rhai::serde::from_dynamic::<N>(data).map_err(|error| {
+            CircuitScriptError::InvalidNodeData {
+                context: rhai_node.context.clone(),
+                error,
+            }
+        })?,
+        None => N::default(),
+    };
+
+    Ok(node)
+}
+
+fn create_node<N, const I: usize, const O: usize>(
+    world: &World,
+    rhai_node: &RhaiNode,
+    wire_entities: &BTreeMap<String, Entity>,
+) -> Result<(), CircuitScriptError>
+where
+    N: Node<I, O> + 'static,
+{
+    let node = parse_node_data::<N, I, O>(rhai_node)?;
+
+    let entity = PlaceNodeSys::<N, I, O>::place_node(
+        node,
+        Pos::from_vec(rhai_node.pos),
+        world.system_data(),
+    );
+
+    if let Some(name) = &rhai_node.name {
+        world
+            .write_storage::<Name>()
+            .insert(entity, Name(name.clone()))
+            .unwrap();
+    }
+
+    if let Some(delay) = rhai_node.delay {
+        world
+            .write_storage::<Delay>()
+            .insert(entity, Delay(delay))
+            .unwrap();
+    }
+
+    if let Some(key) = rhai_node.key {
+        world
+            .write_storage::<KeyBinding>()
+            .insert(entity, key)
+            .unwrap();
+    }
 
-    let wires: Map = {
+    let (inputs, outputs) = {
+        let nodes = world.read_storage::<Connected<N, I, O>>();
+        let node = nodes.get(entity).unwrap();
+        (node.inputs, node.outputs)
+    };
+
+    connect_wires(
+        world,
+        &inputs,
+        &rhai_node.input_wires,
+        wire_entities,
+        &rhai_node.context,
+    )?;
+    connect_wires(
+        world,
+        &outputs,
+        &rhai_node.output_wires,
+        wire_entities,
+        &rhai_node.context,
+    )?;
+
+    Ok(())
+}
+
+fn parse_delays(delays: &Map) -> Result<BTreeMap<NodeTy, u32>, CircuitScriptError> {
+    delays
+        .iter()
+        .map(|(name, delay)| {
+            let ty = node_ty_from_name(name)
+                .ok_or_else(|| CircuitScriptError::UnknownNodeType(name.to_string()))?;
+            let delay = delay
+                .as_int()
+                .ok()
+                .filter(|delay| *delay >= 1)
+                .ok_or_else(|| wrong_type(name, "DELAYS"))?;
+            Ok((ty, delay as u32))
+        })
+        .collect()
+}
+
+/// Creates the circuit described by the WIRES, NODES and DELAYS variables of the current Rhai
+/// scope
+pub fn create_circuit(world: &mut World) -> Result<(), CircuitScriptError> {
+    let (wires, nodes, delays): (Map, Array, Option<Dynamic>) = {
         let engine = world.fetch::<RhaiEngine>();
         let mut scope = world.fetch_mut::<RhaiScope>();
-        engine.0.eval_with_scope(&mut scope.0, "WIRES").unwrap()
+        (
+            engine.0.eval_with_scope(&mut scope.0, "WIRES")?,
+            engine.0.eval_with_scope(&mut scope.0, "NODES")?,
+            scope.0.get_value("DELAYS"),
+        )
     };
 
+    // parse and check everything up front so that mistakes in a script are reported before
+    // anything is added to the world
     let wires = wires
         .iter()
+        .map(|(name, wire)| Ok((name.to_string(), parse_wire(name, wire)?)))
+        .collect::<Result<Vec<_>, CircuitScriptError>>()?;
+
+    let nodes = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| parse_node(i, node))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let delays = match delays {
+        Some(delays) => parse_delays(
+            &delays
+                .try_cast::<Map>()
+                .ok_or_else(|| wrong_type("DELAYS", "the script"))?,
+        )?,
+        None => BTreeMap::new(),
+    };
+
+    for node in nodes.iter() {
+        let undefined = node
+            .input_wires
+            .iter()
+            .chain(node.output_wires.iter())
+            .flatten()
+            .find(|name| !wires.iter().any(|(wire_name, _)| wire_name == *name));
+
+        if let Some(wire) = undefined {
+            return Err(CircuitScriptError::UnknownWire {
+                wire: wire.clone(),
+                context: node.context.clone(),
+            });
+        }
+
+        macro_rules! check_nodes {
+            ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+                match node.ty {
+                    $(NodeTy::$node => {
+                        parse_node_data::<crate::nodes::$node, $i, $o>(node).map(|_| ())
+                    })*
+                }
+            };
+        }
+
+        use crate::all_nodes;
+        all_nodes!(check_nodes)?;
+    }
+
+    world.fetch_mut::<NodeDelays>().0.extend(delays);
+
+    let parent = world
+        .fetch::<CreatingCompoundNode>()
+        .0
+        .as_ref()
+        .map(|data| data.entity);
+
+    let wire_entities = wires
+        .into_iter()
         .map(|(name, wire)| {
-            let wire = wire.clone_cast::<Map>();
-            let bends = wire
-                .get("bends")
-                .unwrap()
-                .clone_cast::<Array>()
-                .iter()
-                .map(|point| {
-                    let point = point.clone_cast::<Map>();
-                    let x = point.get("x").unwrap().clone();
-                    let y = point.get("y").unwrap().clone();
-                    Vec2::new(x.cast::<f32>(), y.cast::<f32>())
-                })
-                .collect();
-
-            (
-                name.to_string(),
-                Wire {
-                    points: bends,
-                    ..Wire::default()
-                },
-            )
-        })
-        .map(|(name, wire)| {
-            (
-                name.clone(),
-                world.create_entity().with(wire.clone()).build(),
-            )
+            let mut builder = world.create_entity().with(wire).with(Name(name.clone()));
+            if let Some(parent) = parent {
+                builder = builder.with(InnerNode { parent });
+            }
+            (name, builder.build())
         })
         .collect::<BTreeMap<String, Entity>>();
 
-    struct RhaiNode {
-        pub ty: NodeTy,
-        pub input_wires: Vec<Vec<String>>,
-        pub output_wires: Vec<Vec<String>>,
-        pub pos: Vec2,
+    let result = nodes.iter().try_for_each(|node| {
+        macro_rules! create_nodes {
+            ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+                match node.ty {
+                    $(NodeTy::$node => {
+                        create_node::<crate::nodes::$node, $i, $o>(world, node, &wire_entities)
+                    })*
+                }
+            };
+        }
+
+        use crate::all_nodes;
+        all_nodes!(create_nodes)
+    });
+
+    // like placed wires, the points include both ends
+    {
+        let mut wire_storage = world.write_storage::<Wire>();
+        wire_entities.values().for_each(|entity| {
+            let wire = wire_storage.get_mut(*entity).unwrap();
+            wire.points.insert(0, wire.start_point);
+            wire.points.push(wire.end_point);
+        });
     }
 
-    let nodes: Array = {
+    world.maintain();
+    crate::systems::update_current_scope_sys::UpdateCurrentScopeSys.run_now(world);
+
+    result
+}
+
+/// Runs the script and creates the circuit it describes
+pub fn run_circuit_script(script: &str, world: &mut World) -> Result<(), CircuitScriptError> {
+    {
         let engine = world.fetch::<RhaiEngine>();
         let mut scope = world.fetch_mut::<RhaiScope>();
-        engine.0.eval_with_scope(&mut scope.0, "NODES").unwrap()
-    };
+        scope.0.clear();
+        engine.0.run_with_scope(&mut scope.0, script)?;
+    }
 
-    let nodes = nodes.iter().map(|node: &Dynamic| {
-        let node = node.clone_cast::<Map>();
-
-        let ty = node.get("type").unwrap();
-        let ty = ty.clone_cast::<String>();
-
-        let ty = {
-            use crate::components::nodes::NodeTy::*;
-
-            match ty.as_str() {
-                "On" => OnNode,
-                "Off" => OffNode,
-                "Not" => NotNode,
-                "And" => AndNode,
-                "Or" => OrNode,
-                "Nand" => NandNode,
-                "Nor" => NorNode,
-                "Xor" => XorNode,
-                "Xnor" => XnorNode,
-                "Switch" => SwitchNode,
-                _ => panic!("Invalid Node"),
-            }
-        };
+    create_circuit(world)
+}
+
+struct ExportedNode {
+    ty: NodeTy,
+    name: Option<String>,
+    pos: Vec2,
+    inputs: Vec<Entity>,
+    outputs: Vec<Entity>,
+    data: Dynamic,
+    delay: Option<u32>,
+    key: Option<char>,
+}
+
+fn export_nodes<N, const I: usize, const O: usize>(
+    world: &World,
+    ty: NodeTy,
+    exported: &mut BTreeMap<Entity, ExportedNode>,
+) -> Result<(), CircuitScriptError>
+where
+    N: Node<I, O> + 'static,
+{
+    let nodes = world.read_storage::<Connected<N, I, O>>();
+    let positions = world.read_storage::<Pos>();
+    let names = world.read_storage::<Name>();
+    let delays = world.read_storage::<Delay>();
+    let keys = world.read_storage::<KeyBinding>();
+    let entities = world.entities();
+
+    for (node, pos, entity) in (&nodes, &positions, &entities).join() {
+        exported.insert(
+            entity,
+            ExportedNode {
+                ty,
+                name: names.get(entity).map(|name| name.0.clone()),
+                pos: pos.pos,
+                inputs: node.inputs.to_vec(),
+                outputs: node.outputs.to_vec(),
+                data: rhai::serde::to_dynamic(&node.node)?,
+                delay: delays.get(entity).map(|delay| delay.0),
+                key: keys.get(entity).map(|key| key.0),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Rhai fails to parse negative literals as object map values (`#{ y: -50.0 }`) so they're
+// wrapped in parentheses
+fn write_negatable(s: String) -> String {
+    if s.starts_with('-') {
+        format!("({})", s)
+    } else {
+        s
+    }
+}
+
+// Debug formatting would write escapes like \u{1b} which Rhai can't read, so only the escapes
+// Rhai knows are used
+fn write_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_key(key: &str) -> String {
+    if is_identifier(key) {
+        key.to_string()
+    } else {
+        write_string(key)
+    }
+}
+
+fn write_float(x: f64) -> String {
+    // Debug always includes the decimal point so it's read back as a float
+    write_negatable(format!("{:?}", x))
+}
+
+fn write_point(p: Vec2) -> String {
+    format!(
+        "#{{ x: {}, y: {} }}",
+        write_float(p.x as f64),
+        write_float(p.y as f64)
+    )
+}
 
-        let process_array = |name: &str| {
-            let arr = node.get(name).unwrap();
-            let arr = arr.clone_cast::<Array>();
-            arr.iter()
-                .map(|connection| {
-                    let connection = connection.clone_cast::<Array>();
-                    connection
-                        .iter()
-                        .map(|input| input.clone_cast::<String>())
-                        .collect::<Vec<String>>()
-                })
-                .collect::<Vec<Vec<String>>>()
+fn write_dynamic(value: &Dynamic) -> String {
+    if value.is::<()>() {
+        "()".to_string()
+    } else if let Some(b) = value.clone().try_cast::<bool>() {
+        b.to_string()
+    } else if let Ok(i) = value.as_int() {
+        write_negatable(i.to_string())
+    } else if let Ok(x) = value.as_float() {
+        write_float(x)
+    } else if let Some(s) = value.clone().try_cast::<rhai::ImmutableString>() {
+        write_string(s.as_str())
+    } else if let Some(arr) = value.clone().try_cast::<Array>() {
+        let items = arr.iter().map(write_dynamic).collect::<Vec<_>>();
+        format!("[{}]", items.join(", "))
+    } else if let Some(map) = value.clone().try_cast::<Map>() {
+        let fields = map
+            .iter()
+            .map(|(k, v)| format!("{}: {}", write_key(k), write_dynamic(v)))
+            .collect::<Vec<_>>();
+        format!("#{{ {} }}", fields.join(", "))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the whole circuit as a script in the layout read by `create_circuit`
+pub fn export_circuit(world: &World) -> Result<String, CircuitScriptError> {
+    // placed ones have a position, the one being created doesn't
+    let compound_nodes = world.read_storage::<CompoundNode>();
+    if (&compound_nodes, &world.read_storage::<Pos>())
+        .join()
+        .next()
+        .is_some()
+    {
+        return Err(CircuitScriptError::CompoundNodes);
+    }
+
+    let mut nodes = BTreeMap::new();
+
+    macro_rules! export_all_nodes {
+        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+            $(
+                export_nodes::<crate::nodes::$node, $i, $o>(world, NodeTy::$node, &mut nodes)?;
+            )*
         };
+    }
 
-        let input_wires = process_array("inputs");
-        let output_wires = process_array("inputs");
+    use crate::all_nodes;
+    all_nodes!(export_all_nodes);
+
+    let wires = world.read_storage::<Wire>();
+    let connections = world.read_storage::<Connection>();
+    let entities = world.entities();
+
+    let names = world.read_storage::<Name>();
+
+    // wires keep their names, unnamed ones get the first free `w{n}`
+    let mut wire_names = (&wires, &names, &entities)
+        .join()
+        .map(|(_, name, entity)| (entity, name.0.clone()))
+        .collect::<BTreeMap<Entity, String>>();
+    let mut next_name = 0..;
+    for (_, entity, _) in (&wires, &entities, !&names).join() {
+        let name = next_name
+            .by_ref()
+            .map(|i| format!("w{}", i))
+            .find(|name| !wire_names.values().any(|n| n == name))
+            .unwrap();
+        wire_names.insert(entity, name);
+    }
 
-        let pos = node.get("pos").unwrap().clone_cast::<Map>();
-        let x = pos.get("x").unwrap().clone_cast::<f32>();
-        let y = pos.get("y").unwrap().clone_cast::<f32>();
-        let pos = Vec2::new(x, y);
+    let mut script = String::from("let WIRES = #{\n");
+    for (wire, entity) in (&wires, &entities).join() {
+        // the first and last points are the ends of the wire, which come from the connections
+        let bends = match wire.points.len() {
+            0 | 1 => &[][..],
+            n => &wire.points[1..n - 1],
+        };
+        let bends = bends
+            .iter()
+            .map(|p| write_point(*p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        script.push_str(&format!(
+            "    {}: #{{ bends: [{}] }},\n",
+            write_key(&wire_names[&entity]),
+            bends
+        ));
+    }
+    script.push_str("};\n\nlet NODES = [\n");
+
+    let write_connections = |connection_entities: &[Entity]| {
+        let connections = connection_entities
+            .iter()
+            .map(|e| {
+                let names = connections
+                    .get(*e)
+                    .unwrap()
+                    .wires
+                    .iter()
+                    .filter_map(|wire| wire_names.get(wire))
+                    .map(|name| write_string(name))
+                    .collect::<Vec<_>>();
+                format!("[{}]", names.join(", "))
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", connections.join(", "))
+    };
 
-        RhaiNode {
-            ty,
-            input_wires,
-            output_wires,
-            pos,
+    for node in nodes.values() {
+        script.push_str("    #{\n");
+        script.push_str(&format!("        type: {:?},\n", node_ty_name(node.ty)));
+        if let Some(name) = &node.name {
+            script.push_str(&format!("        name: {},\n", write_string(name)));
         }
-    });
+        script.push_str(&format!("        pos: {},\n", write_point(node.pos)));
+        script.push_str(&format!(
+            "        inputs: {},\n",
+            write_connections(&node.inputs)
+        ));
+        script.push_str(&format!(
+            "        outputs: {},\n",
+            write_connections(&node.outputs)
+        ));
+        if let Some(delay) = node.delay {
+            script.push_str(&format!("        delay: {},\n", delay));
+        }
+        if let Some(key) = node.key {
+            script.push_str(&format!(
+                "        key: {},\n",
+                write_string(&key.to_string())
+            ));
+        }
+        // nodes without any configuration serialize to () or an empty map
+        let empty_map = node
+            .data
+            .clone()
+            .try_cast::<Map>()
+            .is_some_and(|m| m.is_empty());
+        if !node.data.is::<()>() && !empty_map {
+            script.push_str(&format!("        node: {},\n", write_dynamic(&node.data)));
+        }
+        script.push_str("    },\n");
+    }
+    script.push_str("];\n");
+
+    let delays = world.fetch::<NodeDelays>();
+    if !delays.0.is_empty() {
+        script.push_str("\nlet DELAYS = #{\n");
+        for (ty, delay) in delays.0.iter() {
+            script.push_str(&format!("    {}: {},\n", node_ty_name(*ty), delay));
+        }
+        script.push_str("};\n");
+    }
 
-    // have:
-    //      - nodes: Vec<RhaiNode>
-    //          - type, [[input wires]], [[output wires]], position
-    //          - wires are stored as name which is key to wire map
-    //      - wires: BTreeMap<String, Entity>
-    //          - Already instantiated in specs world
-    //
-    // pseudocode:
-    //      for each node:
-    //          1. Create the world in the specs world
-    //          2. Create a connection for each input/output on the node
-    //          3. Add the wires to the connection
+    Ok(script)
 }
 
-// pub struct CreateScriptedCircuitSys<N, const I: usize, const O: usize>
-// where
-//     N: Node<I, O> + 'static,
-// {
-//     node: PhantomData<N>,
-//     node_name: String,
-// }
-
-// impl<'a, N, const I: usize, const O: usize> System<'a> for CreateScriptedCircuitSys<N, I, O>
-// where
-//     N: Node<I, O> + 'static,
-// {
-//     type SystemData = (
-//         Read<'a, RhaiEngine>,
-//         Write<'a, RhaiScope<'static>>,
-//         WriteStorage<'a, Connected<N, I, O>>,
-//         WriteStorage<'a, Pos>,
-//         WriteStorage<'a, Wire>,
-//         WriteStorage<'a, Connection>,
-//         Entities<'a>,
-//     );
-
-//     fn run(
-//         &mut self,
-//         (engine, mut scope, mut nodes, mut positions, mut wires, mut connections, entities): Self::SystemData,
-//     ) {
-//         let circuit: Array = engine
-//             .0
-//             .eval_with_scope(&mut scope.0, "CIRCUIT")
-//             .unwrap_or(Array::new())
-//             .to_owned();
-
-//         circuit.iter().cloned().for_each(|node: Dynamic| {
-//             let map = node.cast::<Map>();
-//             let ty = map.get("type").unwrap();
-//             let type_str = ty.clone().take_immutable_string().unwrap();
-//             if type_str == self.node_name {
-//                 let pos = map.get("pos").unwrap();
-//                 let pos_arr = pos.clone_cast::<Array>();
-//                 let x = pos_arr[0].clone_cast::<f64>();
-//                 let y = pos_arr[1].clone_cast::<f64>();
-
-//                 let pos = Pos::from_vec((x as f32, y as f32).into());
-//                 let input_offsets = N::input_offsets();
-//                 let output_offsets = N::output_offsets();
-
-//                 let inputs = (0..I)
-//                     .map(|index| {
-//                         entities
-//                             .build_entity()
-//                             .with(
-//                                 Connection {
-//                                     wires: Vec::new(),
-//                                     ty: ConnectionTy::Input,
-//                                     index,
-//                                 },
-//                                 &mut connections,
-//                             )
-//                             .with(
-//                                 Pos::from_vec_unrounded(pos.pos + input_offsets[index]),
-//                                 &mut positions,
-//                             )
-//                             .build()
-//                     })
-//                     .collect::<Vec<_>>()
-//                     .try_into()
-//                     .unwrap();
-
-//                 let outputs = (0..O)
-//                     .map(|index| {
-//                         entities
-//                             .build_entity()
-//                             .with(
-//                                 Connection {
-//                                     wires: Vec::new(),
-//                                     ty: ConnectionTy::Output,
-//                                     index,
-//                                 },
-//                                 &mut connections,
-//                             )
-//                             .with(
-//                                 Pos::from_vec_unrounded(pos.pos + output_offsets[index]),
-//                                 &mut positions,
-//                             )
-//                             .build()
-//                     })
-//                     .collect::<Vec<_>>()
-//                     .try_into()
-//                     .unwrap();
-
-//                 entities
-//                     .build_entity()
-//                     .with(
-//                         Connected {
-//                             node: N::default(),
-//                             inputs,
-//                             outputs,
-//                         },
-//                         &mut nodes,
-//                     )
-//                     .with(pos, &mut positions)
-//                     .build();
-//             }
-//         });
-//     }
-// }
-
-// pub fn create_circuit(circuit: Array, world: &World) {
-//     fn create_node(node: &Dynamic, world: &World) -> Entity {
-//         let map = node.cast::<Map>();
-
-//         let outputs = {
-//             let outputs = map.get("outputs").unwrap();
-//             let output_arr = outputs.clone_cast::<Array>();
-//             output_arr
-//                 .iter()
-//                 .map(|node| create_node(node, world))
-//                 .collect::<Vec<_>>();
-//         };
-//         let pos = {
-//             let pos = map.get("pos").unwrap();
-//             let pos_arr = pos.clone_cast::<Array>();
-//             Pos::from_vec(Vec2::new(
-//                 pos_arr[0].clone_cast::<f32>(),
-//                 pos_arr[1].clone_cast::<f32>(),
-//             ))
-//         };
-
-//         // We have a list of output node entities
-//         todo!();
-//     }
-
-//     circuit.iter().cloned().for_each(|node: Dynamic| {
-//         create_node(&node, world);
-//     });
-// }
-
-// pub fn run_circuit_create_sys(script: String, world: &World) {
-//     use crate::all_nodes;
-//     use crate::nodes::*;
-
-//     {
-//         let engine = &world.fetch::<RhaiEngine>().0;
-//         let scope = &mut world.fetch_mut::<RhaiScope>().0;
-
-//         engine.eval_with_scope::<()>(scope, &script).unwrap();
-//     }
-
-//     macro_rules! run_sys {
-//         ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
-//             $(
-//                 CreateScriptedCircuitSys { node: PhantomData::<$node>, node_name: stringify!($node).to_string() }.run_now(world);
-//             )*
-//         };
-//     }
-
-//     all_nodes!(run_sys);
-// }
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,174 @@
+use super::*;
+use crate::components::nodes::{add_node_systems, AndNode, NotNode, SwitchNode};
+use crate::resources::CompoundNodeLibrary;
+use crate::systems::update_current_scope_sys::UpdateCurrentScopeSys;
+
+fn new_world() -> World {
+    let mut world = World::new();
+    add_node_systems(DispatcherBuilder::new())
+        .build()
+        .setup(&mut world);
+    System::setup(&mut UpdateCurrentScopeSys, &mut world);
+    System::setup(&mut PlaceNodeSys::<SwitchNode, 0, 1>::default(), &mut world);
+    System::setup(&mut PlaceNodeSys::<AndNode, 2, 1>::default(), &mut world);
+    System::setup(&mut PlaceNodeSys::<NotNode, 1, 1>::default(), &mut world);
+    world.register::<CompoundNode>();
+    world.register::<Name>();
+    world.register::<KeyBinding>();
+    world.insert(RhaiEngine::default());
+    world.insert(RhaiScope::default());
+    world.insert(CreatingCompoundNode::default());
+    world.insert(CompoundNodeLibrary::default());
+    world
+}
+
+#[test]
+fn creates_the_scripted_circuit() {
+    let mut world = new_world();
+    run_circuit_script(
+        include_str!("../../test_scripts/basic_circuit.rhai"),
+        &mut world,
+    )
+    .unwrap();
+
+    let switches = world.read_storage::<Connected<SwitchNode, 0, 1>>();
+    let states = switches
+        .join()
+        .map(|switch| switch.node.state)
+        .collect::<Vec<_>>();
+    assert_eq!(states.len(), 2);
+    assert_eq!(states.iter().filter(|state| **state).count(), 1);
+
+    let ands = world.read_storage::<Connected<AndNode, 2, 1>>();
+    let nots = world.read_storage::<Connected<NotNode, 1, 1>>();
+    let connections = world.read_storage::<Connection>();
+    let and = ands.join().next().unwrap();
+    let not = nots.join().next().unwrap();
+
+    // the and gate's output and the not gate's input share the "out" wire
+    let and_output = &connections.get(and.outputs[0]).unwrap().wires;
+    let not_input = &connections.get(not.inputs[0]).unwrap().wires;
+    assert_eq!(and_output.len(), 1);
+    assert_eq!(and_output, not_input);
+
+    // both ends are added around the bend
+    let wires = world.read_storage::<Wire>();
+    let out = wires.get(and_output[0]).unwrap();
+    assert_eq!(out.points.len(), 3);
+    assert_eq!(out.points[0], out.start_point);
+    assert_eq!(out.points[1], Vec2::new(250.0, 0.0));
+    assert_eq!(out.points[2], out.end_point);
+    assert_eq!(wires.join().count(), 3);
+}
+
+#[test]
+fn undefined_wires_are_reported_before_anything_is_added() {
+    let mut world = new_world();
+    let script = r#"
+        let WIRES = #{};
+        let NODES = [
+            #{ type: "Not", pos: #{ x: 0, y: 0 }, inputs: [["missing"]], outputs: [] },
+        ];
+    "#;
+
+    match run_circuit_script(script, &mut world) {
+        Err(CircuitScriptError::UnknownWire { wire, .. }) => assert_eq!(wire, "missing"),
+        other => panic!("expected an unknown wire error, got {:?}", other),
+    }
+    assert_eq!(world.entities().join().count(), 0);
+}
+
+#[test]
+fn bad_nodes_are_reported_before_anything_is_added() {
+    let mut world = new_world();
+    // the first node is fine, the second has one input too many
+    let script = r#"
+        let WIRES = #{ a: #{ bends: [] } };
+        let NODES = [
+            #{ type: "Switch", pos: #{ x: 0, y: 0 }, inputs: [], outputs: [["a"]] },
+            #{ type: "Not", pos: #{ x: 0, y: 0 }, inputs: [["a"], ["a"]], outputs: [] },
+        ];
+    "#;
+    assert!(matches!(
+        run_circuit_script(script, &mut world),
+        Err(CircuitScriptError::TooManyConnections { max: 1, .. })
+    ));
+    assert_eq!(world.entities().join().count(), 0);
+
+    let script = r#"
+        let WIRES = #{ a: #{ bends: [] } };
+        let NODES = [
+            #{ type: "Switch", pos: #{ x: 0, y: 0 }, inputs: [], outputs: [["a"]] },
+            #{ type: "Switch", pos: #{ x: 0, y: 0 }, inputs: [], outputs: [["a"]], node: #{ state: 3 } },
+        ];
+    "#;
+    assert!(matches!(
+        run_circuit_script(script, &mut world),
+        Err(CircuitScriptError::InvalidNodeData { .. })
+    ));
+    assert_eq!(world.entities().join().count(), 0);
+}
+
+#[test]
+fn unknown_node_types_are_reported() {
+    let mut world = new_world();
+    let script = r#"
+        let WIRES = #{};
+        let NODES = [#{ type: "Flux", pos: #{ x: 0, y: 0 }, inputs: [], outputs: [] }];
+    "#;
+
+    assert!(matches!(
+        run_circuit_script(script, &mut world),
+        Err(CircuitScriptError::UnknownNodeType(ty)) if ty == "Flux"
+    ));
+}
+
+#[test]
+fn exported_scripts_import_the_same_circuit() {
+    let mut world = new_world();
+    run_circuit_script(
+        include_str!("../../test_scripts/basic_circuit.rhai"),
+        &mut world,
+    )
+    .unwrap();
+    let script = export_circuit(&world).unwrap();
+
+    let mut imported = new_world();
+    run_circuit_script(&script, &mut imported).unwrap();
+
+    let positions = |world: &World| {
+        let mut positions = (&world.read_storage::<Pos>())
+            .join()
+            .map(|pos| (pos.pos.x as i32, pos.pos.y as i32))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions
+    };
+    assert_eq!(positions(&world), positions(&imported));
+    assert!(positions(&imported).contains(&(0, -50)));
+    assert_eq!(
+        (&imported.read_storage::<Connected<SwitchNode, 0, 1>>())
+            .join()
+            .filter(|switch| switch.node.state)
+            .count(),
+        1
+    );
+    assert_eq!((&imported.read_storage::<Wire>()).join().count(), 3);
+
+    // exporting again gives the same script
+    assert_eq!(export_circuit(&imported).unwrap(), script);
+}
+
+#[test]
+fn exported_strings_read_back_the_same() {
+    let engine = rhai::Engine::new();
+    for s in [
+        "plain",
+        "a \"quoted\" name",
+        "back\\slash",
+        "two\nlines\t\r",
+        "esc\u{1b}ape",
+    ] {
+        assert_eq!(engine.eval::<String>(&write_string(s)).unwrap(), s);
+    }
+}
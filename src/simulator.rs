@@ -0,0 +1,287 @@
+use crate::components::{
+    nodes::{add_node_systems, ButtonNode, DipSwitchNode, SwitchNode, Wire, DIP_SWITCH_BITS},
+    Bus, CompoundNode, Connected, KeyBinding, Name, NodeMarker, Pos,
+};
+use crate::resources::{
+    CompoundNodeLibrary, CreatingCompoundNode, MousePos, Oscillation, PendingRun, RhaiEngine,
+    RhaiScope, Schedule, TimeWheel,
+};
+use crate::save_load::{self, SaveError};
+use crate::scripting::{self, CircuitScriptError};
+use crate::systems::simulation_systems::{self, ResetSys};
+use crate::systems::update_current_scope_sys::UpdateCurrentScopeSys;
+use specs::hibitset::BitSetLike;
+use specs::prelude::*;
+
+/// Ticks `Simulator::run_until_stable` runs before deciding a circuit won't settle
+pub const SETTLE_LIMIT: usize = 10_000;
+
+/// What's left of a `PendingRun` after `Simulator::continue_run`
+#[derive(Debug, PartialEq, Eq)]
+pub enum RunProgress {
+    Running(PendingRun),
+    /// The run is over, with a status message saying how it ended
+    Finished(String),
+}
+
+/// Owns a circuit and runs the simulation systems on it without needing a window, so circuits
+/// can be run from tests and other tools as well as the GUI
+pub struct Simulator<'a, 'b> {
+    pub world: World,
+    dispatcher: Dispatcher<'a, 'b>,
+    ticks: usize,
+}
+
+impl<'a, 'b> Default for Simulator<'a, 'b> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, 'b> Simulator<'a, 'b> {
+    pub fn new() -> Self {
+        let mut world = World::new();
+
+        let mut dispatcher = {
+            let mut builder = DispatcherBuilder::new();
+            builder = add_node_systems(builder);
+            builder.build()
+        };
+        dispatcher.setup(&mut world);
+
+        world.register::<Pos>();
+        world.register::<CompoundNode>();
+        world.register::<NodeMarker>();
+        world.register::<Name>();
+        world.register::<KeyBinding>();
+        world.insert(CreatingCompoundNode::default());
+        world.insert(CompoundNodeLibrary::default());
+        world.insert(MousePos::default());
+        world.insert(RhaiEngine::default());
+        world.insert(RhaiScope::default());
+        System::setup(&mut UpdateCurrentScopeSys, &mut world);
+        System::setup(&mut ResetSys, &mut world);
+
+        Simulator {
+            world,
+            dispatcher,
+            ticks: 0,
+        }
+    }
+
+    /// Runs `n` ticks of the simulation
+    pub fn step(&mut self, n: usize) {
+        for _ in 0..n {
+            self.dispatcher.dispatch_seq(&self.world);
+            self.world.maintain();
+        }
+        self.ticks += n;
+    }
+
+    /// Whether the last tick changed no wires and no delayed changes are waiting, so further
+    /// ticks do nothing until a switch is flipped
+    pub fn is_stable(&self) -> bool {
+        let schedule = self.world.fetch::<Schedule>();
+        !schedule.all
+            && schedule.changed_wires.is_empty()
+            && self.world.fetch::<TimeWheel>().is_empty()
+    }
+
+    /// Carries on with a run started from the UI for as long as `in_budget` allows
+    pub fn continue_run(
+        &mut self,
+        run: PendingRun,
+        mut in_budget: impl FnMut() -> bool,
+    ) -> RunProgress {
+        match run {
+            PendingRun::Ticks(mut left) => {
+                while left > 0 && in_budget() {
+                    self.step(1);
+                    left -= 1;
+                }
+                if left == 0 {
+                    RunProgress::Finished(format!("Tick {}", self.ticks()))
+                } else {
+                    RunProgress::Running(PendingRun::Ticks(left))
+                }
+            }
+            PendingRun::UntilStable(mut ran) => {
+                while in_budget() {
+                    self.step(1);
+                    ran += 1;
+                    if self.is_stable() {
+                        return RunProgress::Finished(format!("Stable after {} ticks", ran));
+                    } else if ran >= SETTLE_LIMIT {
+                        return RunProgress::Finished(format!(
+                            "Still changing after {} ticks",
+                            ran
+                        ));
+                    }
+                }
+                RunProgress::Running(PendingRun::UntilStable(ran))
+            }
+            PendingRun::UntilChange(mut ran) => {
+                while in_budget() {
+                    self.step(1);
+                    ran += 1;
+                    if self.world.fetch::<Schedule>().outputs_changed {
+                        return RunProgress::Finished(format!("Changed after {} ticks", ran));
+                    } else if ran >= SETTLE_LIMIT {
+                        return RunProgress::Finished(format!(
+                            "Nothing changed after {} ticks",
+                            ran
+                        ));
+                    }
+                }
+                RunProgress::Running(PendingRun::UntilChange(ran))
+            }
+        }
+    }
+
+    /// Runs ticks until the circuit is stable, at least one so switch changes get picked up.
+    /// Returns the number of ticks run, or None if it was still changing after `limit` ticks.
+    pub fn run_until_stable(&mut self, limit: usize) -> Option<usize> {
+        for ticks in 1..=limit {
+            self.step(1);
+            if self.is_stable() {
+                return Some(ticks);
+            }
+        }
+        None
+    }
+
+    /// Runs ticks until the state of any wire changes. Returns the number of ticks run, or None
+    /// if nothing changed within `limit` ticks.
+    pub fn step_until_change(&mut self, limit: usize) -> Option<usize> {
+        for ticks in 1..=limit {
+            self.step(1);
+            if self.world.fetch::<Schedule>().outputs_changed {
+                return Some(ticks);
+            }
+        }
+        None
+    }
+
+    /// Undoes the last tick, as long as it's still in the `History`. Returns false if there are
+    /// no recorded ticks left.
+    pub fn step_back(&mut self) -> bool {
+        let ticks = match self.ticks.checked_sub(1) {
+            Some(ticks) => ticks,
+            None => return false,
+        };
+        let stepped = simulation_systems::step_back(&self.world);
+        if stepped {
+            self.ticks = ticks;
+        }
+        stepped
+    }
+
+    /// Nodes still changing long after the circuit's inputs last changed, empty unless the
+    /// circuit is oscillating
+    pub fn oscillating_nodes(&self) -> Vec<Entity> {
+        let oscillation = self.world.fetch::<Oscillation>();
+        (&self.world.entities(), &oscillation.nodes)
+            .join()
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// Number of ticks run since the simulator was created, reset or loaded a file
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    /// Sets every wire back to low, resets the state held by nodes like flip-flops and forgets
+    /// the history recorded for stepping back
+    pub fn reset(&mut self) {
+        simulation_systems::run_reset_systems(&self.world);
+        self.ticks = 0;
+    }
+
+    /// Sets the state of every switch with the given name, or holds down or lets go of every
+    /// button with it, returning false if there are none
+    pub fn set_switch(&mut self, name: &str, state: bool) -> bool {
+        let mut switches = self.world.write_storage::<Connected<SwitchNode, 0, 1>>();
+        let mut buttons = self.world.write_storage::<Connected<ButtonNode, 0, 1>>();
+        let names = self.world.read_storage::<Name>();
+
+        let mut found = false;
+        for (switch, _) in (&mut switches, &names)
+            .join()
+            .filter(|(_, switch_name)| switch_name.0 == name)
+        {
+            switch.node.state = state;
+            found = true;
+        }
+        for (button, _) in (&mut buttons, &names)
+            .join()
+            .filter(|(_, button_name)| button_name.0 == name)
+        {
+            button.node.pressed = state;
+            found = true;
+        }
+        found
+    }
+
+    /// Sets the value of every DIP switch with the given name, returning false if there are none
+    pub fn set_number(&mut self, name: &str, value: u32) -> bool {
+        let mut dips = self
+            .world
+            .write_storage::<Connected<DipSwitchNode, 0, DIP_SWITCH_BITS>>();
+        let names = self.world.read_storage::<Name>();
+
+        let mut found = false;
+        for (dip, _) in (&mut dips, &names)
+            .join()
+            .filter(|(_, dip_name)| dip_name.0 == name)
+        {
+            dip.node.value = value;
+            found = true;
+        }
+        found
+    }
+
+    /// The state at the output end of the named wire
+    pub fn wire_state(&self, name: &str) -> Option<Bus> {
+        let wires = self.world.read_storage::<Wire>();
+        let names = self.world.read_storage::<Name>();
+
+        (&wires, &names)
+            .join()
+            .find(|(_, wire_name)| wire_name.0 == name)
+            .map(|(wire, _)| wire.output_state)
+    }
+
+    pub fn named_wires(&self) -> Vec<(String, Bus)> {
+        let wires = self.world.read_storage::<Wire>();
+        let names = self.world.read_storage::<Name>();
+
+        (&wires, &names)
+            .join()
+            .map(|(wire, name)| (name.0.clone(), wire.output_state))
+            .collect()
+    }
+
+    /// Removes everything, leaving the simulator as it was when created
+    pub fn clear(&mut self) {
+        save_load::clear_circuit(&mut self.world);
+        self.ticks = 0;
+    }
+
+    /// Replaces the circuit with the one in the file, starting its simulation over
+    pub fn load_file(&mut self, path: &str) -> Result<(), SaveError> {
+        // loading also clears the recorded history, so there's nothing to step back into
+        save_load::load_from_file(&mut self.world, path)?;
+        self.ticks = 0;
+        Ok(())
+    }
+
+    pub fn save_file(&self, path: &str) -> Result<(), SaveError> {
+        save_load::save_to_file(&self.world, path)
+    }
+
+    /// Adds the circuit described by the script to the world
+    pub fn load_script(&mut self, script: &str) -> Result<(), CircuitScriptError> {
+        scripting::run_circuit_script(script, &mut self.world)
+    }
+}
@@ -1,7 +1,9 @@
 pub mod cleanup_sys;
+#[cfg(feature = "gui")]
 pub mod draw_systems;
 pub mod place_node_sys;
 pub mod place_wire_sys;
 pub mod simulation_systems;
+#[cfg(feature = "gui")]
 pub mod ui_systems;
 pub mod update_current_scope_sys;
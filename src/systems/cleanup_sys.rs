@@ -2,11 +2,15 @@ use std::marker::PhantomData;
 
 use crate::components::nodes::*;
 use crate::components::Node;
-use crate::{components::Connection, Connected};
+use crate::{
+    components::{CompoundNode, Connection},
+    Connected,
+};
 use specs::prelude::*;
 
+// drops deleted wires from the connections at their other ends
 #[derive(Default)]
-pub struct CleanupWires;
+struct CleanupWires;
 impl<'a> System<'a> for CleanupWires {
     type SystemData = (WriteStorage<'a, Connection>, Entities<'a>);
 
@@ -56,7 +60,38 @@ where
     }
 }
 
-pub fn run_cleanup_systems(entity: Entity, world: &World) {
+/// Deletes the inside of a compound node, along with the wires going to its pins
+pub struct CleanupCompoundNodeSys {
+    entity: Entity,
+}
+
+impl<'a> System<'a> for CleanupCompoundNodeSys {
+    type SystemData = (
+        ReadStorage<'a, CompoundNode>,
+        ReadStorage<'a, Connection>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (compound_nodes, connections, entities): Self::SystemData) {
+        if let Some(compound_node) = compound_nodes.get(self.entity) {
+            compound_node
+                .inputs
+                .iter()
+                .chain(compound_node.outputs.iter())
+                .filter_map(|pin| connections.get(*pin))
+                .for_each(|connection| {
+                    connection.wires.iter().for_each(|wire| {
+                        entities.delete(*wire).unwrap();
+                    });
+                });
+            compound_node.inner.iter().for_each(|inner| {
+                entities.delete(*inner).unwrap();
+            });
+        }
+    }
+}
+
+fn run_cleanup_systems(entity: Entity, world: &World) {
     use crate::all_nodes;
 
     macro_rules! run_cleanup_sys {
@@ -71,4 +106,13 @@ pub fn run_cleanup_systems(entity: Entity, world: &World) {
     }
 
     all_nodes!(run_cleanup_sys);
+    CleanupCompoundNodeSys { entity }.run_now(world);
+}
+
+/// Deletes a node or compound node with its connections and every wire attached to them
+pub fn delete_node(entity: Entity, world: &mut World) {
+    world.entities().delete(entity).unwrap();
+    run_cleanup_systems(entity, world);
+    world.maintain();
+    CleanupWires.run_now(world);
 }
@@ -1,3 +1,18 @@
+use crate::compound::{pin_offsets, COMPOUND_NODE_HALF_WIDTH};
+use crate::nodes::gate_height;
+use crate::nodes::{memory_size, RamNode, RomNode, TFlipFlopNode};
+use crate::nodes::{AdderNode, ComparatorNode, FullAdderNode, HalfAdderNode};
+use crate::nodes::{ButtonNode, DipSwitchNode, DIP_SWITCH_BITS, LED_MATRIX_SIZE};
+use crate::nodes::{ClockNode, DFlipFlopNode, DLatchNode, JkFlipFlopNode, SrLatchNode};
+use crate::nodes::{Counter4Node, Counter8Node, Register4Node, Register8Node};
+use crate::nodes::{Decoder4Node, Decoder8Node, Demux2Node, Demux4Node, Demux8Node};
+use crate::nodes::{DecoderNode, DemuxNode, EncoderNode, MuxNode};
+use crate::nodes::{Encoder4Node, Encoder8Node, Mux2Node, Mux4Node, Mux8Node};
+use crate::nodes::{HexDigitNode, LedColor, LedMatrixNode, LedNode, SevenSegmentNode};
+use crate::nodes::{InputPortNode, OutputPortNode};
+use crate::nodes::{Merger4Node, Merger8Node, Splitter4Node, Splitter8Node};
+use crate::nodes::{ShiftRegister4Node, ShiftRegister8Node};
+use crate::resources::{CombinationalLoops, FanInPolicy, Oscillation};
 use crate::{components::nodes::NorNode, nodes::OnNode};
 use crate::{components::nodes::XnorNode, nodes::XorNode};
 use crate::{
@@ -5,29 +20,34 @@ use crate::{
     resources::TickProgress,
     resources::UIState,
 };
-use crate::{components::Connection, nodes::SwitchNode};
+use crate::{components::Connection, nodes::SwitchNode, nodes::TriStateNode};
 use crate::{
     components::{nodes::AndNode, Node},
-    resources::Textures,
+    ui::resources::Textures,
 };
 use crate::{
-    components::{nodes::NandNode, CurrentScope},
+    components::{
+        nodes::NandNode, Bus, CompoundNode, CurrentScope, KeyBinding, NodeWarning, Signal,
+    },
     nodes::NotNode,
 };
-use crate::{resources::CameraRes, Wire};
 use crate::{resources::GridMode, Connected};
 use crate::{resources::MousePos, Pos};
+use crate::{ui::resources::CameraRes, Wire};
 use core::marker::PhantomData;
 use macroquad::prelude::*;
 use specs::prelude::*;
 use std::sync::Arc;
 
+// the last argument is the state of each input, as read by the node
+type DrawFn<N, const I: usize> = Arc<dyn Fn(&N, Pos, &Textures, &[Bus; I])>;
+
 pub struct DrawNodeSys<N, const I: usize, const O: usize>
 where
     N: Node<I, O> + 'static,
 {
     node: PhantomData<N>,
-    draw_fn: Arc<dyn Fn(&N, Pos, &Textures)>,
+    draw_fn: DrawFn<N, I>,
 }
 
 impl<'a, N, const I: usize, const O: usize> System<'a> for DrawNodeSys<N, I, O>
@@ -39,13 +59,31 @@ where
         ReadStorage<'a, Connected<N, I, O>>,
         ReadStorage<'a, CurrentScope>,
         Read<'a, Textures>,
+        ReadStorage<'a, Connection>,
+        ReadStorage<'a, Wire>,
+        Read<'a, FanInPolicy>,
     );
 
-    fn run(&mut self, (positions, nodes, current_scope_markers, textures): Self::SystemData) {
+    fn run(
+        &mut self,
+        (positions, nodes, current_scope_markers, textures, connections, wires, fan_in): Self::SystemData,
+    ) {
+        // merged the same way ElectroSys merges them
+        let input_state = |input: &Entity| {
+            let connection = connections.get(*input).unwrap();
+            let buses = connection
+                .wires
+                .iter()
+                .filter_map(|wire| wires.get(*wire))
+                .map(|wire| wire.output_state);
+            fan_in.0.resolve_bus(buses)
+        };
+
         (&positions, &nodes, &current_scope_markers)
             .join()
             .for_each(|(self_pos, node, _)| {
-                (self.draw_fn)(&node.node, *self_pos, &textures);
+                let inputs = node.inputs.each_ref().map(input_state);
+                (self.draw_fn)(&node.node, *self_pos, &textures, &inputs);
             });
     }
 }
@@ -85,6 +123,25 @@ impl<'a> Points<'a> {
     }
 }
 
+fn bus_color(bus: &Bus) -> Color {
+    if bus.width() == 1 {
+        return match bus.bit(0) {
+            Signal::Low => WHITE,
+            Signal::High => RED,
+            Signal::Z => GRAY,
+            Signal::X => ORANGE,
+        };
+    }
+
+    if bus.bits().iter().all(|bit| *bit == Signal::Z) {
+        GRAY
+    } else if bus.to_u64().is_some() {
+        SKYBLUE
+    } else {
+        ORANGE
+    }
+}
+
 pub struct DrawWireSys;
 impl<'a> System<'a> for DrawWireSys {
     type SystemData = (
@@ -108,50 +165,60 @@ impl<'a> System<'a> for DrawWireSys {
 
                 let mut new_col_len_remaining = tick_progress.0 as f32 * total_len;
 
+                let (new_col, old_col) =
+                    (bus_color(&wire.input_state), bus_color(&wire.output_state));
+                let thickness = if wire.input_state.width() > 1 || wire.output_state.width() > 1 {
+                    9.0
+                } else {
+                    5.0
+                };
+
                 if wire.output_state == wire.input_state {
                     new_col_len_remaining = total_len;
                 }
 
-                let (new_col, old_col) = if wire.input_state {
-                    (RED, WHITE)
+                // short circuits stand out from X's that were only passed along
+                let (new_col, old_col) = if wire.conflict {
+                    new_col_len_remaining = total_len;
+                    (MAGENTA, MAGENTA)
                 } else {
-                    (WHITE, RED)
+                    (new_col, old_col)
                 };
 
                 points.for_each(|sp, ep| {
                     // vertical
                     if new_col_len_remaining > (ep.y - sp.y).abs() {
-                        draw_line(sp.x, sp.y, sp.x, ep.y, 5.0, new_col);
+                        draw_line(sp.x, sp.y, sp.x, ep.y, thickness, new_col);
                         new_col_len_remaining -= (sp.y - ep.y).abs();
                     } else if new_col_len_remaining <= 0.0 {
-                        draw_line(sp.x, sp.y, sp.x, ep.y, 5.0, old_col);
+                        draw_line(sp.x, sp.y, sp.x, ep.y, thickness, old_col);
                     } else {
                         let diff = (ep.y - sp.y).signum();
                         let midpoint = new_col_len_remaining * diff + sp.y;
 
-                        draw_line(sp.x, sp.y, sp.x, midpoint, 5.0, new_col);
-                        draw_line(sp.x, midpoint, sp.x, ep.y, 5.0, old_col);
+                        draw_line(sp.x, sp.y, sp.x, midpoint, thickness, new_col);
+                        draw_line(sp.x, midpoint, sp.x, ep.y, thickness, old_col);
                         new_col_len_remaining = 0.0
                     }
 
                     // horizontal
                     if new_col_len_remaining > (ep.x - sp.x).abs() {
-                        draw_line(sp.x, ep.y, ep.x, ep.y, 5.0, new_col);
-                        draw_circle(sp.x, ep.y, 5.0, new_col);
-                        draw_circle(ep.x, ep.y, 5.0, new_col);
+                        draw_line(sp.x, ep.y, ep.x, ep.y, thickness, new_col);
+                        draw_circle(sp.x, ep.y, thickness, new_col);
+                        draw_circle(ep.x, ep.y, thickness, new_col);
                         new_col_len_remaining -= (sp.x - ep.x).abs();
                     } else if new_col_len_remaining <= 0.0 {
-                        draw_line(sp.x, ep.y, ep.x, ep.y, 5.0, old_col);
-                        draw_circle(sp.x, ep.y, 5.0, old_col);
-                        draw_circle(ep.x, ep.y, 5.0, old_col);
+                        draw_line(sp.x, ep.y, ep.x, ep.y, thickness, old_col);
+                        draw_circle(sp.x, ep.y, thickness, old_col);
+                        draw_circle(ep.x, ep.y, thickness, old_col);
                     } else {
                         let diff = (ep.x - sp.x).signum();
                         let midpoint = new_col_len_remaining * diff + sp.x;
 
-                        draw_line(sp.x, ep.y, midpoint, ep.y, 5.0, new_col);
-                        draw_line(midpoint, ep.y, ep.x, ep.y, 5.0, old_col);
-                        draw_circle(sp.x, ep.y, 5.0, new_col);
-                        draw_circle(ep.x, ep.y, 5.0, old_col);
+                        draw_line(sp.x, ep.y, midpoint, ep.y, thickness, new_col);
+                        draw_line(midpoint, ep.y, ep.x, ep.y, thickness, old_col);
+                        draw_circle(sp.x, ep.y, thickness, new_col);
+                        draw_circle(ep.x, ep.y, thickness, old_col);
                         new_col_len_remaining = 0.0
                     }
                 });
@@ -159,6 +226,57 @@ impl<'a> System<'a> for DrawWireSys {
     }
 }
 
+/// Writes the value of every bus next to the start of the wire
+pub struct DrawBusLabelSys;
+impl<'a> System<'a> for DrawBusLabelSys {
+    type SystemData = (
+        ReadStorage<'a, Wire>,
+        ReadStorage<'a, CurrentScope>,
+        Read<'a, CameraRes>,
+    );
+
+    fn run(&mut self, (wires, current_scope_markers, camera_res): Self::SystemData) {
+        // world space is y up which would draw text upside down, so labels are drawn in screen
+        // space instead
+        set_default_camera();
+        (&wires, &current_scope_markers)
+            .join()
+            .filter(|(wire, _)| wire.output_state.width() > 1)
+            .for_each(|(wire, _)| {
+                let next = wire.points.first().unwrap_or(&wire.end_point);
+                let pos = camera_res
+                    .0
+                    .world_to_screen((wire.start_point + *next) / 2.0);
+                draw_text(
+                    &wire.output_state.to_string(),
+                    pos.x,
+                    pos.y - 8.0,
+                    20.0,
+                    bus_color(&wire.output_state),
+                );
+            });
+        set_camera(camera_res.0);
+    }
+}
+
+/// Shows the key bound to each switch or button in a box above it
+pub struct DrawKeyBindingSys;
+impl<'a> System<'a> for DrawKeyBindingSys {
+    type SystemData = (
+        ReadStorage<'a, KeyBinding>,
+        ReadStorage<'a, Pos>,
+        ReadStorage<'a, CurrentScope>,
+    );
+
+    fn run(&mut self, (keys, positions, current_scope_markers): Self::SystemData) {
+        for (key, Pos { pos, .. }, _) in (&keys, &positions, &current_scope_markers).join() {
+            draw_rectangle(pos.x - 10.0, pos.y + 32.0, 20.0, 20.0, WHITE);
+            draw_rectangle_lines(pos.x - 10.0, pos.y + 32.0, 20.0, 20.0, 2.0, BLACK);
+            draw_world_text(&key.0.to_string(), *pos + Vec2::new(0.0, 42.0), 18.0, BLACK);
+        }
+    }
+}
+
 pub struct TempWireDrawSys;
 impl<'a> System<'a> for TempWireDrawSys {
     type SystemData = (Read<'a, UIState>, ReadStorage<'a, Pos>, Read<'a, MousePos>);
@@ -180,7 +298,7 @@ impl<'a> System<'a> for TempWireDrawSys {
 
             let points = Points {
                 start_point,
-                points: &wire_points,
+                points: wire_points,
                 end_point: &mouse_pos,
             };
 
@@ -197,6 +315,69 @@ impl<'a> System<'a> for TempWireDrawSys {
     }
 }
 
+/// Draws placed compound nodes as a box named after them with the name of the port inside next
+/// to each pin, the pins are drawn as connections
+pub struct DrawCompoundNodeSys;
+impl<'a> System<'a> for DrawCompoundNodeSys {
+    type SystemData = (
+        ReadStorage<'a, CompoundNode>,
+        ReadStorage<'a, Connected<InputPortNode, 1, 1>>,
+        ReadStorage<'a, Connected<OutputPortNode, 1, 1>>,
+        ReadStorage<'a, Pos>,
+        ReadStorage<'a, CurrentScope>,
+    );
+
+    fn run(
+        &mut self,
+        (compound_nodes, input_ports, output_ports, positions, current_scope_markers): Self::SystemData,
+    ) {
+        (&compound_nodes, &positions, &current_scope_markers)
+            .join()
+            .for_each(|(compound_node, Pos { pos, .. }, _)| {
+                let pins = compound_node.inputs.len().max(compound_node.outputs.len());
+                let top = pins.saturating_sub(1) as f32 * 10.0 + 20.0;
+                let left = pos.x - COMPOUND_NODE_HALF_WIDTH;
+                let width = COMPOUND_NODE_HALF_WIDTH * 2.0;
+
+                draw_rectangle(left, pos.y - top, width, top * 2.0, WHITE);
+                draw_rectangle_lines(left, pos.y - top, width, top * 2.0, 2.5, BLACK);
+                // above the box, the pin labels fill the inside
+                draw_world_text(
+                    &compound_node.name,
+                    Vec2::new(pos.x, pos.y + top + 10.0),
+                    16.0,
+                    WHITE,
+                );
+
+                let input_names = compound_node.inputs.iter().map(|pin| {
+                    input_ports
+                        .join()
+                        .find(|port| port.inputs[0] == *pin)
+                        .map_or("", |port| port.node.name.as_str())
+                });
+                let output_names = compound_node.outputs.iter().map(|pin| {
+                    output_ports
+                        .join()
+                        .find(|port| port.outputs[0] == *pin)
+                        .map_or("", |port| port.node.name.as_str())
+                });
+
+                // each label lines up with the inside edge of the box
+                let draw_label = |name: &str, offset: Vec2, side: f32| {
+                    let half = measure_text(name, None, 14, 1.0).width / 2.0;
+                    let x = pos.x + side * (COMPOUND_NODE_HALF_WIDTH - 4.0 - half);
+                    draw_world_text(name, Vec2::new(x, pos.y + offset.y), 14.0, BLACK);
+                };
+                input_names
+                    .zip(pin_offsets(0.0, compound_node.inputs.len()))
+                    .for_each(|(name, offset)| draw_label(name, offset, -1.0));
+                output_names
+                    .zip(pin_offsets(0.0, compound_node.outputs.len()))
+                    .for_each(|(name, offset)| draw_label(name, offset, 1.0));
+            });
+    }
+}
+
 pub struct DrawConnectionSys;
 impl<'a> System<'a> for DrawConnectionSys {
     type SystemData = (
@@ -226,6 +407,71 @@ impl<'a> System<'a> for DrawConnectionSys {
     }
 }
 
+pub struct DrawNodeWarningSys;
+impl<'a> System<'a> for DrawNodeWarningSys {
+    type SystemData = (
+        ReadStorage<'a, NodeWarning>,
+        ReadStorage<'a, Pos>,
+        ReadStorage<'a, CurrentScope>,
+    );
+
+    fn run(&mut self, (warnings, positions, current_scope_markers): Self::SystemData) {
+        (&warnings, &positions, &current_scope_markers)
+            .join()
+            .for_each(|(warning, Pos { pos, .. }, _)| {
+                let color = match warning {
+                    NodeWarning::UnconnectedInput => YELLOW,
+                    NodeWarning::ConflictingInput => ORANGE,
+                    NodeWarning::WidthMismatch => SKYBLUE,
+                };
+
+                // a small exclamation mark badge in the top right corner of the node
+                // world space is y up
+                let (x, y) = (pos.x + 25.0, pos.y + 25.0);
+                draw_circle(x, y, 10.0, color);
+                draw_circle_lines(x, y, 10.0, 2.0, BLACK);
+                draw_rectangle(x - 1.5, y - 1.0, 3.0, 7.0, BLACK);
+                draw_circle(x, y - 4.5, 1.5, BLACK);
+            });
+    }
+}
+
+/// Rings nodes found in combinational loops in magenta and nodes which are oscillating in red
+pub struct DrawLoopSys;
+impl<'a> System<'a> for DrawLoopSys {
+    type SystemData = (
+        Read<'a, CombinationalLoops>,
+        Read<'a, Oscillation>,
+        ReadStorage<'a, Pos>,
+        ReadStorage<'a, CurrentScope>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (loops, oscillation, positions, current_scope_markers, entities): Self::SystemData,
+    ) {
+        let draw_ring = |entity: Entity, radius: f32, color: Color| {
+            // the loop may be stale, nodes can have been deleted since it was found
+            if !entities.is_alive(entity) || !current_scope_markers.contains(entity) {
+                return;
+            }
+            if let Some(Pos { pos, .. }) = positions.get(entity) {
+                draw_circle_lines(pos.x, pos.y, radius, 3.0, color);
+            }
+        };
+
+        loops
+            .0
+            .iter()
+            .flatten()
+            .for_each(|entity| draw_ring(*entity, 40.0, MAGENTA));
+        (&entities, &oscillation.nodes)
+            .join()
+            .for_each(|(entity, _)| draw_ring(entity, 46.0, RED));
+    }
+}
+
 pub struct DrawGridSys;
 impl<'a> System<'a> for DrawGridSys {
     type SystemData = (Read<'a, GridMode>, Read<'a, CameraRes>);
@@ -319,26 +565,105 @@ impl<'a> System<'a> for DrawGridSys {
     }
 }
 
+// the texture of a kind of gate, drawn at `size` with its center `center_y` above the node
+struct GateTexture {
+    name: &'static str,
+    size: Vec2,
+    center_y: f32,
+}
+
+const AND_GATE: GateTexture = GateTexture {
+    name: "AND_GATE",
+    size: const_vec2!([75.0, 50.0]),
+    center_y: 0.0,
+};
+const OR_GATE: GateTexture = GateTexture {
+    name: "OR_GATE",
+    size: const_vec2!([80.0, 60.0]),
+    center_y: 0.5,
+};
+const NAND_GATE: GateTexture = GateTexture {
+    name: "NAND_GATE",
+    size: const_vec2!([80.0, 60.0]),
+    center_y: 0.5,
+};
+const NOR_GATE: GateTexture = GateTexture {
+    name: "NOR_GATE",
+    size: const_vec2!([80.0, 60.0]),
+    center_y: 0.5,
+};
+const XOR_GATE: GateTexture = GateTexture {
+    name: "XOR_GATE",
+    size: const_vec2!([80.0, 60.0]),
+    center_y: 2.5,
+};
+const XNOR_GATE: GateTexture = GateTexture {
+    name: "XNOR_GATE",
+    size: const_vec2!([80.0, 60.0]),
+    center_y: 2.5,
+};
+
+// textures are drawn for two inputs and stretched vertically so their inputs cover the pins of
+// gates with more
+fn gate_draw_sys<N, const I: usize>(texture: &'static GateTexture) -> DrawNodeSys<N, I, 1>
+where
+    N: Node<I, 1> + 'static,
+{
+    let stretch = gate_height(I) / gate_height(2);
+    let size = Vec2::new(texture.size.x, texture.size.y * stretch);
+    let center_y = texture.center_y * stretch;
+    DrawNodeSys {
+        node: PhantomData,
+        draw_fn: Arc::new(move |_, Pos { pos, .. }, textures: &Textures, _| {
+            let corner = pos + Vec2::new(0.0, center_y) - size / 2.0;
+            draw_texture_ex(
+                *textures.0.get(texture.name).unwrap(),
+                corner.x,
+                corner.y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(size),
+                    ..DrawTextureParams::default()
+                },
+            );
+        }),
+    }
+}
+
+// every input count of one kind of gate, up to MAX_GATE_INPUTS
+macro_rules! with_gate_draw_systems {
+    ($builder:expr, $gate:ident, $texture:expr) => {
+        $builder
+            .with_thread_local(gate_draw_sys::<$gate<2>, 2>($texture))
+            .with_thread_local(gate_draw_sys::<$gate<3>, 3>($texture))
+            .with_thread_local(gate_draw_sys::<$gate<4>, 4>($texture))
+            .with_thread_local(gate_draw_sys::<$gate<5>, 5>($texture))
+            .with_thread_local(gate_draw_sys::<$gate<6>, 6>($texture))
+            .with_thread_local(gate_draw_sys::<$gate<7>, 7>($texture))
+            .with_thread_local(gate_draw_sys::<$gate<8>, 8>($texture))
+    };
+}
+
 pub fn add_draw_system<'a, 'b>(builder: DispatcherBuilder<'a, 'b>) -> DispatcherBuilder<'a, 'b> {
-    builder
+    let builder = builder
         .with_thread_local(DrawGridSys)
         .with_thread_local(DrawWireSys)
         .with_thread_local(TempWireDrawSys)
         .with_thread_local(DrawNodeSys {
             node: PhantomData::<OnNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, _| {
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
                 draw_circle(pos.x, pos.y, 25.0, RED);
             }),
         })
         .with_thread_local(DrawNodeSys {
             node: PhantomData::<OffNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, _| {
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
                 draw_circle(pos.x, pos.y, 25.0, WHITE);
             }),
         })
         .with_thread_local(DrawNodeSys {
             node: PhantomData::<NotNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
+            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures, _| {
                 let texture = textures.0.get("NOT_GATE").unwrap();
                 let w = 50.0;
                 let h = 50.0;
@@ -353,129 +678,531 @@ pub fn add_draw_system<'a, 'b>(builder: DispatcherBuilder<'a, 'b>) -> Dispatcher
                     },
                 );
             }),
+        });
+    let builder = with_gate_draw_systems!(builder, AndNode, &AND_GATE);
+    let builder = with_gate_draw_systems!(builder, OrNode, &OR_GATE);
+    let builder = with_gate_draw_systems!(builder, NandNode, &NAND_GATE);
+    let builder = with_gate_draw_systems!(builder, NorNode, &NOR_GATE);
+    let builder = with_gate_draw_systems!(builder, XorNode, &XOR_GATE);
+    let builder = with_gate_draw_systems!(builder, XnorNode, &XNOR_GATE);
+    builder
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Wire>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _: &Textures, _| {
+                draw_circle(pos.x, pos.y, 10.0, WHITE);
+            }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<AndNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
-                let texture = textures.0.get("AND_GATE").unwrap();
-                let w = 75.0;
-                let h = 50.0;
-                draw_texture_ex(
-                    *texture,
-                    pos.x - w / 2.0,
-                    pos.y - h / 2.0,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(Vec2::new(w, h)),
-                        ..DrawTextureParams::default()
-                    },
-                );
+            node: PhantomData::<SwitchNode>,
+            draw_fn: Arc::new(|node: &SwitchNode, Pos { pos, .. }, _: &Textures, _| {
+                let color = if node.state { RED } else { WHITE };
+                draw_rectangle(pos.x - 30.0, pos.y - 30.0, 60.0, 60.0, WHITE);
+                draw_circle(pos.x, pos.y, 25.0, color);
+                draw_circle_lines(pos.x, pos.y, 25.0, 2.5, BLACK);
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<OrNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
-                let texture = textures.0.get("OR_GATE").unwrap();
-                let w = 100.0;
-                let h = 75.0;
-                draw_texture_ex(
-                    *texture,
-                    pos.x - w / 2.0 + w * 0.1,
-                    pos.y - h / 2.0 + w * 0.08,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(Vec2::new(w * 0.8, h * 0.8)),
-                        ..DrawTextureParams::default()
-                    },
-                );
+            node: PhantomData::<ButtonNode>,
+            draw_fn: Arc::new(|node: &ButtonNode, Pos { pos, .. }, _: &Textures, _| {
+                draw_rectangle(pos.x - 25.0, pos.y - 25.0, 50.0, 50.0, LIGHTGRAY);
+                let color = if node.pressed { RED } else { MAROON };
+                draw_circle(pos.x, pos.y, 18.0, color);
+                draw_circle_lines(pos.x, pos.y, 18.0, 2.5, BLACK);
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<NandNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
-                let texture = textures.0.get("NAND_GATE").unwrap();
-                let w = 100.0;
-                let h = 75.0;
-                draw_texture_ex(
-                    *texture,
-                    pos.x - w / 2.0 + w * 0.1,
-                    pos.y - h / 2.0 + w * 0.08,
+            node: PhantomData::<DipSwitchNode>,
+            draw_fn: Arc::new(|node: &DipSwitchNode, Pos { pos, .. }, _: &Textures, _| {
+                let half_height = DIP_SWITCH_BITS as f32 * 10.0;
+                draw_rectangle(
+                    pos.x - 25.0,
+                    pos.y - half_height,
+                    50.0,
+                    half_height * 2.0,
+                    MAROON,
+                );
+                // a slider for each bit lined up with its output, to the right when on
+                for bit in 0..DIP_SWITCH_BITS {
+                    let y = pos.y + half_height - 10.0 - bit as f32 * 20.0;
+                    draw_rectangle(pos.x - 12.0, y - 6.0, 24.0, 12.0, DARKGRAY);
+                    let x = if node.value >> bit & 1 == 1 {
+                        pos.x
+                    } else {
+                        pos.x - 12.0
+                    };
+                    draw_rectangle(x, y - 6.0, 12.0, 12.0, WHITE);
+                }
+                let value = node.value.to_string();
+                draw_world_text(
+                    &value,
+                    pos - Vec2::new(0.0, half_height + 12.0),
+                    18.0,
                     WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(Vec2::new(w * 0.8, h * 0.8)),
-                        ..DrawTextureParams::default()
-                    },
                 );
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<NorNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
-                let texture = textures.0.get("NOR_GATE").unwrap();
-                let w = 100.0;
-                let h = 75.0;
-                draw_texture_ex(
-                    *texture,
-                    pos.x - w / 2.0 + w * 0.1,
-                    pos.y - h / 2.0 + w * 0.08,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(Vec2::new(w * 0.8, h * 0.8)),
-                        ..DrawTextureParams::default()
-                    },
-                );
+            node: PhantomData::<ClockNode>,
+            draw_fn: Arc::new(|node: &ClockNode, Pos { pos, .. }, _: &Textures, _| {
+                draw_rectangle(pos.x - 30.0, pos.y - 30.0, 60.0, 60.0, WHITE);
+
+                // one period of the wave, high part first
+                let (left, w) = (pos.x - 20.0, 40.0);
+                let (high, low) = (pos.y + 10.0, pos.y - 10.0);
+                let fall = left + w * node.high_ticks() as f32 / node.period.max(1) as f32;
+                draw_line(left, low, left, high, 2.5, BLACK);
+                draw_line(left, high, fall, high, 2.5, BLACK);
+                draw_line(fall, high, fall, low, 2.5, BLACK);
+                draw_line(fall, low, left + w, low, 2.5, BLACK);
+
+                // sweeps across the wave as the clock runs
+                let x = left + w * node.position as f32 / node.period.max(1) as f32;
+                let color = if node.state { RED } else { GRAY };
+                draw_line(x, pos.y - 20.0, x, pos.y + 20.0, 3.0, color);
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<XorNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
-                let texture = textures.0.get("XOR_GATE").unwrap();
-                let w = 100.0;
-                let h = 75.0;
-                draw_texture_ex(
-                    *texture,
-                    pos.x - w / 2.0 + w * 0.1,
-                    pos.y - h / 2.0 + w * 0.1,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(Vec2::new(w * 0.8, h * 0.8)),
-                        ..DrawTextureParams::default()
-                    },
-                );
+            node: PhantomData::<SrLatchNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<SrLatchNode, 2, 2>(pos, "SR", ["S", "R"], ["Q", "Q'"]);
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<XnorNode>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, textures: &Textures| {
-                let texture = textures.0.get("XNOR_GATE").unwrap();
-                let w = 100.0;
-                let h = 75.0;
-                draw_texture_ex(
-                    *texture,
-                    pos.x - w / 2.0 + w * 0.1,
-                    pos.y - h / 2.0 + w * 0.1,
-                    WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(Vec2::new(w * 0.8, h * 0.8)),
-                        ..DrawTextureParams::default()
-                    },
+            node: PhantomData::<DLatchNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<DLatchNode, 2, 2>(pos, "Latch", ["D", "E"], ["Q", "Q'"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<DFlipFlopNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<DFlipFlopNode, 2, 2>(pos, "D", ["D", ">"], ["Q", "Q'"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<JkFlipFlopNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<JkFlipFlopNode, 3, 2>(pos, "JK", ["J", ">", "K"], ["Q", "Q'"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<TFlipFlopNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<TFlipFlopNode, 2, 2>(pos, "T", ["T", ">"], ["Q", "Q'"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Register4Node>,
+            draw_fn: Arc::new(|node: &Register4Node, Pos { pos, .. }, _: &Textures, _| {
+                draw_block::<Register4Node, 4, 1>(pos, "REG", ["D", "EN", ">", "R"], ["Q"]);
+                draw_register_value(pos, node.value());
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Register8Node>,
+            draw_fn: Arc::new(|node: &Register8Node, Pos { pos, .. }, _: &Textures, _| {
+                draw_block::<Register8Node, 4, 1>(pos, "REG", ["D", "EN", ">", "R"], ["Q"]);
+                draw_register_value(pos, node.value());
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Counter4Node>,
+            draw_fn: Arc::new(|node: &Counter4Node, Pos { pos, .. }, _: &Textures, _| {
+                draw_block::<Counter4Node, 4, 2>(pos, "CTR", ["EN", "UP", ">", "R"], ["Q", "C"]);
+                draw_register_value(pos, node.value());
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Counter8Node>,
+            draw_fn: Arc::new(|node: &Counter8Node, Pos { pos, .. }, _: &Textures, _| {
+                draw_block::<Counter8Node, 4, 2>(pos, "CTR", ["EN", "UP", ">", "R"], ["Q", "C"]);
+                draw_register_value(pos, node.value());
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<ShiftRegister4Node>,
+            draw_fn: Arc::new(
+                |node: &ShiftRegister4Node, Pos { pos, .. }, _: &Textures, _| {
+                    draw_block::<ShiftRegister4Node, 4, 1>(
+                        pos,
+                        "SHIFT",
+                        ["D", "EN", ">", "R"],
+                        ["Q"],
+                    );
+                    draw_register_value(pos, node.value());
+                },
+            ),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<ShiftRegister8Node>,
+            draw_fn: Arc::new(
+                |node: &ShiftRegister8Node, Pos { pos, .. }, _: &Textures, _| {
+                    draw_block::<ShiftRegister8Node, 4, 1>(
+                        pos,
+                        "SHIFT",
+                        ["D", "EN", ">", "R"],
+                        ["Q"],
+                    );
+                    draw_register_value(pos, node.value());
+                },
+            ),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<RomNode>,
+            draw_fn: Arc::new(|node: &RomNode, Pos { pos, .. }, _: &Textures, _| {
+                draw_block::<RomNode, 1, 1>(pos, "ROM", ["A"], ["D"]);
+                draw_memory_size(pos, node.address_width, node.data_width);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<RamNode>,
+            draw_fn: Arc::new(|node: &RamNode, Pos { pos, .. }, _: &Textures, _| {
+                draw_block::<RamNode, 4, 1>(pos, "RAM", ["A", "D", "WE", ">"], ["D"]);
+                draw_memory_size(pos, node.address_width, node.data_width);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<LedNode>,
+            draw_fn: Arc::new(|node: &LedNode, Pos { pos, .. }, _: &Textures, inputs| {
+                draw_circle(
+                    pos.x,
+                    pos.y,
+                    20.0,
+                    light_color(inputs[0].bit(0), node.color),
                 );
+                draw_circle_lines(pos.x, pos.y, 20.0, 2.5, BLACK);
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<Wire>,
-            draw_fn: Arc::new(|_, Pos { pos, .. }, _: &Textures| {
-                draw_circle(pos.x, pos.y, 10.0, WHITE);
+            node: PhantomData::<SevenSegmentNode>,
+            draw_fn: Arc::new(
+                |node: &SevenSegmentNode, Pos { pos, .. }, _: &Textures, inputs| {
+                    draw_rectangle(pos.x - 40.0, pos.y - 80.0, 80.0, 160.0, BLACK);
+                    draw_digit(pos, inputs.map(|input| input.bit(0)), node.color);
+                },
+            ),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<HexDigitNode>,
+            draw_fn: Arc::new(
+                |node: &HexDigitNode, Pos { pos, .. }, _: &Textures, inputs| {
+                    draw_rectangle(pos.x - 40.0, pos.y - 60.0, 80.0, 120.0, BLACK);
+                    draw_digit(pos, HexDigitNode::segments(inputs[0]), node.color);
+                },
+            ),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<LedMatrixNode>,
+            draw_fn: Arc::new(
+                |node: &LedMatrixNode, Pos { pos, .. }, _: &Textures, inputs| {
+                    let side = LED_MATRIX_SIZE as f32 * 20.0 + 20.0;
+                    draw_rectangle(pos.x - side / 2.0, pos.y - side / 2.0, side, side, BLACK);
+                    // rows line up with their inputs
+                    for (row, input) in inputs.iter().enumerate() {
+                        for column in 0..LED_MATRIX_SIZE {
+                            let x = pos.x - 70.0 + column as f32 * 20.0;
+                            let y = pos.y + 70.0 - row as f32 * 20.0;
+                            draw_circle(x, y, 7.0, light_color(input.bit(column), node.color));
+                        }
+                    }
+                },
+            ),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<TriStateNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                let a = Vec2::new(pos.x - 25.0, pos.y - 25.0);
+                let b = Vec2::new(pos.x - 25.0, pos.y + 25.0);
+                let c = Vec2::new(pos.x + 25.0, pos.y);
+                draw_triangle(a, b, c, WHITE);
+                draw_triangle_lines(a, b, c, 2.5, BLACK);
+                // the enable input comes in from below
+                draw_line(pos.x, pos.y - 12.5, pos.x, pos.y - 25.0, 2.5, BLACK);
             }),
         })
         .with_thread_local(DrawNodeSys {
-            node: PhantomData::<SwitchNode>,
-            draw_fn: Arc::new(|node: &SwitchNode, Pos { pos, .. }, _: &Textures| {
-                let color = if node.state { RED } else { WHITE };
-                draw_rectangle(pos.x - 30.0, pos.y - 30.0, 60.0, 60.0, WHITE);
-                draw_circle(pos.x, pos.y, 25.0, color);
-                draw_circle_lines(pos.x, pos.y, 25.0, 2.5, BLACK);
+            node: PhantomData::<Splitter4Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| draw_bus_bar(pos, 4, -15.0)),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Splitter8Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| draw_bus_bar(pos, 8, -15.0)),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Merger4Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| draw_bus_bar(pos, 4, 15.0)),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Merger8Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| draw_bus_bar(pos, 8, 15.0)),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<HalfAdderNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<HalfAdderNode, 2, 2>(pos, "HA", ["A", "B"], ["S", "C"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<FullAdderNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<FullAdderNode, 3, 2>(pos, "FA", ["A", "B", "C"], ["S", "C"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<AdderNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<AdderNode, 3, 2>(pos, "+", ["A", "B", "C"], ["S", "C"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<ComparatorNode>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_block::<ComparatorNode, 2, 3>(pos, "CMP", ["A", "B"], ["<", "=", ">"]);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Mux2Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_mux::<2, 3>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Mux4Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_mux::<4, 5>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Mux8Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_mux::<8, 9>(pos);
             }),
         })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Demux2Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_demux::<2>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Demux4Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_demux::<4>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Demux8Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_demux::<8>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Decoder4Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_decoder::<4>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Decoder8Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_decoder::<8>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Encoder4Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_encoder::<4>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<Encoder8Node>,
+            draw_fn: Arc::new(|_, Pos { pos, .. }, _, _| {
+                draw_encoder::<8>(pos);
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<InputPortNode>,
+            draw_fn: Arc::new(|node: &InputPortNode, Pos { pos, .. }, _, _| {
+                draw_port(pos, &node.name, "IN");
+            }),
+        })
+        .with_thread_local(DrawNodeSys {
+            node: PhantomData::<OutputPortNode>,
+            draw_fn: Arc::new(|node: &OutputPortNode, Pos { pos, .. }, _, _| {
+                draw_port(pos, &node.name, "OUT");
+            }),
+        })
+        .with_thread_local(DrawCompoundNodeSys)
         .with_thread_local(DrawConnectionSys)
+        .with_thread_local(DrawNodeWarningSys)
+        .with_thread_local(DrawLoopSys)
+        .with_thread_local(DrawKeyBindingSys)
+        .with_thread_local(DrawBusLabelSys)
+}
+
+// splitters and mergers are a bar as tall as their bit pins, with a stub out to the bus pin
+fn draw_bus_bar(pos: Vec2, width: usize, bus_x: f32) {
+    let h = width as f32 * 20.0;
+    draw_rectangle(pos.x - 5.0, pos.y - h / 2.0, 10.0, h, DARKGRAY);
+    draw_line(pos.x, pos.y, pos.x + bus_x, pos.y, 9.0, DARKGRAY);
+}
+
+// a lit light is its full color, an unlit one a dim version and an unknown one gray
+fn light_color(signal: Signal, color: LedColor) -> Color {
+    let lit = match color {
+        LedColor::Red => RED,
+        LedColor::Green => GREEN,
+        LedColor::Blue => BLUE,
+        LedColor::Yellow => YELLOW,
+        LedColor::White => WHITE,
+    };
+    match signal.read() {
+        Signal::High => lit,
+        Signal::Low => Color::new(lit.r * 0.25, lit.g * 0.25, lit.b * 0.25, 1.0),
+        _ => GRAY,
+    }
+}
+
+// segments a to g of a digit 40 wide and 100 tall, clockwise from the top with g in the middle
+const SEGMENTS: [(Vec2, Vec2); 7] = [
+    (const_vec2!([-20.0, 50.0]), const_vec2!([20.0, 50.0])),
+    (const_vec2!([20.0, 50.0]), const_vec2!([20.0, 0.0])),
+    (const_vec2!([20.0, 0.0]), const_vec2!([20.0, -50.0])),
+    (const_vec2!([20.0, -50.0]), const_vec2!([-20.0, -50.0])),
+    (const_vec2!([-20.0, -50.0]), const_vec2!([-20.0, 0.0])),
+    (const_vec2!([-20.0, 0.0]), const_vec2!([-20.0, 50.0])),
+    (const_vec2!([-20.0, 0.0]), const_vec2!([20.0, 0.0])),
+];
+
+// draws segments a to g followed by the decimal point
+fn draw_digit(pos: Vec2, segments: [Signal; 8], color: LedColor) {
+    for ((start, end), segment) in SEGMENTS.iter().zip(segments.iter()) {
+        // a gap at each end keeps the segments apart
+        let gap = (*end - *start).normalize() * 5.0;
+        let (start, end) = (pos + *start + gap, pos + *end - gap);
+        draw_line(
+            start.x,
+            start.y,
+            end.x,
+            end.y,
+            6.0,
+            light_color(*segment, color),
+        );
+    }
+    draw_circle(
+        pos.x + 30.0,
+        pos.y - 50.0,
+        4.0,
+        light_color(segments[7], color),
+    );
+}
+
+// words x bits under the title of a memory node
+fn draw_memory_size(pos: Vec2, address_width: u32, data_width: u32) {
+    let size = format!("{}x{}", memory_size(address_width), data_width);
+    draw_world_text(&size, pos - Vec2::new(0.0, 16.0), 12.0, DARKGRAY);
+}
+
+fn draw_register_value(pos: Vec2, value: Bus) {
+    draw_world_text(
+        &value.to_string(),
+        pos - Vec2::new(0.0, 16.0),
+        12.0,
+        DARKGRAY,
+    );
+}
+
+/// Draws text centred on a point in world space. World space is y up, which would draw the
+/// text upside down, so it's flipped back around the point.
+pub fn draw_world_text(text: &str, pos: Vec2, font_size: f32, color: Color) {
+    let size = measure_text(text, None, font_size as u16, 1.0);
+    let gl = unsafe { get_internal_gl() }.quad_gl;
+    gl.push_model_matrix(
+        Mat4::from_translation(vec3(pos.x, pos.y, 0.0)) * Mat4::from_scale(vec3(1.0, -1.0, 1.0)),
+    );
+    draw_text(text, -size.width / 2.0, size.height / 2.0, font_size, color);
+    gl.pop_model_matrix();
+}
+
+// a box reaching just short of the node's pins with a label inside the box next to each pin, a
+// pin labelled ">" is a clock input and gets the usual triangle instead
+fn draw_block<N, const I: usize, const O: usize>(
+    pos: Vec2,
+    title: &str,
+    input_labels: [&str; I],
+    output_labels: [&str; O],
+) where
+    N: Node<I, O>,
+{
+    let inputs = N::input_offsets();
+    let outputs = N::output_offsets();
+    let right = outputs.iter().map(|o| o.x).fold(20.0, f32::max) - 10.0;
+    let left = inputs.iter().map(|o| o.x).fold(-right - 10.0, f32::min) + 10.0;
+    let top = inputs
+        .iter()
+        .chain(outputs.iter())
+        .map(|o| o.y.abs())
+        .fold(10.0, f32::max)
+        + 20.0;
+
+    draw_rectangle(pos.x + left, pos.y - top, right - left, top * 2.0, WHITE);
+    draw_rectangle_lines(
+        pos.x + left,
+        pos.y - top,
+        right - left,
+        top * 2.0,
+        2.5,
+        BLACK,
+    );
+    draw_world_text(title, pos, 14.0, DARKGRAY);
+
+    for (label, offset) in input_labels.iter().zip(inputs.iter()) {
+        let y = pos.y + offset.y;
+        if *label == ">" {
+            let x = pos.x + left;
+            draw_line(x, y + 7.0, x + 10.0, y, 2.0, BLACK);
+            draw_line(x + 10.0, y, x, y - 7.0, 2.0, BLACK);
+        } else {
+            draw_world_text(label, Vec2::new(pos.x + left + 9.0, y), 16.0, BLACK);
+        }
+    }
+    for (label, offset) in output_labels.iter().zip(outputs.iter()) {
+        let y = pos.y + offset.y;
+        draw_world_text(label, Vec2::new(pos.x + right - 9.0, y), 16.0, BLACK);
+    }
+}
+
+// a box with the port's name in it, or what kind of port it is until it's named
+fn draw_port(pos: Vec2, name: &str, kind: &str) {
+    draw_rectangle(pos.x - 30.0, pos.y - 15.0, 60.0, 30.0, WHITE);
+    draw_rectangle_lines(pos.x - 30.0, pos.y - 15.0, 60.0, 30.0, 2.5, BLACK);
+    let label = if name.is_empty() { kind } else { name };
+    draw_world_text(label, pos, 16.0, BLACK);
+}
+
+// labels of the numbered pins of routing blocks
+const PIN_NUMBERS: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+
+// I is the D data inputs plus the select input
+fn draw_mux<const D: usize, const I: usize>(pos: Vec2)
+where
+    MuxNode<D>: Node<I, 1>,
+{
+    let inputs = std::array::from_fn(|i| if i == D { "S" } else { PIN_NUMBERS[i] });
+    draw_block::<MuxNode<D>, I, 1>(pos, "MUX", inputs, ["Y"]);
+}
+
+fn draw_demux<const O: usize>(pos: Vec2) {
+    let outputs = std::array::from_fn(|i| PIN_NUMBERS[i]);
+    draw_block::<DemuxNode<O>, 2, O>(pos, "DMX", ["D", "S"], outputs);
+}
+
+fn draw_decoder<const O: usize>(pos: Vec2) {
+    let outputs = std::array::from_fn(|i| PIN_NUMBERS[i]);
+    draw_block::<DecoderNode<O>, 1, O>(pos, "DEC", ["S"], outputs);
+}
+
+fn draw_encoder<const I: usize>(pos: Vec2) {
+    let inputs = std::array::from_fn(|i| PIN_NUMBERS[i]);
+    draw_block::<EncoderNode<I>, I, 2>(pos, "ENC", inputs, ["Y", "V"]);
 }
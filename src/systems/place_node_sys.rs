@@ -32,8 +32,20 @@ where
         Entities<'a>,
     );
 
-    fn run(
-        &mut self,
+    fn run(&mut self, data: Self::SystemData) {
+        let pos = Pos::from_vec(data.6 .0);
+        Self::place_node(N::default(), pos, data);
+    }
+}
+
+impl<'a, N, const I: usize, const O: usize> PlaceNodeSys<N, I, O>
+where
+    N: Node<I, O> + 'static,
+{
+    /// Creates the node along with its input and output connections, returning the node entity
+    pub fn place_node(
+        node: N,
+        pos: Pos,
         (
             mut node_storage,
             mut position_storage,
@@ -41,12 +53,11 @@ where
             mut node_markers,
             mut current_scope_markers,
             mut inner_nodes,
-            mouse_pos,
+            _,
             creating_compound,
             entities,
-        ): Self::SystemData,
-    ) {
-        let pos = Pos::from_vec(mouse_pos.0);
+        ): <Self as System<'a>>::SystemData,
+    ) -> Entity {
         let input_offsets = N::input_offsets();
         let output_offsets = N::output_offsets();
 
@@ -117,7 +128,7 @@ where
             .with(CurrentScope, &mut current_scope_markers)
             .with(
                 Connected {
-                    node: N::default(),
+                    node,
                     inputs,
                     outputs,
                 },
@@ -125,6 +136,6 @@ where
             )
             .with(pos, &mut position_storage);
         add_inner_node_data!(builder);
-        builder.build();
+        builder.build()
     }
 }
@@ -8,7 +8,7 @@ use crate::{
     components::{round_to_snap, InnerNode},
     resources::CreatingCompoundNode,
 };
-use macroquad::prelude::Vec2;
+use glam::Vec2;
 use specs::prelude::*;
 
 pub struct WirePlaceSys;
@@ -1,23 +1,68 @@
+use crate::nodes::{ClockNode, NodeTy};
 use crate::Connected;
 use crate::{components::Connection, nodes::Wire};
-use crate::{components::Node, resources::Tick};
+use crate::{
+    components::{Bus, Delay, Node, NodeWarning, Signal},
+    resources::{DriverPolicy, FanInPolicy, Fanout, Schedule, SchedulerMode, Tick, TimeWheel},
+    resources::{History, NodeDelays, Oscillation, Snapshot, WireSnapshot, OSCILLATION_TICKS},
+};
 use core::marker::PhantomData;
+use specs::hibitset::BitSetLike;
 use specs::prelude::*;
+use specs::storage::ComponentEvent;
+use std::collections::HashMap;
+
+/// Sets the value the output connection drives onto each of its wires, the wire's state is then
+/// decided from all of its drivers by ResolveDriversSys. Returns whether any wire was driven
+/// with a new value.
+fn drive(
+    output_entity: Entity,
+    value: Bus,
+    connections: &ReadStorage<Connection>,
+    wires: &mut WriteStorage<Wire>,
+    dirty_wires: &mut BitSet,
+) -> bool {
+    let connection = match connections.get(output_entity) {
+        Some(connection) => connection,
+        // deleted while a delayed change was waiting
+        None => return false,
+    };
+
+    let mut changed = false;
+    connection.wires.iter().for_each(|e| {
+        let wire = wires.get_mut(*e).unwrap();
+        match wire
+            .drivers
+            .iter_mut()
+            .find(|(driver, _)| *driver == output_entity)
+        {
+            Some((_, state)) if *state == value => return,
+            Some((_, state)) => *state = value,
+            None => wire.drivers.push((output_entity, value)),
+        }
+        dirty_wires.add(e.id());
+        changed = true;
+    });
+    changed
+}
 
 pub struct ElectroSys<N, const I: usize, const O: usize>
 where
     N: Node<I, O> + 'static,
 {
     node: PhantomData<N>,
+    /// Used to look up the delay set for the whole type in NodeDelays
+    ty: NodeTy,
 }
 
-impl<'a, N, const I: usize, const O: usize> Default for ElectroSys<N, I, O>
+impl<N, const I: usize, const O: usize> ElectroSys<N, I, O>
 where
     N: Node<I, O> + 'static,
 {
-    fn default() -> Self {
+    pub fn new(ty: NodeTy) -> Self {
         ElectroSys {
             node: PhantomData::<N>,
+            ty,
         }
     }
 }
@@ -30,60 +75,543 @@ where
         WriteStorage<'a, Connected<N, I, O>>,
         ReadStorage<'a, Connection>,
         WriteStorage<'a, Wire>,
+        WriteStorage<'a, NodeWarning>,
+        Read<'a, FanInPolicy>,
+        Read<'a, SchedulerMode>,
+        Write<'a, Schedule>,
+        Write<'a, Fanout>,
+        ReadStorage<'a, Delay>,
+        Read<'a, NodeDelays>,
+        Write<'a, TimeWheel>,
+        Write<'a, Oscillation>,
+        Entities<'a>,
     );
 
-    fn run(&mut self, (mut nodes, connections, mut wires): Self::SystemData) {
-        (&mut nodes).join().for_each(|node| {
-            let mut inputs = [false; I];
+    fn run(
+        &mut self,
+        (
+            mut nodes,
+            connections,
+            mut wires,
+            mut warnings,
+            fan_in,
+            mode,
+            mut schedule,
+            mut fanout,
+            delays,
+            type_delays,
+            mut time_wheel,
+            mut oscillation,
+            entities,
+        ): Self::SystemData,
+    ) {
+        if fanout.rebuilding {
+            for (node, entity) in (&nodes, &entities).join() {
+                for input_entity in node.inputs.iter() {
+                    for wire in connections.get(*input_entity).unwrap().wires.iter() {
+                        fanout.sinks.entry(wire.id()).or_default().push(entity);
+                    }
+                }
+            }
+        }
+
+        let evaluate_all = schedule.evaluate_all(*mode);
+        let Schedule {
+            nodes: pending,
+            dirty_wires,
+            ..
+        } = &mut *schedule;
+        let type_delay = type_delays
+            .0
+            .get(&self.ty)
+            .copied()
+            .unwrap_or_else(N::delay);
+
+        let mut evaluate = |node: &mut Connected<N, I, O>, entity: Entity| {
+            let widths = N::input_widths();
+            let mut inputs = widths.map(|width| Bus::new(width.max(1), Signal::Z));
+            let mut warning = None;
+
             for (i, input_entity) in node.inputs.iter().enumerate() {
                 let connection = connections.get(*input_entity).unwrap();
                 if connection.wires.is_empty() {
-                    return;
-                } else {
-                    connection.wires.iter().for_each(|e| {
-                        let wire = wires.get(*e).expect("All inputs must be a wire");
-                        inputs[i] = wire.output_state;
-                    });
+                    warning = Some(NodeWarning::UnconnectedInput);
+                    continue;
+                }
+
+                let buses = connection.wires.iter().map(|e| {
+                    wires
+                        .get(*e)
+                        .expect("All inputs must be a wire")
+                        .output_state
+                });
+
+                let bus = fan_in.0.resolve_bus(buses.clone());
+                let has_x = |bus: &Bus| bus.bits().contains(&Signal::X);
+                if has_x(&bus) && !buses.clone().any(|bus| has_x(&bus)) {
+                    warning.get_or_insert(NodeWarning::ConflictingInput);
                 }
+                // an undriven wire doesn't have a width of its own
+                let driven = bus.bits().iter().any(|bit| bit.is_driven());
+                if widths[i] != 0 && bus.width() != widths[i] && driven {
+                    warning.get_or_insert(NodeWarning::WidthMismatch);
+                }
+                inputs[i] = bus;
             }
 
-            let outputs = node.calculate_state(inputs);
-
-            for (i, output_entity) in node.outputs.iter().enumerate() {
-                let connection = connections.get(*output_entity).unwrap();
-                connection.wires.iter().for_each(|e| {
-                    let wire = wires.get_mut(*e).unwrap();
-                    if wire.input_state != outputs[i] {
-                        wire.input_state = outputs[i];
-                        wire.changed_input = true;
-                    } else {
-                        wire.changed_input = false;
-                    }
+            match warning {
+                Some(warning) => {
+                    warnings.insert(entity, warning).unwrap();
+                }
+                None => {
+                    warnings.remove(entity);
+                }
+            }
+
+            let outputs = node.calculate_bus_state(inputs);
+
+            // a node's own delay wins over the one set for its type
+            let delay = delays.get(entity).map_or(type_delay, |delay| delay.0);
+            for (output_entity, output) in node.outputs.iter().zip(outputs.iter()) {
+                let changed = if delay > 1 {
+                    time_wheel.schedule(delay - 1, *output_entity, *output)
+                } else {
+                    time_wheel.forget(*output_entity);
+                    drive(
+                        *output_entity,
+                        *output,
+                        &connections,
+                        &mut wires,
+                        dirty_wires,
+                    )
+                };
+                oscillation.source_changed |= changed && I == 0;
+            }
+        };
+
+        // nodes without inputs can change by themselves (e.g. a switch being clicked) so they
+        // are always evaluated
+        if evaluate_all || I == 0 {
+            (&mut nodes, &entities)
+                .join()
+                .for_each(|(node, entity)| evaluate(node, entity));
+        } else {
+            (&mut nodes, &entities, &*pending)
+                .join()
+                .for_each(|(node, entity, _)| evaluate(node, entity));
+        }
+    }
+}
+
+/// Queues up the nodes reading from wires which changed last tick, and rebuilds the fanout
+/// index whenever connections are added, changed or removed
+#[derive(Default)]
+pub struct ScheduleSys {
+    reader: Option<ReaderId<ComponentEvent>>,
+}
+
+impl<'a> System<'a> for ScheduleSys {
+    type SystemData = (
+        ReadStorage<'a, Connection>,
+        Write<'a, Schedule>,
+        Write<'a, Fanout>,
+    );
+
+    fn run(&mut self, (connections, mut schedule, mut fanout): Self::SystemData) {
+        let reader = self.reader.as_mut().unwrap();
+        if connections.channel().read(reader).count() > 0 {
+            // every ElectroSys adds the sinks of its nodes back in this tick
+            fanout.sinks.clear();
+            fanout.rebuilding = true;
+            schedule.all = true;
+            return;
+        }
+
+        let Schedule {
+            nodes,
+            changed_wires,
+            ..
+        } = &mut *schedule;
+        for wire in (&*changed_wires).join() {
+            if let Some(sinks) = fanout.sinks.get(&wire) {
+                sinks.iter().for_each(|sink| {
+                    nodes.add(sink.id());
                 });
             }
-        })
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(WriteStorage::<Connection>::fetch(world).register_reader());
+    }
+}
+
+/// Saves the state of every wire before the tick runs so it can be stepped back to later
+pub struct HistorySys;
+impl<'a> System<'a> for HistorySys {
+    type SystemData = (
+        ReadStorage<'a, Wire>,
+        Read<'a, TimeWheel>,
+        Write<'a, History>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (wires, time_wheel, mut history, entities): Self::SystemData) {
+        if history.capacity == 0 {
+            return;
+        }
+
+        let wires = (&wires, &entities)
+            .join()
+            .map(|(wire, entity)| {
+                let snapshot = WireSnapshot {
+                    input_state: wire.input_state,
+                    output_state: wire.output_state,
+                    changed_input: wire.changed_input,
+                    drivers: wire.drivers.clone(),
+                    conflict: wire.conflict,
+                };
+                (entity, snapshot)
+            })
+            .collect();
+
+        history.push(Snapshot {
+            wires,
+            time_wheel: time_wheel.clone(),
+            nodes: HashMap::new(),
+        });
+    }
+}
+
+/// Adds the state of sequential nodes to the snapshot just taken by HistorySys
+pub struct NodeHistorySys<N, const I: usize, const O: usize>
+where
+    N: Node<I, O> + 'static,
+{
+    node: PhantomData<N>,
+}
+
+impl<N, const I: usize, const O: usize> Default for NodeHistorySys<N, I, O>
+where
+    N: Node<I, O> + 'static,
+{
+    fn default() -> Self {
+        NodeHistorySys {
+            node: PhantomData::<N>,
+        }
+    }
+}
+
+impl<'a, N, const I: usize, const O: usize> System<'a> for NodeHistorySys<N, I, O>
+where
+    N: Node<I, O> + 'static,
+{
+    type SystemData = (
+        ReadStorage<'a, Connected<N, I, O>>,
+        Write<'a, History>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (nodes, mut history, entities): Self::SystemData) {
+        if !N::is_sequential() || history.capacity == 0 {
+            return;
+        }
+
+        let snapshot = history.snapshots.back_mut().unwrap();
+        for (node, entity) in (&nodes, &entities).join() {
+            let state = bincode::serialize(&node.node).expect("Nodes must serialize");
+            snapshot.nodes.insert(entity, state);
+        }
+    }
+}
+
+fn restore_nodes<N, const I: usize, const O: usize>(world: &World, snapshot: &Snapshot)
+where
+    N: Node<I, O> + 'static,
+{
+    if !N::is_sequential() {
+        return;
+    }
+
+    let mut nodes = world.write_storage::<Connected<N, I, O>>();
+    let entities = world.entities();
+    for (node, entity) in (&mut nodes, &entities).join() {
+        if let Some(state) = snapshot.nodes.get(&entity) {
+            node.node = bincode::deserialize(state).expect("Nodes must deserialize");
+        }
+    }
+}
+
+/// Puts the wires and sequential nodes back how they were before the last recorded tick,
+/// returning false if there are no ticks left to go back to
+pub fn step_back(world: &World) -> bool {
+    let snapshot = match world.fetch_mut::<History>().snapshots.pop_back() {
+        Some(snapshot) => snapshot,
+        None => return false,
+    };
+
+    let mut wires = world.write_storage::<Wire>();
+    for (entity, saved) in snapshot.wires.iter() {
+        // wires deleted since the snapshot was taken are skipped
+        if let Some(wire) = wires.get_mut(*entity) {
+            wire.input_state = saved.input_state;
+            wire.output_state = saved.output_state;
+            wire.changed_input = saved.changed_input;
+            wire.drivers = saved.drivers.clone();
+            wire.conflict = saved.conflict;
+        }
+    }
+    std::mem::drop(wires);
+
+    macro_rules! restore_all_nodes {
+        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+            $(
+                restore_nodes::<crate::nodes::$node, $i, $o>(world, &snapshot);
+            )*
+        };
+    }
+
+    use crate::all_nodes;
+    all_nodes!(restore_all_nodes);
+
+    *world.fetch_mut::<TimeWheel>() = snapshot.time_wheel.clone();
+
+    // which nodes need evaluating isn't saved, so all of them are evaluated next tick
+    world.fetch_mut::<Schedule>().all = true;
+    true
+}
+
+/// Moves every clock on to the current tick before the nodes are evaluated
+pub struct ClockSys;
+impl<'a> System<'a> for ClockSys {
+    type SystemData = (
+        WriteStorage<'a, Connected<ClockNode, 0, 1>>,
+        Read<'a, TimeWheel>,
+    );
+
+    fn run(&mut self, (mut clocks, time_wheel): Self::SystemData) {
+        (&mut clocks)
+            .join()
+            .for_each(|clock| clock.node.update(time_wheel.now));
     }
 }
 
 pub struct WireSys;
 impl<'a> System<'a> for WireSys {
-    type SystemData = WriteStorage<'a, Wire>;
+    type SystemData = (
+        WriteStorage<'a, Wire>,
+        Write<'a, Schedule>,
+        Read<'a, SchedulerMode>,
+    );
 
-    fn run(&mut self, mut wires: Self::SystemData) {
-        (&mut wires).join().for_each(|wire| {
+    fn run(&mut self, (mut wires, mut schedule, mode): Self::SystemData) {
+        let mut outputs_changed = false;
+        let mut update = |wire: &mut Wire| {
+            outputs_changed |= wire.output_state != wire.input_state;
             wire.output_state = wire.input_state;
-        });
+        };
+
+        if schedule.evaluate_all(*mode) {
+            (&mut wires).join().for_each(update);
+        } else {
+            (&mut wires, &schedule.changed_wires)
+                .join()
+                .for_each(|(wire, _)| update(wire));
+        }
+        schedule.outputs_changed = outputs_changed;
     }
 }
 
+/// Drives the delayed output changes which are due this tick
+pub struct TimeWheelSys;
+impl<'a> System<'a> for TimeWheelSys {
+    type SystemData = (
+        ReadStorage<'a, Connection>,
+        WriteStorage<'a, Wire>,
+        Write<'a, TimeWheel>,
+        Write<'a, Schedule>,
+    );
+
+    fn run(&mut self, (connections, mut wires, mut time_wheel, mut schedule): Self::SystemData) {
+        for (output_entity, value) in time_wheel.advance() {
+            drive(
+                output_entity,
+                value,
+                &connections,
+                &mut wires,
+                &mut schedule.dirty_wires,
+            );
+        }
+    }
+}
+
+pub struct ResolveDriversSys;
+impl<'a> System<'a> for ResolveDriversSys {
+    type SystemData = (
+        WriteStorage<'a, Wire>,
+        ReadStorage<'a, Connection>,
+        Read<'a, DriverPolicy>,
+        Read<'a, SchedulerMode>,
+        Write<'a, Schedule>,
+        Write<'a, Fanout>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (mut wires, connections, policy, mode, mut schedule, mut fanout, entities): Self::SystemData,
+    ) {
+        let evaluate_all = schedule.evaluate_all(*mode);
+        let Schedule {
+            nodes,
+            dirty_wires,
+            changed_wires,
+            all,
+            ..
+        } = &mut *schedule;
+        changed_wires.clear();
+
+        let mut resolve = |wire: &mut Wire, wire_entity: Entity| {
+            // forget drivers which have been deleted or disconnected from the wire
+            wire.drivers.retain(|(driver, _)| {
+                connections
+                    .get(*driver)
+                    .is_some_and(|c| c.wires.contains(&wire_entity))
+            });
+
+            let state = policy.resolve_bus(wire.drivers.iter().map(|(_, state)| *state));
+            wire.conflict = policy.conflicts(wire.drivers.iter().map(|(_, state)| *state));
+
+            wire.changed_input = wire.input_state != state;
+            wire.input_state = state;
+            if wire.changed_input {
+                changed_wires.add(wire_entity.id());
+            }
+        };
+
+        // drivers only go away when connections change, which makes every wire get resolved
+        if evaluate_all {
+            (&mut wires, &entities)
+                .join()
+                .for_each(|(wire, entity)| resolve(wire, entity));
+        } else {
+            (&mut wires, &entities, &*dirty_wires)
+                .join()
+                .for_each(|(wire, entity, _)| resolve(wire, entity));
+        }
+
+        // everything scheduled for this tick has been done by now
+        nodes.clear();
+        dirty_wires.clear();
+        *all = false;
+        fanout.rebuilding = false;
+    }
+}
+
+/// Counts the ticks the circuit has been changing since a source last changed, and once that
+/// reaches OSCILLATION_TICKS collects the nodes which are still changing
+pub struct OscillationSys;
+impl<'a> System<'a> for OscillationSys {
+    type SystemData = (
+        Read<'a, Schedule>,
+        Read<'a, Fanout>,
+        Read<'a, TimeWheel>,
+        Write<'a, Oscillation>,
+    );
+
+    fn run(&mut self, (schedule, fanout, time_wheel, mut oscillation): Self::SystemData) {
+        let changing = !schedule.changed_wires.is_empty() || !time_wheel.is_empty();
+        if oscillation.source_changed || !changing {
+            oscillation.unsettled_ticks = 0;
+            oscillation.nodes.clear();
+        } else {
+            oscillation.unsettled_ticks += 1;
+        }
+        oscillation.source_changed = false;
+
+        if oscillation.unsettled_ticks >= OSCILLATION_TICKS {
+            for wire in (&schedule.changed_wires).join() {
+                if let Some(sinks) = fanout.sinks.get(&wire) {
+                    sinks.iter().for_each(|sink| {
+                        oscillation.nodes.add(sink.id());
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Resets the state held by every node of one type
+pub struct ResetNodeSys<N, const I: usize, const O: usize>
+where
+    N: Node<I, O> + 'static,
+{
+    node: PhantomData<N>,
+}
+
+impl<N, const I: usize, const O: usize> Default for ResetNodeSys<N, I, O>
+where
+    N: Node<I, O> + 'static,
+{
+    fn default() -> Self {
+        ResetNodeSys {
+            node: PhantomData::<N>,
+        }
+    }
+}
+
+impl<'a, N, const I: usize, const O: usize> System<'a> for ResetNodeSys<N, I, O>
+where
+    N: Node<I, O> + 'static,
+{
+    type SystemData = WriteStorage<'a, Connected<N, I, O>>;
+
+    fn run(&mut self, mut nodes: Self::SystemData) {
+        (&mut nodes).join().for_each(|node| node.node.reset());
+    }
+}
+
+/// Restarts the simulation, resetting the wires with ResetSys and every node with ResetNodeSys
+pub fn run_reset_systems(world: &World) {
+    ResetSys.run_now(world);
+
+    macro_rules! run_reset_node_sys {
+        ( $([$node:ident, $i:expr, $o:expr]),* $(,)? ) => {
+            $(
+                ResetNodeSys::<crate::nodes::$node, $i, $o>::default().run_now(world);
+            )*
+        };
+    }
+
+    use crate::all_nodes;
+    all_nodes!(run_reset_node_sys);
+}
+
 pub struct ResetSys;
 impl<'a> System<'a> for ResetSys {
-    type SystemData = (WriteStorage<'a, Wire>, Write<'a, Tick>);
+    type SystemData = (
+        WriteStorage<'a, Wire>,
+        Write<'a, Tick>,
+        Write<'a, Schedule>,
+        Write<'a, TimeWheel>,
+        Write<'a, History>,
+        Write<'a, Oscillation>,
+    );
 
-    fn run(&mut self, (mut wires, mut tick): Self::SystemData) {
+    fn run(
+        &mut self,
+        (mut wires, mut tick, mut schedule, mut time_wheel, mut history, mut oscillation): Self::SystemData,
+    ) {
+        schedule.all = true;
+        time_wheel.clear();
+        history.snapshots.clear();
+        *oscillation = Oscillation::default();
         (&mut wires).join().for_each(|wire| {
-            wire.input_state = false;
-            wire.output_state = false;
+            wire.input_state = Bus::default();
+            wire.output_state = Bus::default();
+            wire.drivers.clear();
+            wire.conflict = false;
         });
         tick.0 = 0;
     }
@@ -1,7 +1,15 @@
-use crate::resources::UIState;
+use crate::components::nodes::Light;
+use crate::components::KeyBinding;
+use crate::components::Node;
+use crate::nodes::{ButtonNode, ClockNode, DipSwitchNode, RamNode, RomNode, DIP_SWITCH_BITS};
+use crate::nodes::{InputPortNode, OutputPortNode};
+use crate::resources::{MemoryInspector, SelectedClock, UIState};
+use crate::resources::{SelectedDipSwitch, SelectedPort};
 use crate::Connected;
 use crate::Pos;
 use crate::{nodes::SwitchNode, resources::MousePos};
+use core::marker::PhantomData;
+use macroquad::prelude::{is_key_down, is_key_pressed, KeyCode};
 use specs::prelude::*;
 
 use crate::resources::CurrentModeText;
@@ -12,7 +20,7 @@ impl<'a> System<'a> for CurrentModeSys {
 
     fn run(&mut self, (mut current_mode, ui_state): Self::SystemData) {
         match *ui_state {
-            UIState::AddingNode(_) => {
+            UIState::AddingNode(_) | UIState::AddingCompoundNode(_) => {
                 current_mode.0 = "Click to place node".to_string();
             }
             UIState::AddingWire { .. } => {
@@ -21,6 +29,16 @@ impl<'a> System<'a> for CurrentModeSys {
             UIState::Deleting => {
                 current_mode.0 = "Click a node or wire focus to delete it".to_string();
             }
+            UIState::BindingKey(None) => {
+                current_mode.0 = "Click a switch or button to bind a key to it".to_string();
+            }
+            UIState::BindingKey(Some(_)) => {
+                current_mode.0 =
+                    "Press a letter or digit to bind, or Escape to remove the binding".to_string();
+            }
+            UIState::SettingDelay => {
+                current_mode.0 = "Click a node to set its delay".to_string();
+            }
             _ => {
                 *current_mode = CurrentModeText::default();
             }
@@ -48,3 +66,207 @@ impl<'a> System<'a> for SwitchClickSys {
         }
     }
 }
+
+pub struct ClockClickSys;
+impl<'a> System<'a> for ClockClickSys {
+    type SystemData = (
+        ReadStorage<'a, Connected<ClockNode, 0, 1>>,
+        Write<'a, SelectedClock>,
+        Read<'a, MousePos>,
+        ReadStorage<'a, Pos>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (clocks, mut selected, mouse_pos, positions, entities): Self::SystemData) {
+        let mouse_pos = mouse_pos.0;
+
+        let target_clock = (&clocks, &positions, &entities)
+            .join()
+            .find(|(_, pos, _)| (pos.pos - mouse_pos).length() < 35.0);
+
+        if let Some((_, _, entity)) = target_clock {
+            selected.0 = Some(entity);
+        }
+    }
+}
+
+pub struct PortClickSys;
+impl<'a> System<'a> for PortClickSys {
+    type SystemData = (
+        ReadStorage<'a, Connected<InputPortNode, 1, 1>>,
+        ReadStorage<'a, Connected<OutputPortNode, 1, 1>>,
+        Write<'a, SelectedPort>,
+        Read<'a, MousePos>,
+        ReadStorage<'a, Pos>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (inputs, outputs, mut selected, mouse_pos, positions, entities): Self::SystemData,
+    ) {
+        let mouse_pos = mouse_pos.0;
+
+        let target_port = (&positions, &entities)
+            .join()
+            .filter(|(_, e)| inputs.contains(*e) || outputs.contains(*e))
+            .find(|(pos, _)| (pos.pos - mouse_pos).length() < 30.0);
+
+        if let Some((_, entity)) = target_port {
+            selected.0 = Some(entity);
+        }
+    }
+}
+
+pub struct MemoryClickSys;
+impl<'a> System<'a> for MemoryClickSys {
+    type SystemData = (
+        ReadStorage<'a, Connected<RomNode, 1, 1>>,
+        ReadStorage<'a, Connected<RamNode, 4, 1>>,
+        Write<'a, MemoryInspector>,
+        Read<'a, MousePos>,
+        ReadStorage<'a, Pos>,
+        Entities<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (roms, rams, mut inspector, mouse_pos, positions, entities): Self::SystemData,
+    ) {
+        let mouse_pos = mouse_pos.0;
+
+        let target_memory = (&positions, &entities)
+            .join()
+            .filter(|(_, entity)| roms.contains(*entity) || rams.contains(*entity))
+            .find(|(pos, _)| (pos.pos - mouse_pos).length() < 40.0);
+
+        if let Some((_, entity)) = target_memory {
+            inspector.selected = Some(entity);
+        }
+    }
+}
+
+/// Changes the color of a clicked LED or display
+#[derive(Default)]
+pub struct LightClickSys<N, const I: usize, const O: usize> {
+    node: PhantomData<N>,
+}
+
+impl<'a, N, const I: usize, const O: usize> System<'a> for LightClickSys<N, I, O>
+where
+    N: Node<I, O> + Light + 'static,
+{
+    type SystemData = (
+        WriteStorage<'a, Connected<N, I, O>>,
+        Read<'a, MousePos>,
+        ReadStorage<'a, Pos>,
+    );
+
+    fn run(&mut self, (mut lights, mouse_pos, positions): Self::SystemData) {
+        let mouse_pos = mouse_pos.0;
+
+        let target_light = (&mut lights, &positions)
+            .join()
+            .find(|(_, pos)| (pos.pos - mouse_pos).length() < 35.0);
+
+        if let Some((light, _)) = target_light {
+            let color = light.node.color().next();
+            light.node.set_color(color);
+        }
+    }
+}
+
+/// Every key which can be bound to a switch or button
+pub fn bindable_keys() -> impl Iterator<Item = (char, KeyCode)> {
+    use KeyCode::*;
+    let codes = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, Q, R, S, T, U, V, W, X, Y, Z, Key0, Key1,
+        Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    ];
+    "ABCDEFGHIJKLMNOQRSTUVWXYZ0123456789".chars().zip(codes)
+}
+
+fn key_code(key: KeyBinding) -> Option<KeyCode> {
+    bindable_keys()
+        .find(|(c, _)| *c == key.0)
+        .map(|(_, code)| code)
+}
+
+/// Holds buttons down while the mouse is pressed on them or their key is held
+pub struct ButtonSys {
+    pub mouse_down: bool,
+    /// Whether keys are being typed somewhere else, e.g. into a text box
+    pub typing: bool,
+}
+
+impl<'a> System<'a> for ButtonSys {
+    type SystemData = (
+        WriteStorage<'a, Connected<ButtonNode, 0, 1>>,
+        ReadStorage<'a, KeyBinding>,
+        Read<'a, MousePos>,
+        ReadStorage<'a, Pos>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (mut buttons, keys, mouse_pos, positions, entities): Self::SystemData) {
+        let mouse_pos = mouse_pos.0;
+
+        for (button, pos, entity) in (&mut buttons, &positions, &entities).join() {
+            let clicked = self.mouse_down && (pos.pos - mouse_pos).length() < 35.0;
+            let key_held = !self.typing
+                && keys
+                    .get(entity)
+                    .and_then(|key| key_code(*key))
+                    .is_some_and(is_key_down);
+            button.node.pressed = clicked || key_held;
+        }
+    }
+}
+
+/// Toggles switches whose key was pressed
+pub struct KeySwitchSys;
+impl<'a> System<'a> for KeySwitchSys {
+    type SystemData = (
+        WriteStorage<'a, Connected<SwitchNode, 0, 1>>,
+        ReadStorage<'a, KeyBinding>,
+    );
+
+    fn run(&mut self, (mut switches, keys): Self::SystemData) {
+        for (switch, key) in (&mut switches, &keys).join() {
+            if key_code(*key).is_some_and(is_key_pressed) {
+                switch.node.state = !switch.node.state;
+            }
+        }
+    }
+}
+
+/// Flips the clicked switch of a DIP switch and shows its value in the top panel
+pub struct DipSwitchClickSys;
+impl<'a> System<'a> for DipSwitchClickSys {
+    type SystemData = (
+        WriteStorage<'a, Connected<DipSwitchNode, 0, DIP_SWITCH_BITS>>,
+        Write<'a, SelectedDipSwitch>,
+        Read<'a, MousePos>,
+        ReadStorage<'a, Pos>,
+        Entities<'a>,
+    );
+
+    fn run(&mut self, (mut dips, mut selected, mouse_pos, positions, entities): Self::SystemData) {
+        let mouse_pos = mouse_pos.0;
+        let half_height = DIP_SWITCH_BITS as f32 * 10.0;
+
+        let target_dip = (&mut dips, &positions, &entities)
+            .join()
+            .find(|(_, pos, _)| {
+                let offset = mouse_pos - pos.pos;
+                offset.x.abs() < 25.0 && offset.y.abs() < half_height
+            });
+
+        if let Some((dip, pos, entity)) = target_dip {
+            // the switches line up with their outputs, bit 0 at the top
+            let bit = ((pos.pos.y + half_height - mouse_pos.y) / 20.0) as usize;
+            dip.node.value ^= 1 << bit.min(DIP_SWITCH_BITS - 1);
+            selected.0 = Some(entity);
+        }
+    }
+}
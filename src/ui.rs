@@ -1,2 +1,5 @@
+pub mod keyboard;
+pub mod memory_inspector;
 pub mod mouse_click;
+pub mod resources;
 pub mod top_panel;
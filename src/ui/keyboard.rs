@@ -0,0 +1,44 @@
+use crate::components::KeyBinding;
+use crate::resources::{StatusText, UIState};
+use crate::systems::ui_systems::{bindable_keys, ButtonSys, KeySwitchSys};
+use macroquad::prelude::*;
+use specs::prelude::*;
+
+/// Runs the switches and buttons bound to keys, and binds a key once one is pressed after
+/// choosing a switch or button. Keys typed into a text box are left alone.
+pub fn handle_keys(world: &mut World, typing: bool) {
+    ButtonSys {
+        mouse_down: is_mouse_button_down(MouseButton::Left),
+        typing,
+    }
+    .run_now(world);
+
+    if typing {
+        return;
+    }
+    KeySwitchSys.run_now(world);
+
+    let entity = match *world.fetch::<UIState>() {
+        UIState::BindingKey(Some(entity)) => entity,
+        _ => return,
+    };
+
+    let status = if is_key_pressed(KeyCode::Escape) {
+        world.write_storage::<KeyBinding>().remove(entity);
+        "Removed the key binding".to_string()
+    } else {
+        match bindable_keys().find(|(_, code)| is_key_pressed(*code)) {
+            Some((key, _)) => {
+                world
+                    .write_storage::<KeyBinding>()
+                    .insert(entity, KeyBinding(key))
+                    .unwrap();
+                format!("Bound {}", key)
+            }
+            None => return,
+        }
+    };
+
+    world.insert(UIState::Nothing);
+    world.insert(StatusText(Some(status)));
+}
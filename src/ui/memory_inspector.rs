@@ -0,0 +1,130 @@
+use crate::components::nodes::{memory_size, Memory, RamNode, RomNode, MAX_ADDRESS_WIDTH};
+use crate::components::Node;
+use crate::memory_file::read_memory_file;
+use crate::resources::{MemoryInspector, Schedule, StatusText};
+use crate::Connected;
+use specs::prelude::*;
+
+// words shown on each row of the contents
+const ROW_WORDS: usize = 8;
+
+/// Shows the contents and settings of the selected ROM or RAM node in a window
+pub fn render_memory_inspector(ctx: &egui::CtxRef, world: &mut World) {
+    let selected = match world.fetch::<MemoryInspector>().selected {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let is_rom = world
+        .read_storage::<Connected<RomNode, 1, 1>>()
+        .contains(selected);
+    let open = if is_rom {
+        render_memory::<RomNode, 1, 1>(ctx, world, selected)
+    } else {
+        render_memory::<RamNode, 4, 1>(ctx, world, selected)
+    };
+
+    if !open {
+        world.fetch_mut::<MemoryInspector>().selected = None;
+    }
+}
+
+// returns whether the window is still open
+fn render_memory<N, const I: usize, const O: usize>(
+    ctx: &egui::CtxRef,
+    world: &World,
+    entity: Entity,
+) -> bool
+where
+    N: Node<I, O> + Memory + 'static,
+{
+    let mut memories = world.write_storage::<Connected<N, I, O>>();
+    let memory = match memories.get_mut(entity) {
+        Some(memory) => &mut memory.node,
+        // deleted since it was selected
+        None => return false,
+    };
+    let mut inspector = world.fetch_mut::<MemoryInspector>();
+
+    let mut open = true;
+    let mut changed = false;
+    let mut status = None;
+    egui::Window::new(N::NAME)
+        .open(&mut open)
+        .default_pos(egui::pos2(20.0, 80.0))
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let address_width = memory.address_width();
+                let data_width = memory.data_width();
+                ui.add(
+                    egui::DragValue::u32(memory.address_width_mut())
+                        .clamp_range(1.0..=MAX_ADDRESS_WIDTH as f32)
+                        .prefix("address bits: "),
+                );
+                ui.add(
+                    egui::DragValue::u32(memory.data_width_mut())
+                        .clamp_range(1.0..=32.0)
+                        .prefix("data bits: "),
+                );
+                changed |=
+                    address_width != memory.address_width() || data_width != memory.data_width();
+            });
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut inspector.path);
+                if ui.button("Load").clicked() {
+                    let data_width = memory.data_width() as usize;
+                    status = Some(match read_memory_file(&inspector.path, data_width) {
+                        Ok(words) => {
+                            let loaded =
+                                format!("Loaded {} words from {}", words.len(), inspector.path);
+                            memory.load(words);
+                            changed = true;
+                            loaded
+                        }
+                        Err(e) => format!("Failed to load {}: {}", inspector.path, e),
+                    });
+                }
+                if ui.button("Clear").clicked() {
+                    memory.load(Vec::new());
+                    changed = true;
+                }
+            });
+
+            let size = memory_size(memory.address_width());
+            // whole rows of the stored words, the rest are all 0
+            let shown = memory
+                .stored_words()
+                .clamp(1, size)
+                .next_multiple_of(ROW_WORDS)
+                .min(size);
+            egui::ScrollArea::from_max_height(400.0).show(ui, |ui| {
+                for row in (0..shown).step_by(ROW_WORDS) {
+                    let words = (row..(row + ROW_WORDS).min(size))
+                        .map(|address| {
+                            let word = memory.word(address).to_string();
+                            word.strip_prefix("0x").unwrap_or(&word).to_string()
+                        })
+                        .collect::<Vec<_>>();
+                    ui.monospace(format!("{:04X}: {}", row, words.join(" ")));
+                }
+            });
+            if shown < size {
+                ui.label(format!(
+                    "Addresses from {:04X} to {:04X} read as 0",
+                    shown,
+                    size - 1
+                ));
+            }
+        });
+
+    // nodes only update when their inputs change, so new contents need every node evaluated
+    if changed {
+        world.fetch_mut::<Schedule>().all = true;
+    }
+    if status.is_some() {
+        *world.fetch_mut::<StatusText>() = StatusText(status);
+    }
+
+    open
+}
@@ -1,13 +1,28 @@
 use crate::components::Connection;
 use crate::components::Pos;
 use crate::resources::MousePos;
+use crate::resources::SelectedDelay;
+use crate::resources::StatusText;
 use crate::resources::UIState;
 use crate::systems::place_node_sys::PlaceNodeSys;
+use crate::systems::ui_systems::LightClickSys;
+use crate::Connected;
 use specs::prelude::*;
 
 use crate::nodes;
 pub fn handle_mouse_click(world: &mut World) {
-    crate::systems::ui_systems::SwitchClickSys.run_now(world);
+    // clicking a switch to bind a key to it shouldn't flip it
+    if !matches!(*world.fetch::<UIState>(), UIState::BindingKey(_)) {
+        crate::systems::ui_systems::SwitchClickSys.run_now(world);
+        crate::systems::ui_systems::DipSwitchClickSys.run_now(world);
+    }
+    crate::systems::ui_systems::ClockClickSys.run_now(world);
+    crate::systems::ui_systems::PortClickSys.run_now(world);
+    crate::systems::ui_systems::MemoryClickSys.run_now(world);
+    LightClickSys::<nodes::LedNode, 1, 0>::default().run_now(world);
+    LightClickSys::<nodes::SevenSegmentNode, 8, 0>::default().run_now(world);
+    LightClickSys::<nodes::HexDigitNode, 1, 0>::default().run_now(world);
+    LightClickSys::<nodes::LedMatrixNode, 8, 0>::default().run_now(world);
 
     let mut ui_state = world.fetch_mut::<UIState>();
 
@@ -33,6 +48,16 @@ pub fn handle_mouse_click(world: &mut World) {
 
             *ui_state = UIState::Nothing;
         }
+        UIState::AddingCompoundNode(ref name) => {
+            let name = name.clone();
+            *ui_state = UIState::Nothing;
+            std::mem::drop(ui_state);
+
+            let pos = Pos::from_vec(world.fetch::<MousePos>().0);
+            if let Err(e) = crate::compound::place_compound_node(world, &name, pos) {
+                world.insert(StatusText(Some(format!("Failed to place {}: {}", name, e))));
+            }
+        }
         UIState::Deleting => {
             let positions = world.read_storage::<Pos>();
             let entities = world.entities();
@@ -44,15 +69,44 @@ pub fn handle_mouse_click(world: &mut World) {
             std::mem::drop(connections);
 
             if let Some((_, entity)) = target {
-                entities.delete(entity).unwrap();
                 std::mem::drop(positions);
                 std::mem::drop(entities);
                 std::mem::drop(ui_state);
-                crate::systems::cleanup_sys::run_cleanup_systems(entity, world);
-                world.maintain();
-                crate::systems::cleanup_sys::CleanupWires.run_now(world);
+                crate::systems::cleanup_sys::delete_node(entity, world);
             }
         }
+        UIState::BindingKey(None) => {
+            let positions = world.read_storage::<Pos>();
+            let entities = world.entities();
+            let mouse_pos = world.fetch::<MousePos>().0;
+            let switches = world.read_storage::<Connected<nodes::SwitchNode, 0, 1>>();
+            let buttons = world.read_storage::<Connected<nodes::ButtonNode, 0, 1>>();
+
+            *ui_state = (&positions, &entities)
+                .join()
+                .filter(|(_, e)| switches.contains(*e) || buttons.contains(*e))
+                .find(|(pos, _)| (pos.pos - mouse_pos).length() < 35.0)
+                .map_or(UIState::Nothing, |(_, entity)| {
+                    UIState::BindingKey(Some(entity))
+                });
+        }
+        // waiting for a key
+        UIState::BindingKey(Some(_)) => {}
+        UIState::SettingDelay => {
+            let positions = world.read_storage::<Pos>();
+            let entities = world.entities();
+            let mouse_pos = world.fetch::<MousePos>().0;
+
+            let target = (&positions, &entities)
+                .join()
+                .filter(|(_, e)| nodes::NodeTy::of(world, *e).is_some())
+                .find(|(pos, _)| (pos.pos - mouse_pos).length() < 35.0)
+                .map(|(_, entity)| entity);
+            if target.is_some() {
+                world.fetch_mut::<SelectedDelay>().0 = target;
+            }
+            *ui_state = UIState::Nothing;
+        }
         UIState::Nothing => {}
     }
 }
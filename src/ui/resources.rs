@@ -0,0 +1,23 @@
+// Resources only the GUI uses, these need a window so the headless Simulator never creates them
+
+use macroquad::camera::Camera2D;
+use macroquad::prelude::{screen_height, screen_width, Vec2};
+use macroquad::texture::Texture2D;
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+pub struct Textures(pub BTreeMap<String, Texture2D>);
+
+pub struct CameraRes(pub Camera2D);
+
+impl Default for CameraRes {
+    fn default() -> Self {
+        CameraRes(Camera2D {
+            rotation: 0.0,
+            zoom: Vec2::new(2.0 / screen_width(), 2.0 / screen_height()),
+            target: Vec2::new(0.0, 0.0),
+            offset: Vec2::new(0.0, 0.0),
+            render_target: None,
+        })
+    }
+}
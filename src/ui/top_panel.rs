@@ -1,15 +1,50 @@
-use crate::resources::{self, CompoundNodeData, CreatingCompoundNode, GridMode};
-use crate::resources::{CurrentModeText, UiSignals};
-use crate::ResetSys;
+use crate::components::nodes::Port;
+use crate::components::Delay;
+use crate::components::Node;
+use crate::resources::{
+    self, CompoundNodeData, CompoundNodeLibrary, CreatingCompoundNode, DriverPolicy, GridMode,
+};
+use crate::resources::{CircuitPath, CurrentModeText, ScriptPath, StatusText, UiSignals};
+use crate::resources::{FanInPolicy, GateInputs, Oscillation, Schedule, SchedulerMode};
+use crate::resources::{NodeDelays, SelectedDelay, SelectedDipSwitch, SelectedPort};
+use crate::resources::{Paused, PendingRun, RunLength, SelectedClock, SpeedMode, TicksPerSecond};
+use crate::systems::simulation_systems::run_reset_systems;
+use crate::Connected;
 use crate::{components::nodes, UiSignal};
 use egui::menu;
 use egui::Layout;
 use macroquad::prelude::*;
+use specs::hibitset::BitSetLike;
 use specs::prelude::*;
 
 pub fn render_top_panel(ui: &mut egui::Ui, world: &mut World) {
     ui.horizontal(|ui| {
         ui.horizontal(|ui| {
+            menu::menu(ui, "File", |ui| {
+                ui.text_edit_singleline(&mut world.fetch_mut::<CircuitPath>().0);
+                if ui.button("Save").clicked() {
+                    world.fetch_mut::<UiSignals>().0.push(UiSignal::SaveCircuit);
+                }
+                if ui.button("Open").clicked() {
+                    world.fetch_mut::<UiSignals>().0.push(UiSignal::OpenCircuit);
+                }
+                ui.separator();
+                ui.text_edit_singleline(&mut world.fetch_mut::<ScriptPath>().0);
+                if ui.button("Import Script").clicked() {
+                    world
+                        .fetch_mut::<UiSignals>()
+                        .0
+                        .push(UiSignal::ImportScript);
+                }
+                if ui.button("Export Script").clicked() {
+                    world
+                        .fetch_mut::<UiSignals>()
+                        .0
+                        .push(UiSignal::ExportScript);
+                }
+            });
+
+            let mut gate_inputs = world.fetch::<GateInputs>().0;
             menu::menu(ui, "Nodes", |ui| {
                 macro_rules! node_button {
                     ( $name:expr, $node:ident ) => {
@@ -22,24 +57,155 @@ pub fn render_top_panel(ui: &mut egui::Ui, world: &mut World) {
                     };
                 }
 
+                // picks the variant of a gate with `gate_inputs` inputs
+                macro_rules! gate_button {
+                    ( $name:expr, [$($node:ident),* $(,)?] ) => {
+                        if ui.button($name).clicked() {
+                            let ty = [$(nodes::NodeTy::$node),*][gate_inputs - 2];
+                            world
+                                .fetch_mut::<resources::UiSignals>()
+                                .0
+                                .push(UiSignal::AddNode(ty));
+                        }
+                    };
+                }
+
                 node_button!("Connection Node", Wire);
                 node_button!("On Node", OnNode);
                 node_button!("Off Node", OffNode);
                 node_button!("Not Node", NotNode);
-                node_button!("And Node", AndNode);
-                node_button!("Or Node", OrNode);
-                node_button!("Nand Node", NandNode);
-                node_button!("Nor Node", NorNode);
-                node_button!("Xor Node", XorNode);
-                node_button!("Xnor Node", XnorNode);
+                ui.add(
+                    egui::DragValue::usize(&mut gate_inputs)
+                        .clamp_range(2.0..=nodes::MAX_GATE_INPUTS as f32)
+                        .prefix("gate inputs: "),
+                );
+                gate_button!(
+                    "And Node",
+                    [AndNode, And3Node, And4Node, And5Node, And6Node, And7Node, And8Node]
+                );
+                gate_button!(
+                    "Or Node",
+                    [OrNode, Or3Node, Or4Node, Or5Node, Or6Node, Or7Node, Or8Node]
+                );
+                gate_button!(
+                    "Nand Node",
+                    [NandNode, Nand3Node, Nand4Node, Nand5Node, Nand6Node, Nand7Node, Nand8Node]
+                );
+                gate_button!(
+                    "Nor Node",
+                    [NorNode, Nor3Node, Nor4Node, Nor5Node, Nor6Node, Nor7Node, Nor8Node]
+                );
+                gate_button!(
+                    "Xor Node",
+                    [XorNode, Xor3Node, Xor4Node, Xor5Node, Xor6Node, Xor7Node, Xor8Node]
+                );
+                gate_button!(
+                    "Xnor Node",
+                    [XnorNode, Xnor3Node, Xnor4Node, Xnor5Node, Xnor6Node, Xnor7Node, Xnor8Node]
+                );
                 node_button!("Switch Node", SwitchNode);
+                node_button!("Push Button", ButtonNode);
+                node_button!("DIP Switch", DipSwitchNode);
+                node_button!("Clock Node", ClockNode);
+                node_button!("SR Latch", SrLatchNode);
+                node_button!("D Latch", DLatchNode);
+                node_button!("D Flip-Flop", DFlipFlopNode);
+                node_button!("JK Flip-Flop", JkFlipFlopNode);
+                node_button!("T Flip-Flop", TFlipFlopNode);
+                node_button!("4 Bit Register", Register4Node);
+                node_button!("8 Bit Register", Register8Node);
+                node_button!("4 Bit Counter", Counter4Node);
+                node_button!("8 Bit Counter", Counter8Node);
+                node_button!("4 Bit Shift Register", ShiftRegister4Node);
+                node_button!("8 Bit Shift Register", ShiftRegister8Node);
+                node_button!("ROM", RomNode);
+                node_button!("RAM", RamNode);
+                node_button!("LED", LedNode);
+                node_button!("7 Segment Display", SevenSegmentNode);
+                node_button!("Hex Display", HexDigitNode);
+                node_button!("LED Matrix", LedMatrixNode);
+                node_button!("Tri-State Buffer", TriStateNode);
+                node_button!("4 Bit Splitter", Splitter4Node);
+                node_button!("8 Bit Splitter", Splitter8Node);
+                node_button!("4 Bit Merger", Merger4Node);
+                node_button!("8 Bit Merger", Merger8Node);
+                node_button!("Half Adder", HalfAdderNode);
+                node_button!("Full Adder", FullAdderNode);
+                node_button!("Adder", AdderNode);
+                node_button!("Comparator", ComparatorNode);
+                node_button!("2:1 Mux", Mux2Node);
+                node_button!("4:1 Mux", Mux4Node);
+                node_button!("8:1 Mux", Mux8Node);
+                node_button!("1:2 Demux", Demux2Node);
+                node_button!("1:4 Demux", Demux4Node);
+                node_button!("1:8 Demux", Demux8Node);
+                node_button!("2:4 Decoder", Decoder4Node);
+                node_button!("3:8 Decoder", Decoder8Node);
+                node_button!("4:2 Encoder", Encoder4Node);
+                node_button!("8:3 Encoder", Encoder8Node);
+                node_button!("Input Port", InputPortNode);
+                node_button!("Output Port", OutputPortNode);
+
+                let mut library = world
+                    .fetch::<CompoundNodeLibrary>()
+                    .0
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !library.is_empty() {
+                    ui.separator();
+                }
+                library.sort();
+                for name in library {
+                    if ui.button(&name).clicked() {
+                        world
+                            .fetch_mut::<resources::UiSignals>()
+                            .0
+                            .push(UiSignal::AddCompoundNode(name));
+                    }
+                }
             });
+            world.insert(GateInputs(gate_inputs));
 
             if ui.button("Restart Sim").clicked() || is_key_pressed(KeyCode::Space) {
-                ResetSys.run_now(&world);
+                run_reset_systems(world);
                 world.insert(resources::Tick(0));
             }
 
+            // keys typed into a text box aren't shortcuts
+            let shortcuts = !ui.ctx().wants_keyboard_input();
+            let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+            let mut paused = world.fetch::<Paused>().0;
+            if ui.button(if paused { "Play" } else { "Pause" }).clicked()
+                || (shortcuts && is_key_pressed(KeyCode::P))
+            {
+                paused = !paused;
+            }
+            world.insert(Paused(paused));
+
+            if ui.button("Step Back").clicked() || (shortcuts && is_key_pressed(KeyCode::Left)) {
+                world.fetch_mut::<UiSignals>().0.push(UiSignal::StepBack);
+            }
+
+            if ui.button("Step").clicked()
+                || (shortcuts && !shift && is_key_pressed(KeyCode::Right))
+            {
+                world
+                    .fetch_mut::<UiSignals>()
+                    .0
+                    .push(UiSignal::Run(PendingRun::Ticks(1)));
+            }
+
+            if ui.button("Step Until Change").clicked()
+                || (shortcuts && shift && is_key_pressed(KeyCode::Right))
+            {
+                world
+                    .fetch_mut::<UiSignals>()
+                    .0
+                    .push(UiSignal::Run(PendingRun::UntilChange(0)));
+            }
+
             let mut grid_mode = *world.fetch::<GridMode>();
             menu::menu(ui, "Grid Mode", |ui| {
                 ui.radio_value(&mut grid_mode, GridMode::CrossHatches, "Cross Hatches");
@@ -49,14 +215,95 @@ pub fn render_top_panel(ui: &mut egui::Ui, world: &mut World) {
             });
             world.insert(grid_mode);
 
+            let old_driver_policy = *world.fetch::<DriverPolicy>();
+            let mut driver_policy = old_driver_policy;
+            menu::menu(ui, "Multiple Drivers", |ui| {
+                ui.radio_value(&mut driver_policy, DriverPolicy::Error, "Error");
+                ui.radio_value(&mut driver_policy, DriverPolicy::WiredOr, "Wired OR");
+                ui.radio_value(&mut driver_policy, DriverPolicy::WiredAnd, "Wired AND");
+            });
+            if driver_policy != old_driver_policy {
+                world.fetch_mut::<Schedule>().all = true;
+            }
+            world.insert(driver_policy);
+
+            let mut fan_in = *world.fetch::<FanInPolicy>();
+            menu::menu(ui, "Input Fan-in", |ui| {
+                ui.radio_value(&mut fan_in.0, DriverPolicy::Error, "Error");
+                ui.radio_value(&mut fan_in.0, DriverPolicy::WiredOr, "Wired OR");
+                ui.radio_value(&mut fan_in.0, DriverPolicy::WiredAnd, "Wired AND");
+            });
+            // the scheduler doesn't know which nodes read from several wires
+            if fan_in != *world.fetch::<FanInPolicy>() {
+                world.fetch_mut::<Schedule>().all = true;
+            }
+            world.insert(fan_in);
+
+            let mut scheduler_mode = *world.fetch::<SchedulerMode>();
+            menu::menu(ui, "Scheduler", |ui| {
+                ui.radio_value(
+                    &mut scheduler_mode,
+                    SchedulerMode::EventDriven,
+                    "Event Driven",
+                );
+                ui.radio_value(&mut scheduler_mode, SchedulerMode::Sweep, "Every Node");
+            });
+            world.insert(scheduler_mode);
+
+            let mut speed_mode = *world.fetch::<SpeedMode>();
+            menu::menu(ui, "Speed", |ui| {
+                ui.radio_value(&mut speed_mode, SpeedMode::FramesPerTick, "Frames per Tick");
+                ui.radio_value(
+                    &mut speed_mode,
+                    SpeedMode::TicksPerSecond,
+                    "Ticks per Second",
+                );
+            });
+            world.insert(speed_mode);
+
+            let mut run_length = world.fetch::<RunLength>().0;
+            menu::menu(ui, "Run", |ui| {
+                ui.add(egui::DragValue::usize(&mut run_length).clamp_range(1.0..=1e9));
+                if ui.button("Run Ticks").clicked() {
+                    world
+                        .fetch_mut::<UiSignals>()
+                        .0
+                        .push(UiSignal::Run(PendingRun::Ticks(run_length)));
+                }
+                if ui.button("Run Until Stable").clicked() {
+                    world
+                        .fetch_mut::<UiSignals>()
+                        .0
+                        .push(UiSignal::Run(PendingRun::UntilStable(0)));
+                }
+            });
+            world.insert(RunLength(run_length));
+
+            if ui.button("Find Loops").clicked() {
+                world.fetch_mut::<UiSignals>().0.push(UiSignal::FindLoops);
+            }
+
+            if ui.button("Bind Key").clicked() {
+                world.fetch_mut::<UiSignals>().0.push(UiSignal::BindKey);
+            }
+
+            if ui.button("Set Delay").clicked() {
+                world.fetch_mut::<UiSignals>().0.push(UiSignal::SetDelay);
+            }
+
             if ui.button("Delete").clicked() {
                 world.fetch_mut::<UiSignals>().0.push(UiSignal::Delete);
             }
 
             if ui.button("Remove All").clicked() {
-                world.delete_all();
+                world.fetch_mut::<UiSignals>().0.push(UiSignal::RemoveAll);
             }
 
+            render_clock_settings(ui, world);
+            render_dip_switch_settings(ui, world);
+            render_port_settings(ui, world);
+            render_delay_settings(ui, world);
+
             let mut compound_node_data = world.fetch_mut::<CreatingCompoundNode>();
             match compound_node_data.0.as_mut() {
                 Some(CompoundNodeData { name, .. }) => {
@@ -85,9 +332,203 @@ pub fn render_top_panel(ui: &mut egui::Ui, world: &mut World) {
                 ui.add(egui::Label::new(&current_mode.0).wrap(true));
             }
 
-            let mut tick_frames = world.fetch::<resources::TickFrames>().0;
-            ui.add(egui::Slider::usize(&mut tick_frames, 1..=512).text("Frames per Tick"));
-            world.insert(resources::TickFrames(tick_frames));
+            if let Some(status) = &world.fetch::<StatusText>().0 {
+                ui.label(status);
+            }
+
+            if !world.fetch::<Oscillation>().nodes.is_empty() {
+                ui.colored_label(egui::Color32::RED, "Oscillating");
+            }
+
+            let speed_mode = *world.fetch::<SpeedMode>();
+            match speed_mode {
+                SpeedMode::FramesPerTick => {
+                    let mut tick_frames = world.fetch::<resources::TickFrames>().0;
+                    ui.add(egui::Slider::usize(&mut tick_frames, 1..=512).text("Frames per Tick"));
+                    world.insert(resources::TickFrames(tick_frames));
+                }
+                SpeedMode::TicksPerSecond => {
+                    let mut ticks_per_second = world.fetch::<TicksPerSecond>().0;
+                    ui.add(
+                        egui::Slider::f64(&mut ticks_per_second, 1.0..=1_000_000.0)
+                            .logarithmic(true)
+                            .integer()
+                            .text("Ticks per Second"),
+                    );
+                    world.insert(TicksPerSecond(ticks_per_second));
+                }
+            }
         });
     });
 }
+
+// the selected clock's settings are edited in place until it's closed
+fn render_clock_settings(ui: &mut egui::Ui, world: &mut World) {
+    let selected = match world.fetch::<SelectedClock>().0 {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let mut clocks = world.write_storage::<Connected<nodes::ClockNode, 0, 1>>();
+    let clock = match clocks.get_mut(selected) {
+        Some(clock) => &mut clock.node,
+        // deleted since it was selected
+        None => {
+            std::mem::drop(clocks);
+            world.insert(SelectedClock(None));
+            return;
+        }
+    };
+
+    ui.label("Clock");
+    ui.add(
+        egui::DragValue::u32(&mut clock.period)
+            .clamp_range(1.0..=1e6)
+            .prefix("period: "),
+    );
+    ui.add(egui::DragValue::u32(&mut clock.phase).prefix("phase: "));
+    ui.add(
+        egui::DragValue::f64(&mut clock.duty)
+            .clamp_range(0.0..=1.0)
+            .speed(0.01)
+            .prefix("duty: "),
+    );
+    let close = ui.button("Close").clicked();
+
+    std::mem::drop(clocks);
+    if close {
+        world.insert(SelectedClock(None));
+    }
+}
+
+// like the clock settings, the value is typed in place until it's closed
+fn render_dip_switch_settings(ui: &mut egui::Ui, world: &mut World) {
+    let selected = match world.fetch::<SelectedDipSwitch>().0 {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let mut dips = world.write_storage::<Connected<nodes::DipSwitchNode, 0, 8>>();
+    let dip = match dips.get_mut(selected) {
+        Some(dip) => &mut dip.node,
+        None => {
+            std::mem::drop(dips);
+            world.insert(SelectedDipSwitch(None));
+            return;
+        }
+    };
+
+    let max = (1u64 << nodes::DIP_SWITCH_BITS) - 1;
+    ui.label("DIP Switch");
+    ui.add(
+        egui::DragValue::u32(&mut dip.value)
+            .clamp_range(0.0..=max as f32)
+            .prefix("value: "),
+    );
+    let close = ui.button("Close").clicked();
+
+    std::mem::drop(dips);
+    if close {
+        world.insert(SelectedDipSwitch(None));
+    }
+}
+
+fn render_port_settings(ui: &mut egui::Ui, world: &mut World) {
+    let selected = match world.fetch::<SelectedPort>().0 {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let is_input = world
+        .read_storage::<Connected<nodes::InputPortNode, 1, 1>>()
+        .contains(selected);
+    let open = if is_input {
+        render_port::<nodes::InputPortNode>(ui, world, selected)
+    } else {
+        render_port::<nodes::OutputPortNode>(ui, world, selected)
+    };
+
+    if !open {
+        world.insert(SelectedPort(None));
+    }
+}
+
+// returns whether the settings are still shown
+fn render_port<N>(ui: &mut egui::Ui, world: &World, entity: Entity) -> bool
+where
+    N: Node<1, 1> + Port + 'static,
+{
+    let mut ports = world.write_storage::<Connected<N, 1, 1>>();
+    let port = match ports.get_mut(entity) {
+        Some(port) => &mut port.node,
+        // deleted since it was selected
+        None => return false,
+    };
+
+    ui.label(N::NAME);
+    ui.text_edit_singleline(port.name_mut());
+    ui.add(egui::DragValue::u32(port.order_mut()).prefix("order: "));
+    !ui.button("Close").clicked()
+}
+
+// the node's own delay can be turned off to fall back on the one set for its type
+fn render_delay_settings(ui: &mut egui::Ui, world: &mut World) {
+    let selected = match world.fetch::<SelectedDelay>().0 {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let ty = match nodes::NodeTy::of(world, selected) {
+        Some(ty) => ty,
+        // deleted since it was selected
+        None => {
+            world.insert(SelectedDelay(None));
+            return;
+        }
+    };
+
+    let mut delays = world.write_storage::<Delay>();
+    let mut type_delays = world.fetch_mut::<NodeDelays>();
+    let mut type_delay = type_delays
+        .0
+        .get(&ty)
+        .copied()
+        .unwrap_or_else(|| ty.default_delay());
+
+    ui.label("Delay");
+    let mut own = delays.contains(selected);
+    ui.checkbox(&mut own, "own");
+    match (own, delays.get_mut(selected)) {
+        (true, Some(delay)) => {
+            ui.add(
+                egui::DragValue::u32(&mut delay.0)
+                    .clamp_range(1.0..=1e6)
+                    .prefix("node: "),
+            );
+        }
+        (true, None) => {
+            delays.insert(selected, Delay(type_delay)).unwrap();
+        }
+        (false, Some(_)) => {
+            delays.remove(selected);
+        }
+        (false, None) => {}
+    }
+
+    let old_type_delay = type_delay;
+    ui.add(
+        egui::DragValue::u32(&mut type_delay)
+            .clamp_range(1.0..=1e6)
+            .prefix("type: "),
+    );
+    if type_delay != old_type_delay {
+        type_delays.0.insert(ty, type_delay);
+    }
+    let close = ui.button("Close").clicked();
+
+    std::mem::drop(delays);
+    std::mem::drop(type_delays);
+    if close {
+        world.insert(SelectedDelay(None));
+    }
+}
@@ -0,0 +1,115 @@
+use simple_electronics::components::Node;
+use simple_electronics::nodes::{
+    AdderNode, ComparatorNode, Decoder4Node, Demux4Node, Encoder8Node, FullAdderNode,
+    HalfAdderNode, Mux4Node, MuxNode,
+};
+use simple_electronics::Signal::{High, Low, X};
+use simple_electronics::{Bus, Signal};
+
+fn add(a: u64, b: u64, carry: bool, width: usize) -> (Option<u64>, Signal) {
+    let [sum, carry] = AdderNode.calculate_bus_state([
+        Bus::from_u64(a, width),
+        Bus::from_u64(b, width),
+        Bus::from(Signal::from(carry)),
+    ]);
+    assert_eq!(sum.width(), width);
+    (sum.to_u64(), carry.bit(0))
+}
+
+fn compare(a: u64, b: u64) -> [Signal; 3] {
+    ComparatorNode
+        .calculate_bus_state([Bus::from_u64(a, 8), Bus::from_u64(b, 8)])
+        .map(|out| out.bit(0))
+}
+
+#[test]
+fn one_bit_adders() {
+    assert_eq!(HalfAdderNode.calculate_state([High, High]), [Low, High]);
+    assert_eq!(HalfAdderNode.calculate_state([High, Low]), [High, Low]);
+    assert_eq!(
+        FullAdderNode.calculate_state([High, High, High]),
+        [High, High]
+    );
+    assert_eq!(
+        FullAdderNode.calculate_state([Low, High, High]),
+        [Low, High]
+    );
+    assert_eq!(FullAdderNode.calculate_state([Low, Low, High]), [High, Low]);
+}
+
+#[test]
+fn adder_carries_out_of_the_top_bit() {
+    assert_eq!(add(3, 4, false, 4), (Some(7), Low));
+    assert_eq!(add(3, 4, true, 4), (Some(8), Low));
+    assert_eq!(add(9, 8, false, 4), (Some(1), High));
+    assert_eq!(add(0xFF, 0, true, 8), (Some(0), High));
+}
+
+#[test]
+fn adder_pads_the_narrower_operand() {
+    let [sum, carry] = AdderNode.calculate_bus_state([
+        Bus::from_u64(0xF0, 8),
+        Bus::from_u64(0xF, 4),
+        Bus::from(Low),
+    ]);
+    assert_eq!((sum.to_u64(), carry.bit(0)), (Some(0xFF), Low));
+}
+
+#[test]
+fn comparator_sets_exactly_one_output() {
+    assert_eq!(compare(3, 200), [High, Low, Low]);
+    assert_eq!(compare(42, 42), [Low, High, Low]);
+    assert_eq!(compare(255, 0), [Low, Low, High]);
+
+    let unknown = ComparatorNode
+        .calculate_bus_state([Bus::new(8, X), Bus::from_u64(1, 8)])
+        .map(|out| out.bit(0));
+    assert_eq!(unknown, [X; 3]);
+}
+
+#[test]
+fn mux_passes_the_selected_input() {
+    let data = [0x11, 0x22, 0x33, 0x44].map(|value| Bus::from_u64(value, 8));
+    let mux = |select: Bus| {
+        Mux4Node::default().calculate_bus_state([data[0], data[1], data[2], data[3], select])[0]
+    };
+    assert_eq!(mux(Bus::from_u64(2, 2)), data[2]);
+    assert_eq!(mux(Bus::from_u64(0, 2)), data[0]);
+    assert_eq!(mux(Bus::new(2, X)), Bus::new(8, X));
+}
+
+#[test]
+fn muxes_are_named_after_their_data_inputs() {
+    // the select input comes after the data inputs
+    assert_eq!(MuxNode::<2>::input_widths(), [0, 0, 1]);
+    assert_eq!(MuxNode::<8>::input_widths(), [0, 0, 0, 0, 0, 0, 0, 0, 3]);
+
+    let mut data = [Bus::from(Low); 9];
+    data[6] = Bus::from(High);
+    data[8] = Bus::from_u64(6, 3);
+    assert_eq!(MuxNode::<8> {}.calculate_bus_state(data)[0].bit(0), High);
+}
+
+#[test]
+fn demux_and_decoder_pick_one_output() {
+    let out = Demux4Node::default().calculate_bus_state([Bus::from_u64(5, 4), Bus::from_u64(1, 2)]);
+    assert_eq!(
+        out.map(|bus| bus.to_u64()),
+        [Some(0), Some(5), Some(0), Some(0)]
+    );
+
+    let out = Decoder4Node::default().calculate_bus_state([Bus::from_u64(3, 2)]);
+    assert_eq!(out.map(|bus| bus.bit(0)), [Low, Low, Low, High]);
+}
+
+#[test]
+fn encoder_gives_the_highest_input() {
+    let mut inputs = [Bus::from(Low); 8];
+    inputs[2] = Bus::from(High);
+    inputs[5] = Bus::from(High);
+    let [y, valid] = Encoder8Node::default().calculate_bus_state(inputs);
+    assert_eq!((y.to_u64(), valid.bit(0)), (Some(5), High));
+
+    let [_, valid] = Encoder8Node::default().calculate_bus_state([Bus::from(Low); 8]);
+    assert_eq!(valid.bit(0), Low);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::{bit, script, simulator};
+use simple_electronics::components::NodeWarning;
+use simple_electronics::{Bus, Signal};
+use specs::prelude::*;
+
+#[test]
+fn u64_round_trip() {
+    let bus = Bus::from_u64(0b1011, 4);
+    assert_eq!(bus.width(), 4);
+    assert_eq!(
+        bus.bits(),
+        [Signal::High, Signal::High, Signal::Low, Signal::High]
+    );
+    assert_eq!(bus.to_u64(), Some(0b1011));
+
+    // bits past the width are dropped
+    assert_eq!(Bus::from_u64(0x1FF, 8).to_u64(), Some(0xFF));
+    assert_eq!(
+        Bus::from_u64(u32::MAX as u64, 32).to_u64(),
+        Some(u32::MAX as u64)
+    );
+}
+
+#[test]
+fn unknown_bits_have_no_value() {
+    let mut bus = Bus::from_u64(0, 8);
+    bus.set_bit(5, Signal::X);
+    assert_eq!(bus.to_u64(), None);
+    assert_eq!(bus.to_string(), "0xX0");
+    assert_eq!(bus.bit(8), Signal::Z);
+
+    assert_eq!(Bus::new(8, Signal::Z).to_string(), "0xZZ");
+    assert_eq!(Bus::from_u64(0x3F, 8).to_string(), "0x3F");
+    assert_eq!(Bus::from(Signal::High).to_string(), "1");
+}
+
+#[test]
+fn merging_then_splitting_gives_back_the_bits() {
+    let mut sim = simulator(&script(
+        &["b0", "b1", "b2", "b3", "bus", "o0", "o1", "o2", "o3"],
+        &[
+            r#"type: "Switch", name: "s0", inputs: [], outputs: [["b0"]]"#,
+            r#"type: "Switch", name: "s1", inputs: [], outputs: [["b1"]]"#,
+            r#"type: "Switch", name: "s2", inputs: [], outputs: [["b2"]]"#,
+            r#"type: "Switch", name: "s3", inputs: [], outputs: [["b3"]]"#,
+            r#"type: "Merger4", inputs: [["b0"], ["b1"], ["b2"], ["b3"]], outputs: [["bus"]]"#,
+            r#"type: "Splitter4", inputs: [["bus"]], outputs: [["o0"], ["o1"], ["o2"], ["o3"]]"#,
+        ],
+    ));
+
+    for value in [0b0000, 0b0110, 0b1001, 0b1111] {
+        (0..4).for_each(|i| {
+            sim.set_switch(&format!("s{}", i), value >> i & 1 == 1);
+        });
+        sim.step(6);
+
+        assert_eq!(sim.wire_state("bus"), Some(Bus::from_u64(value, 4)));
+        (0..4).for_each(|i| {
+            assert_eq!(
+                bit(&sim, &format!("o{}", i)),
+                Signal::from(value >> i & 1 == 1)
+            );
+        });
+    }
+}
+
+#[test]
+fn splitting_a_bus_of_the_wrong_width_is_a_warning() {
+    let mut sim = simulator(&script(
+        &["b0", "bus", "o0"],
+        &[
+            r#"type: "On", inputs: [], outputs: [["b0"]]"#,
+            r#"type: "Merger4", inputs: [["b0"], [], [], []], outputs: [["bus"]]"#,
+            r#"type: "Splitter8", inputs: [["bus"]], outputs: [["o0"], [], [], [], [], [], [], []]"#,
+        ],
+    ));
+    sim.step(6);
+
+    assert_eq!(bit(&sim, "o0"), Signal::High);
+    assert!((&sim.world.read_storage::<NodeWarning>())
+        .join()
+        .any(|warning| *warning == NodeWarning::WidthMismatch));
+}
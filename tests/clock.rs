@@ -0,0 +1,66 @@
+mod common;
+
+use common::{bit, script, simulator};
+use simple_electronics::nodes::ClockNode;
+use simple_electronics::Signal;
+
+fn sequence(period: u32, phase: u32, duty: f64) -> String {
+    let mut clock = ClockNode {
+        period,
+        phase,
+        duty,
+        ..ClockNode::default()
+    };
+    (0..12)
+        .map(|tick| {
+            clock.update(tick);
+            if clock.state {
+                '1'
+            } else {
+                '0'
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn clock_output_follows_period_phase_and_duty() {
+    assert_eq!(sequence(4, 0, 0.5), "110011001100");
+    assert_eq!(sequence(4, 1, 0.5), "011001100110");
+    assert_eq!(sequence(4, 0, 0.25), "100010001000");
+    assert_eq!(sequence(6, 2, 0.5), "001110001110");
+    assert_eq!(sequence(3, 0, 0.0), "000000000000");
+    assert_eq!(sequence(3, 0, 1.0), "111111111111");
+    // the phase wraps around the period
+    assert_eq!(sequence(4, 5, 0.5), sequence(4, 1, 0.5));
+}
+
+#[test]
+fn clock_drives_its_wire() {
+    let mut sim = simulator(&script(
+        &["clk"],
+        &[r#"type: "Clock", node: #{ period: 6, duty: 0.5 }, inputs: [], outputs: [["clk"]]"#],
+    ));
+
+    let trace = (0..30)
+        .map(|_| {
+            sim.step(1);
+            bit(&sim, "clk")
+        })
+        .collect::<Vec<_>>();
+
+    // after the first edge the wire is a square wave with the clock's period
+    let first_edge = trace.iter().position(|s| *s == Signal::High).unwrap();
+    let expected = [
+        Signal::High,
+        Signal::High,
+        Signal::High,
+        Signal::Low,
+        Signal::Low,
+        Signal::Low,
+    ];
+    trace[first_edge..]
+        .iter()
+        .enumerate()
+        .for_each(|(i, s)| assert_eq!(*s, expected[i % 6], "{:?}", trace));
+}
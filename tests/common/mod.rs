@@ -0,0 +1,53 @@
+#![allow(dead_code)]
+
+use simple_electronics::components::Name;
+use simple_electronics::{Signal, Simulator, Wire};
+use specs::prelude::*;
+
+/// Builds a circuit script from wire names and the fields of each node other than its position
+pub fn script(wires: &[&str], nodes: &[&str]) -> String {
+    let wires = wires
+        .iter()
+        .map(|wire| format!("{}: #{{ bends: [] }}", wire))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let nodes = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| format!("#{{ pos: #{{ x: {}.0, y: 0.0 }}, {} }}", i * 100, node))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("let WIRES = #{{ {} }};\nlet NODES = [{}];\n", wires, nodes)
+}
+
+pub fn simulator(script: &str) -> Simulator<'static, 'static> {
+    let mut sim = Simulator::new();
+    sim.load_script(script).unwrap();
+    sim
+}
+
+pub fn wire(sim: &Simulator, name: &str) -> Wire {
+    let wires = sim.world.read_storage::<Wire>();
+    let names = sim.world.read_storage::<Name>();
+    (&wires, &names)
+        .join()
+        .find(|(_, wire_name)| wire_name.0 == name)
+        .map(|(wire, _)| wire.clone())
+        .unwrap()
+}
+
+pub fn bit(sim: &Simulator, wire: &str) -> Signal {
+    sim.wire_state(wire).unwrap().bit(0)
+}
+
+pub fn settle(sim: &mut Simulator) {
+    assert!(sim.run_until_stable(100).is_some());
+}
+
+/// Sets the switch high then low again, settling after each
+pub fn pulse(sim: &mut Simulator, switch: &str) {
+    sim.set_switch(switch, true);
+    settle(sim);
+    sim.set_switch(switch, false);
+    settle(sim);
+}
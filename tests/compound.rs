@@ -0,0 +1,225 @@
+mod common;
+
+use common::{bit, settle, simulator};
+use glam::Vec2;
+use simple_electronics::components::{CompoundNode, Connection, Name};
+use simple_electronics::compound::{place_compound_node, save_compound_node, CompoundNodeError};
+use simple_electronics::resources::{CompoundNodeLibrary, CreatingCompoundNode};
+use simple_electronics::save_load::SaveError;
+use simple_electronics::systems::update_current_scope_sys::UpdateCurrentScopeSys;
+use simple_electronics::{CompoundNodeData, Pos, Signal, Simulator, Wire};
+use specs::prelude::*;
+
+// two switches and an output wire to hook a compound node up to
+const OUTSIDE: &str = r#"
+let WIRES = #{ a: #{ bends: [] }, b: #{ bends: [] }, out: #{ bends: [] } };
+let NODES = [
+    #{ type: "Switch", name: "a", pos: #{ x: 0.0, y: 0.0 }, inputs: [], outputs: [["a"]] },
+    #{ type: "Switch", name: "b", pos: #{ x: 0.0, y: 100.0 }, inputs: [], outputs: [["b"]] },
+];
+"#;
+
+// high when the top input is high and the bottom one is low, so swapped pins show up
+const A_AND_NOT_B: &str = r#"
+let WIRES = #{ a: #{ bends: [] }, b: #{ bends: [] }, nb: #{ bends: [] }, y: #{ bends: [] } };
+let NODES = [
+    #{ type: "InputPort", pos: #{ x: 0.0, y: 20.0 }, inputs: [[]], outputs: [["a"]], node: #{ name: "a" } },
+    #{ type: "InputPort", pos: #{ x: 0.0, y: (-20.0) }, inputs: [[]], outputs: [["b"]], node: #{ name: "b" } },
+    #{ type: "Not", pos: #{ x: 100.0, y: (-20.0) }, inputs: [["b"]], outputs: [["nb"]] },
+    #{ type: "And", pos: #{ x: 200.0, y: 0.0 }, inputs: [["a"], ["nb"]], outputs: [["y"]] },
+    #{ type: "OutputPort", pos: #{ x: 300.0, y: 0.0 }, inputs: [["y"]], outputs: [[]], node: #{ name: "y" } },
+];
+"#;
+
+fn create_compound_node(sim: &mut Simulator, script: &str) -> Entity {
+    let entity = sim
+        .world
+        .create_entity()
+        .with(CompoundNode::default())
+        .build();
+    sim.world
+        .insert(CreatingCompoundNode(Some(CompoundNodeData {
+            entity,
+            name: String::new(),
+        })));
+    UpdateCurrentScopeSys.run_now(&sim.world);
+    sim.load_script(script).unwrap();
+    entity
+}
+
+fn named_wire(sim: &Simulator, name: &str) -> Entity {
+    let wires = sim.world.read_storage::<Wire>();
+    let names = sim.world.read_storage::<Name>();
+    (&wires, &names, &sim.world.entities())
+        .join()
+        .find(|(_, wire_name, _)| wire_name.0 == name)
+        .map(|(_, _, entity)| entity)
+        .unwrap()
+}
+
+// wires the block's pins to the wires outside, inputs to a and b and the output to out
+fn hook_up(sim: &mut Simulator, block: Entity) {
+    let compound_node = sim
+        .world
+        .read_storage::<CompoundNode>()
+        .get(block)
+        .cloned()
+        .unwrap();
+    let pins = compound_node
+        .inputs
+        .iter()
+        .zip(["a", "b"])
+        .chain(compound_node.outputs.iter().zip(["out"]))
+        .map(|(pin, wire)| (*pin, named_wire(sim, wire)))
+        .collect::<Vec<_>>();
+    let mut connections = sim.world.write_storage::<Connection>();
+    for (pin, wire) in pins {
+        connections.get_mut(pin).unwrap().wires.push(wire);
+    }
+}
+
+fn out_with(sim: &mut Simulator, a: bool, b: bool) -> Signal {
+    sim.set_switch("a", a);
+    sim.set_switch("b", b);
+    settle(sim);
+    bit(sim, "out")
+}
+
+fn placed_a_and_not_b() -> (Simulator<'static, 'static>, Entity) {
+    placed(A_AND_NOT_B)
+}
+
+fn placed(script: &str) -> (Simulator<'static, 'static>, Entity) {
+    let mut sim = simulator(OUTSIDE);
+    let entity = create_compound_node(&mut sim, script);
+    assert_eq!(
+        save_compound_node(&mut sim.world, entity, "a and not b").unwrap(),
+        (2, 1)
+    );
+    let block = place_compound_node(
+        &mut sim.world,
+        "a and not b",
+        Pos::from_vec(Vec2::new(200.0, 0.0)),
+    )
+    .unwrap();
+    hook_up(&mut sim, block);
+    (sim, block)
+}
+
+#[test]
+fn saving_moves_the_inner_circuit_into_the_library() {
+    let (sim, _) = placed_a_and_not_b();
+    assert!(sim.world.fetch::<CreatingCompoundNode>().0.is_none());
+
+    let library = sim.world.fetch::<CompoundNodeLibrary>();
+    let template = &library.0["a and not b"];
+    assert_eq!((template.inputs.len(), template.outputs.len()), (2, 1));
+}
+
+#[test]
+fn pins_are_ordered_from_the_top_down() {
+    let (mut sim, block) = placed_a_and_not_b();
+    assert_eq!(out_with(&mut sim, true, false), Signal::High);
+    assert_eq!(out_with(&mut sim, true, true), Signal::Low);
+    assert_eq!(out_with(&mut sim, false, false), Signal::Low);
+
+    // the pins are placed down the sides of the block in the same order
+    let compound_node = sim
+        .world
+        .read_storage::<CompoundNode>()
+        .get(block)
+        .cloned()
+        .unwrap();
+    let positions = sim.world.read_storage::<Pos>();
+    let y = |pin: &Entity| positions.get(*pin).unwrap().pos.y;
+    assert!(y(&compound_node.inputs[0]) > y(&compound_node.inputs[1]));
+}
+
+#[test]
+fn port_order_comes_before_position() {
+    let script = A_AND_NOT_B
+        .replace(r#"#{ name: "a" }"#, r#"#{ name: "a", order: 1 }"#)
+        .replace(r#"#{ name: "b" }"#, r#"#{ name: "b", order: 0 }"#);
+    let (mut sim, _) = placed(&script);
+    // the bottom port is now the first pin, wired to a
+    assert_eq!(out_with(&mut sim, false, true), Signal::High);
+    assert_eq!(out_with(&mut sim, true, false), Signal::Low);
+}
+
+#[test]
+fn compound_nodes_need_a_name_and_pins() {
+    let mut sim = simulator(OUTSIDE);
+    let entity = create_compound_node(&mut sim, A_AND_NOT_B);
+    assert!(matches!(
+        save_compound_node(&mut sim.world, entity, " "),
+        Err(CompoundNodeError::MissingName)
+    ));
+
+    let mut sim = simulator(OUTSIDE);
+    let entity = create_compound_node(
+        &mut sim,
+        r#"
+        let WIRES = #{ x: #{ bends: [] } };
+        let NODES = [#{ type: "Switch", pos: #{ x: 0.0, y: 0.0 }, inputs: [], outputs: [["x"]] }];
+        "#,
+    );
+    assert!(matches!(
+        save_compound_node(&mut sim.world, entity, "no pins"),
+        Err(CompoundNodeError::NoPins)
+    ));
+    assert!(matches!(
+        place_compound_node(
+            &mut sim.world,
+            "missing",
+            Pos::from_vec(Vec2::new(0.0, 0.0))
+        ),
+        Err(CompoundNodeError::UnknownCompoundNode(_))
+    ));
+}
+
+#[test]
+fn saved_files_keep_the_library_and_placed_blocks() {
+    let (sim, _) = placed_a_and_not_b();
+    let path = std::env::temp_dir().join(format!("compound_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    sim.save_file(path).unwrap();
+
+    let mut loaded = Simulator::new();
+    loaded.load_file(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert!(loaded
+        .world
+        .fetch::<CompoundNodeLibrary>()
+        .0
+        .contains_key("a and not b"));
+    assert_eq!(out_with(&mut loaded, true, false), Signal::High);
+    assert_eq!(out_with(&mut loaded, false, true), Signal::Low);
+
+    // and the library's copy can still be placed
+    place_compound_node(
+        &mut loaded.world,
+        "a and not b",
+        Pos::from_vec(Vec2::new(0.0, 0.0)),
+    )
+    .unwrap();
+    assert_eq!(loaded.world.read_storage::<CompoundNode>().count(), 2);
+}
+
+#[test]
+fn templates_with_missing_pins_are_rejected() {
+    let (mut sim, _) = placed_a_and_not_b();
+    {
+        let mut library = sim.world.fetch_mut::<CompoundNodeLibrary>();
+        let mut broken = library.0["a and not b"].clone();
+        broken.inputs.push(u32::MAX);
+        library.0.insert("broken".to_string(), broken);
+    }
+    let entities = sim.world.entities().join().count();
+
+    assert!(matches!(
+        place_compound_node(&mut sim.world, "broken", Pos::from_vec(Vec2::new(0.0, 0.0))),
+        Err(CompoundNodeError::Save(SaveError::MissingEntity(id))) if id == u32::MAX
+    ));
+    assert_eq!(sim.world.entities().join().count(), entities);
+}
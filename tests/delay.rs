@@ -0,0 +1,201 @@
+mod common;
+
+use common::{bit, script, settle, simulator};
+use simple_electronics::components::Delay;
+use simple_electronics::nodes::NodeTy;
+use simple_electronics::resources::NodeDelays;
+use simple_electronics::scripting::export_circuit;
+use simple_electronics::{Signal, Simulator};
+use specs::prelude::*;
+
+// `out = a & !a`, which is always low once settled
+fn hazard(not_delay: &str) -> Simulator<'static, 'static> {
+    settled(&hazard_script(not_delay))
+}
+
+fn hazard_script(not_delay: &str) -> String {
+    script(
+        &["a", "na", "out"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            &format!(
+                r#"type: "Not", inputs: [["a"]], outputs: [["na"]]{}"#,
+                not_delay
+            ),
+            r#"type: "And", inputs: [["a"], ["na"]], outputs: [["out"]]"#,
+        ],
+    )
+}
+
+// the same circuit with every not gate delayed by 3
+fn table_hazard(not_delay: &str) -> Simulator<'static, 'static> {
+    settled(&format!(
+        "{}let DELAYS = #{{ Not: 3 }};\n",
+        hazard_script(not_delay)
+    ))
+}
+
+fn settled(script: &str) -> Simulator<'static, 'static> {
+    let mut sim = simulator(script);
+    sim.step(10);
+    sim
+}
+
+// the number of ticks `out` is high for after `a` rises
+fn glitch_width(sim: &mut Simulator) -> usize {
+    sim.set_switch("a", true);
+    (0..12)
+        .filter(|_| {
+            sim.step(1);
+            bit(sim, "out") == Signal::High
+        })
+        .count()
+}
+
+#[test]
+fn unequal_path_delays_cause_a_static_hazard() {
+    let mut sim = hazard("");
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+    assert_eq!(glitch_width(&mut sim), 1);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+
+    // the pulse is as wide as the delay of the not gate
+    let mut sim = hazard(", delay: 4");
+    assert_eq!(glitch_width(&mut sim), 4);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+}
+
+#[test]
+fn delays_hold_back_output_changes() {
+    let mut sim = simulator(&script(
+        &["a", "na"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", delay: 5, inputs: [["a"]], outputs: [["na"]]"#,
+        ],
+    ));
+    sim.step(10);
+    assert_eq!(bit(&sim, "na"), Signal::High);
+
+    sim.set_switch("a", true);
+    let trace = (0..8)
+        .map(|_| {
+            sim.step(1);
+            bit(&sim, "na")
+        })
+        .collect::<Vec<_>>();
+    let changed_at = trace.iter().position(|s| *s == Signal::Low).unwrap();
+    assert!(trace[changed_at..].iter().all(|s| *s == Signal::Low));
+
+    // the same circuit without the delay changes 4 ticks sooner
+    let mut sim = simulator(&script(
+        &["a", "na"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["na"]]"#,
+        ],
+    ));
+    sim.step(10);
+    sim.set_switch("a", true);
+    let undelayed = (0..8)
+        .position(|_| {
+            sim.step(1);
+            bit(&sim, "na") == Signal::Low
+        })
+        .unwrap();
+    assert_eq!(changed_at, undelayed + 4);
+}
+
+#[test]
+fn delays_are_kept_by_scripts_and_saves() {
+    let sim = hazard(", delay: 4");
+    let exported = export_circuit(&sim.world).unwrap();
+    assert!(exported.contains("delay: 4"));
+    assert_eq!(glitch_width(&mut settled(&exported)), 4);
+
+    let path = std::env::temp_dir().join(format!("delay_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    sim.save_file(path).unwrap();
+    let mut loaded = Simulator::new();
+    loaded.load_file(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    loaded.step(10);
+    assert_eq!(glitch_width(&mut loaded), 4);
+}
+
+#[test]
+fn type_delays_apply_to_nodes_without_their_own() {
+    let mut sim = table_hazard("");
+    assert_eq!(glitch_width(&mut sim), 3);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+
+    let mut sim = table_hazard(", delay: 2");
+    assert_eq!(glitch_width(&mut sim), 2);
+
+    // other types keep their default
+    let sim = table_hazard("");
+    assert_eq!(
+        sim.world.fetch::<NodeDelays>().0.get(&NodeTy::AndNode),
+        None
+    );
+    assert_eq!(NodeTy::AndNode.default_delay(), 1);
+}
+
+#[test]
+fn type_delays_are_kept_by_scripts_and_saves() {
+    let sim = table_hazard("");
+    let exported = export_circuit(&sim.world).unwrap();
+    assert!(exported.contains("let DELAYS = #{\n    Not: 3,\n};"));
+    assert_eq!(glitch_width(&mut settled(&exported)), 3);
+
+    let path = std::env::temp_dir().join(format!("type_delay_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    sim.save_file(path).unwrap();
+    let mut loaded = Simulator::new();
+    loaded.load_file(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    loaded.step(10);
+    assert_eq!(glitch_width(&mut loaded), 3);
+}
+
+#[test]
+fn type_delays_must_be_positive() {
+    let mut sim = Simulator::new();
+    let error = sim
+        .load_script(&format!(
+            "{}let DELAYS = #{{ Not: 0 }};\n",
+            hazard_script("")
+        ))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "`Not` of DELAYS has the wrong type");
+    assert!(sim.world.fetch::<NodeDelays>().0.is_empty());
+}
+
+#[test]
+fn changing_a_delay_mid_run_keeps_later_changes() {
+    let mut sim = simulator(&script(
+        &["a", "na"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", delay: 3, inputs: [["a"]], outputs: [["na"]]"#,
+        ],
+    ));
+    let set_delay = |sim: &mut Simulator, delay| {
+        (&mut sim.world.write_storage::<Delay>())
+            .join()
+            .for_each(|d| d.0 = delay);
+    };
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "na"), Signal::High);
+
+    set_delay(&mut sim, 1);
+    sim.set_switch("a", true);
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "na"), Signal::Low);
+
+    // high was the last value scheduled, but the wire has been low since
+    set_delay(&mut sim, 3);
+    sim.set_switch("a", false);
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "na"), Signal::High);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{bit, script, simulator};
+use simple_electronics::resources::DriverPolicy;
+use simple_electronics::Signal;
+
+// two switches driving the same wire
+fn two_drivers(policy: DriverPolicy, a: bool, b: bool) -> Signal {
+    let mut sim = simulator(&script(
+        &["w"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["w"]]"#,
+            r#"type: "Switch", name: "b", inputs: [], outputs: [["w"]]"#,
+        ],
+    ));
+    sim.world.insert(policy);
+    sim.set_switch("a", a);
+    sim.set_switch("b", b);
+    sim.step(4);
+    bit(&sim, "w")
+}
+
+#[test]
+fn disagreeing_drivers_are_an_error_by_default() {
+    assert_eq!(two_drivers(DriverPolicy::Error, true, false), Signal::X);
+    assert_eq!(two_drivers(DriverPolicy::Error, false, true), Signal::X);
+    assert_eq!(two_drivers(DriverPolicy::Error, true, true), Signal::High);
+    assert_eq!(two_drivers(DriverPolicy::Error, false, false), Signal::Low);
+}
+
+#[test]
+fn wired_or_is_high_if_any_driver_is() {
+    assert_eq!(
+        two_drivers(DriverPolicy::WiredOr, true, false),
+        Signal::High
+    );
+    assert_eq!(
+        two_drivers(DriverPolicy::WiredOr, false, false),
+        Signal::Low
+    );
+}
+
+#[test]
+fn wired_and_is_high_only_if_every_driver_is() {
+    assert_eq!(
+        two_drivers(DriverPolicy::WiredAnd, true, false),
+        Signal::Low
+    );
+    assert_eq!(
+        two_drivers(DriverPolicy::WiredAnd, true, true),
+        Signal::High
+    );
+}
+
+#[test]
+fn conflicts_clear_when_the_drivers_agree() {
+    let mut sim = simulator(&script(
+        &["w"],
+        &[
+            r#"type: "Switch", name: "a", node: #{ state: true }, inputs: [], outputs: [["w"]]"#,
+            r#"type: "Switch", name: "b", inputs: [], outputs: [["w"]]"#,
+        ],
+    ));
+    sim.step(4);
+    assert_eq!(bit(&sim, "w"), Signal::X);
+
+    sim.set_switch("b", true);
+    sim.step(4);
+    assert_eq!(bit(&sim, "w"), Signal::High);
+}
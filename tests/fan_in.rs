@@ -0,0 +1,67 @@
+mod common;
+
+use common::{bit, script, simulator};
+use simple_electronics::components::NodeWarning;
+use simple_electronics::resources::{DriverPolicy, FanInPolicy};
+use simple_electronics::{Signal, Simulator};
+use specs::prelude::*;
+
+// two switches on separate wires both attached to the input of a not gate
+fn fan_in(policy: DriverPolicy, a: bool, b: bool) -> Simulator<'static, 'static> {
+    let mut sim = simulator(&script(
+        &["a", "b", "out"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Switch", name: "b", inputs: [], outputs: [["b"]]"#,
+            r#"type: "Not", inputs: [["a", "b"]], outputs: [["out"]]"#,
+        ],
+    ));
+    sim.world.insert(FanInPolicy(policy));
+    sim.set_switch("a", a);
+    sim.set_switch("b", b);
+    sim.step(4);
+    sim
+}
+
+fn warnings(sim: &Simulator) -> Vec<NodeWarning> {
+    (&sim.world.read_storage::<NodeWarning>())
+        .join()
+        .copied()
+        .collect()
+}
+
+#[test]
+fn fan_in_is_merged_with_the_policy() {
+    let out = |policy, a, b| bit(&fan_in(policy, a, b), "out");
+    assert_eq!(out(DriverPolicy::WiredOr, true, false), Signal::Low);
+    assert_eq!(out(DriverPolicy::WiredOr, false, false), Signal::High);
+    assert_eq!(out(DriverPolicy::WiredAnd, true, false), Signal::High);
+    assert_eq!(out(DriverPolicy::WiredAnd, true, true), Signal::Low);
+}
+
+#[test]
+fn conflicting_fan_in_is_unknown() {
+    let sim = fan_in(DriverPolicy::Error, true, false);
+    assert_eq!(bit(&sim, "out"), Signal::X);
+    assert_eq!(warnings(&sim), vec![NodeWarning::ConflictingInput]);
+
+    let sim = fan_in(DriverPolicy::Error, true, true);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+    assert!(warnings(&sim).is_empty());
+}
+
+#[test]
+fn unknown_values_propagate_through_nodes() {
+    let mut sim = simulator(&script(
+        &["a", "b"],
+        &[
+            r#"type: "Not", inputs: [[]], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["b"]]"#,
+        ],
+    ));
+    sim.step(4);
+
+    assert_eq!(bit(&sim, "a"), Signal::X);
+    assert_eq!(bit(&sim, "b"), Signal::X);
+    assert_eq!(warnings(&sim), vec![NodeWarning::UnconnectedInput]);
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{bit, script, settle, simulator};
+use simple_electronics::components::Node;
+use simple_electronics::nodes::{gate_height, And4Node, Nor3Node, Xnor5Node, Xor3Node};
+use simple_electronics::Signal::{self, High, Low, X};
+
+#[test]
+fn wide_gates_combine_every_input() {
+    let mut and = And4Node {};
+    assert_eq!(and.calculate_state([High; 4]), [High]);
+    assert_eq!(and.calculate_state([High, High, Low, High]), [Low]);
+
+    let mut nor = Nor3Node {};
+    assert_eq!(nor.calculate_state([Low; 3]), [High]);
+    assert_eq!(nor.calculate_state([Low, Low, High]), [Low]);
+
+    let mut xor = Xor3Node {};
+    assert_eq!(xor.calculate_state([High, High, High]), [High]);
+    assert_eq!(xor.calculate_state([High, Low, High]), [Low]);
+
+    let mut xnor = Xnor5Node {};
+    assert_eq!(xnor.calculate_state([High, High, Low, Low, Low]), [High]);
+    assert_eq!(xnor.calculate_state([High, Low, Low, Low, Low]), [Low]);
+}
+
+#[test]
+fn unknown_inputs_only_matter_when_they_decide_the_output() {
+    let mut and = And4Node {};
+    assert_eq!(and.calculate_state([High, X, Low, High]), [Low]);
+    assert_eq!(and.calculate_state([High, X, High, High]), [X]);
+
+    let mut xor = Xor3Node {};
+    assert_eq!(xor.calculate_state([Low, X, Low]), [X]);
+}
+
+#[test]
+fn inputs_are_spread_over_the_gate_height() {
+    let offsets = And4Node::input_offsets();
+    assert_eq!(gate_height(2), 40.0);
+    assert_eq!(gate_height(4), 60.0);
+    assert_eq!(gate_height(8), 140.0);
+
+    let ys: Vec<f32> = offsets.iter().map(|offset| offset.y).collect();
+    assert_eq!(ys, [-30.0, -10.0, 10.0, 30.0]);
+    assert!(offsets.iter().all(|offset| offset.x == -35.0));
+}
+
+#[test]
+fn scripts_place_wide_gates() {
+    let mut sim = simulator(&script(
+        &["a", "b", "out"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Switch", name: "b", inputs: [], outputs: [["b"]]"#,
+            r#"type: "Or4", inputs: [["a"], ["b"], ["a"], ["b"]], outputs: [["out"]]"#,
+        ],
+    ));
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+
+    sim.set_switch("b", true);
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "out"), Signal::High);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{script, simulator};
+use simple_electronics::resources::{History, PendingRun, OSCILLATION_TICKS};
+use simple_electronics::simulator::RunProgress;
+use simple_electronics::{Bus, Simulator};
+
+// a not gate ring mixed with a switch, so something changes every tick
+fn circuit() -> Simulator<'static, 'static> {
+    simulator(&script(
+        &["s", "r", "mix", "n"],
+        &[
+            r#"type: "Switch", name: "s", inputs: [], outputs: [["s"]]"#,
+            r#"type: "Not", inputs: [["r"]], outputs: [["r"]]"#,
+            r#"type: "Xor", inputs: [["r"], ["s"]], outputs: [["mix"]]"#,
+            r#"type: "Not", inputs: [["mix"]], outputs: [["n"]]"#,
+        ],
+    ))
+}
+
+fn wires(sim: &Simulator) -> Vec<(String, Bus)> {
+    let mut wires = sim.named_wires();
+    wires.sort_by(|a, b| a.0.cmp(&b.0));
+    wires
+}
+
+fn trace(sim: &mut Simulator, ticks: usize) -> Vec<Vec<(String, Bus)>> {
+    (0..ticks)
+        .map(|tick| {
+            if tick == 3 {
+                sim.set_switch("s", true);
+            }
+            sim.step(1);
+            wires(sim)
+        })
+        .collect()
+}
+
+#[test]
+fn step_back_then_replay() {
+    let mut sim = circuit();
+    sim.world.insert(History::new(64));
+    assert!(!sim.step_back());
+
+    let forward = trace(&mut sim, 20);
+    (0..8).for_each(|_| assert!(sim.step_back()));
+    assert_eq!(sim.ticks(), 12);
+    assert_eq!(wires(&sim), forward[11]);
+
+    let replayed = (0..8)
+        .map(|_| {
+            sim.step(1);
+            wires(&sim)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(replayed, forward[12..]);
+}
+
+#[test]
+fn history_only_keeps_its_capacity() {
+    let mut sim = circuit();
+    sim.world.insert(History::new(5));
+    sim.step(20);
+
+    (0..5).for_each(|_| assert!(sim.step_back()));
+    assert!(!sim.step_back());
+    assert_eq!(sim.ticks(), 15);
+}
+
+#[test]
+fn nothing_is_recorded_without_a_history() {
+    let mut sim = circuit();
+    sim.step(20);
+    assert!(!sim.step_back());
+    assert_eq!(sim.ticks(), 20);
+}
+
+#[test]
+fn step_until_change_stops_on_the_first_change() {
+    let mut sim = simulator(&script(
+        &["a", "b"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["b"]]"#,
+        ],
+    ));
+    assert!(sim.run_until_stable(100).is_some());
+    assert_eq!(sim.step_until_change(50), None);
+
+    sim.set_switch("a", true);
+    assert_eq!(sim.step_until_change(50), Some(2));
+    // the not gate changes on the next tick
+    assert_eq!(sim.step_until_change(50), Some(1));
+    assert!(sim.run_until_stable(100).is_some());
+
+    sim.set_switch("a", false);
+    assert_eq!(
+        sim.continue_run(PendingRun::UntilChange(0), || true),
+        RunProgress::Finished("Changed after 2 ticks".to_string())
+    );
+}
+
+#[test]
+fn loading_a_file_starts_the_history_over() {
+    let mut sim = circuit();
+    sim.world.insert(History::new(64));
+    let path = std::env::temp_dir().join(format!("history_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    sim.save_file(path).unwrap();
+
+    sim.step(6);
+    sim.load_file(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(sim.ticks(), 0);
+    assert!(!sim.step_back());
+    assert_eq!(sim.ticks(), 0);
+}
+
+#[test]
+fn resetting_starts_the_history_over() {
+    let mut sim = circuit();
+    sim.world.insert(History::new(64));
+    sim.step(6);
+
+    sim.reset();
+    assert!(!sim.step_back());
+    assert_eq!(sim.ticks(), 0);
+}
+
+#[test]
+fn removing_everything_starts_over() {
+    let mut sim = circuit();
+    sim.world.insert(History::new(64));
+    // long enough for the ring to count as oscillating
+    sim.step(OSCILLATION_TICKS + 10);
+    assert!(!sim.oscillating_nodes().is_empty());
+
+    sim.clear();
+    assert_eq!(sim.ticks(), 0);
+    assert!(sim.named_wires().is_empty());
+    assert!(sim.oscillating_nodes().is_empty());
+    assert!(!sim.step_back());
+
+    // a new circuit runs from scratch
+    sim.load_script(&script(
+        &["a", "b"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["b"]]"#,
+        ],
+    ))
+    .unwrap();
+    assert!(sim.run_until_stable(100).is_some());
+    assert_eq!(wires(&sim).len(), 2);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::{bit, script, settle, simulator};
+use simple_electronics::scripting::export_circuit;
+use simple_electronics::{Signal, Simulator};
+
+const BITS: [&str; 8] = ["b0", "b1", "b2", "b3", "b4", "b5", "b6", "b7"];
+
+fn dip_switch(value: u32) -> Simulator<'static, 'static> {
+    let node = format!(
+        r#"type: "DipSwitch", name: "dip", inputs: [], outputs: [{}], node: #{{ value: {} }}"#,
+        BITS.iter()
+            .map(|bit| format!("[\"{}\"]", bit))
+            .collect::<Vec<_>>()
+            .join(", "),
+        value
+    );
+    let mut sim = simulator(&script(&BITS, &[&node]));
+    settle(&mut sim);
+    sim
+}
+
+fn value(sim: &Simulator) -> String {
+    // bit 7 first, like the number is written
+    BITS.iter()
+        .rev()
+        .map(|name| match bit(sim, name) {
+            Signal::High => '1',
+            Signal::Low => '0',
+            _ => '?',
+        })
+        .collect()
+}
+
+#[test]
+fn dip_switch_drives_a_bit_per_output() {
+    let mut sim = dip_switch(0b1010_0110);
+    assert_eq!(value(&sim), "10100110");
+
+    assert!(sim.set_number("dip", 0x0F));
+    settle(&mut sim);
+    assert_eq!(value(&sim), "00001111");
+
+    // bits past the number of outputs are ignored
+    assert!(sim.set_number("dip", 0x1FF));
+    settle(&mut sim);
+    assert_eq!(value(&sim), "11111111");
+    assert!(!sim.set_number("missing", 1));
+}
+
+#[test]
+fn buttons_are_high_while_held() {
+    let mut sim = simulator(&script(
+        &["out"],
+        &[r#"type: "Button", name: "button", key: "b", inputs: [], outputs: [["out"]]"#],
+    ));
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+
+    assert!(sim.set_switch("button", true));
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "out"), Signal::High);
+
+    sim.set_switch("button", false);
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "out"), Signal::Low);
+}
+
+#[test]
+fn key_bindings_are_kept_by_scripts() {
+    let mut sim = simulator(&script(
+        &["out"],
+        &[r#"type: "Switch", key: "k", inputs: [], outputs: [["out"]]"#],
+    ));
+    let exported = export_circuit(&sim.world).unwrap();
+    // keys are kept in upper case
+    assert!(exported.contains(r#"key: "K""#), "{}", exported);
+
+    let bad = script(
+        &["out"],
+        &[r#"type: "Switch", key: "kk", inputs: [], outputs: [["out"]]"#],
+    );
+    assert!(sim.load_script(&bad).is_err());
+}
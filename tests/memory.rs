@@ -0,0 +1,124 @@
+use simple_electronics::components::Node;
+use simple_electronics::memory_file::{parse_binary, parse_hex, read_memory_file, MemoryFileError};
+use simple_electronics::nodes::{Memory, RamNode, RomNode};
+use simple_electronics::{Bus, Signal};
+
+#[test]
+fn hex_files_skip_comments_and_prefixes() {
+    let text = "# a header\n0x12 34 # trailing comment\n\n  0xff\tA\n";
+    assert_eq!(parse_hex(text, 8).unwrap(), vec![0x12, 0x34, 0xFF, 0xA]);
+    assert_eq!(parse_hex("", 8).unwrap(), Vec::<u32>::new());
+}
+
+#[test]
+fn hex_words_must_fit_the_data_width() {
+    assert_eq!(parse_hex("f", 4).unwrap(), vec![0xF]);
+    match parse_hex("1 2\n10", 4) {
+        Err(MemoryFileError::WordTooWide { line, word }) => {
+            assert_eq!((line, word.as_str()), (2, "10"));
+        }
+        other => panic!("expected a word too wide error, got {:?}", other),
+    }
+    assert!(parse_hex("ffffffff", 32).is_ok());
+    assert!(matches!(
+        parse_hex("1ffffffff", 32),
+        Err(MemoryFileError::WordTooWide { .. })
+    ));
+}
+
+#[test]
+fn invalid_hex_words_are_reported() {
+    match parse_hex("12\n34 zz", 8) {
+        Err(MemoryFileError::InvalidWord { line, word }) => {
+            assert_eq!((line, word.as_str()), (2, "zz"));
+        }
+        other => panic!("expected an invalid word error, got {:?}", other),
+    }
+    assert!(matches!(
+        parse_hex("0x", 8),
+        Err(MemoryFileError::InvalidWord { .. })
+    ));
+}
+
+#[test]
+fn binary_words_are_little_endian() {
+    assert_eq!(parse_binary(&[1, 2, 3], 8), vec![1, 2, 3]);
+    assert_eq!(
+        parse_binary(&[0x34, 0x12, 0x78, 0x56], 16),
+        vec![0x1234, 0x5678]
+    );
+    // bits past the data width are dropped
+    assert_eq!(parse_binary(&[0xFF, 0xFF], 12), vec![0xFFF]);
+    assert_eq!(parse_binary(&[0xAB], 4), vec![0xB]);
+}
+
+#[test]
+fn partial_last_binary_word_is_padded() {
+    assert_eq!(parse_binary(&[0x34, 0x12, 0x78], 16), vec![0x1234, 0x78]);
+    assert_eq!(parse_binary(&[1, 2, 3, 4, 5], 32), vec![0x04030201, 5]);
+}
+
+#[test]
+fn memory_files_are_read_by_extension() {
+    let dir = std::env::temp_dir();
+    let hex = dir.join(format!("memory_{}.hex", std::process::id()));
+    let bin = dir.join(format!("memory_{}.bin", std::process::id()));
+    std::fs::write(&hex, "41 42").unwrap();
+    std::fs::write(&bin, "41 42").unwrap();
+
+    assert_eq!(read_memory_file(&hex, 8).unwrap(), vec![0x41, 0x42]);
+    assert_eq!(
+        read_memory_file(&bin, 8).unwrap(),
+        vec![
+            b'4' as u32,
+            b'1' as u32,
+            b' ' as u32,
+            b'4' as u32,
+            b'2' as u32
+        ]
+    );
+    std::fs::remove_file(&hex).unwrap();
+    std::fs::remove_file(&bin).unwrap();
+    assert!(matches!(
+        read_memory_file(&hex, 8),
+        Err(MemoryFileError::Io(_))
+    ));
+}
+
+#[test]
+fn rom_outputs_the_word_at_the_address() {
+    let mut rom = RomNode::default();
+    rom.load(vec![0x12, 0x34]);
+    let read = |rom: &mut RomNode, address| rom.calculate_bus_state([address])[0];
+    let shared = &rom;
+    assert_eq!((shared.address_width(), shared.data_width()), (8, 8));
+
+    assert_eq!(read(&mut rom, Bus::from_u64(1, 8)), Bus::from_u64(0x34, 8));
+    // words which were never loaded read as 0
+    assert_eq!(read(&mut rom, Bus::from_u64(200, 8)), Bus::from_u64(0, 8));
+    assert_eq!(
+        read(&mut rom, Bus::new(8, Signal::X)),
+        Bus::new(8, Signal::X)
+    );
+}
+
+#[test]
+fn ram_writes_on_the_rising_edge() {
+    let mut ram = RamNode::default();
+    let address = Bus::from_u64(3, 8);
+    let data = Bus::from_u64(0x5A, 8);
+    let tick = |ram: &mut RamNode, write: bool, clock: bool| {
+        ram.calculate_bus_state([
+            address,
+            data,
+            Bus::from(Signal::from(write)),
+            Bus::from(Signal::from(clock)),
+        ])[0]
+    };
+
+    assert_eq!(tick(&mut ram, false, true), Bus::from_u64(0, 8));
+    tick(&mut ram, true, false);
+    assert_eq!(tick(&mut ram, true, true), data);
+    assert_eq!(ram.word(3), data);
+    assert_eq!(ram.stored_words(), 4);
+}
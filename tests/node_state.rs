@@ -0,0 +1,73 @@
+mod common;
+
+use common::{bit, pulse, script, settle, simulator};
+use simple_electronics::nodes::{DFlipFlopNode, SwitchNode};
+use simple_electronics::resources::History;
+use simple_electronics::systems::simulation_systems::ResetNodeSys;
+use simple_electronics::{Connected, Signal, Simulator};
+use specs::prelude::*;
+
+// a flip-flop clocked by a switch, taking in another switch
+fn flip_flop() -> Simulator<'static, 'static> {
+    simulator(&script(
+        &["d", "clk", "q", "nq"],
+        &[
+            r#"type: "Switch", name: "d", inputs: [], outputs: [["d"]]"#,
+            r#"type: "Switch", name: "clk", inputs: [], outputs: [["clk"]]"#,
+            r#"type: "DFlipFlop", inputs: [["d"], ["clk"]], outputs: [["q"], ["nq"]]"#,
+        ],
+    ))
+}
+
+fn q(sim: &Simulator) -> Signal {
+    (&sim.world.read_storage::<Connected<DFlipFlopNode, 2, 2>>())
+        .join()
+        .next()
+        .unwrap()
+        .node
+        .q
+}
+
+#[test]
+fn reset_node_sys_clears_sequential_state() {
+    let mut sim = flip_flop();
+    sim.set_switch("d", true);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::High);
+
+    ResetNodeSys::<DFlipFlopNode, 2, 2>::default().run_now(&sim.world);
+    assert_eq!(q(&sim), Signal::Low);
+}
+
+#[test]
+fn restarting_resets_nodes_but_keeps_switches() {
+    let mut sim = flip_flop();
+    sim.set_switch("d", true);
+    pulse(&mut sim, "clk");
+    assert_eq!(bit(&sim, "q"), Signal::High);
+
+    sim.reset();
+    assert_eq!(q(&sim), Signal::Low);
+    settle(&mut sim);
+    assert_eq!(bit(&sim, "q"), Signal::Low);
+    assert!((&sim.world.read_storage::<Connected<SwitchNode, 0, 1>>())
+        .join()
+        .any(|switch| switch.node.state));
+}
+
+#[test]
+fn stepping_back_restores_sequential_state() {
+    let mut sim = flip_flop();
+    sim.world.insert(History::new(64));
+    sim.set_switch("d", true);
+    settle(&mut sim);
+    let before = sim.ticks();
+
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::High);
+
+    while sim.ticks() > before {
+        assert!(sim.step_back());
+    }
+    assert_eq!(q(&sim), Signal::Low);
+}
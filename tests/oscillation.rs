@@ -0,0 +1,56 @@
+mod common;
+
+use common::{script, simulator};
+use simple_electronics::analysis::find_combinational_loops;
+use simple_electronics::resources::OSCILLATION_TICKS;
+
+#[test]
+fn not_ring_never_settles() {
+    let mut sim = simulator(&script(
+        &["a"],
+        &[r#"type: "Not", inputs: [["a"]], outputs: [["a"]]"#],
+    ));
+
+    assert_eq!(sim.run_until_stable(100), None);
+    assert!(sim.oscillating_nodes().is_empty());
+    sim.step(OSCILLATION_TICKS);
+    assert_eq!(sim.oscillating_nodes().len(), 1);
+}
+
+#[test]
+fn settled_circuits_are_not_oscillating() {
+    let mut sim = simulator(&script(
+        &["a", "b", "c"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["b"]]"#,
+            r#"type: "Not", inputs: [["b"]], outputs: [["c"]]"#,
+        ],
+    ));
+    sim.step(OSCILLATION_TICKS * 2);
+    assert!(sim.oscillating_nodes().is_empty());
+    assert!(find_combinational_loops(&sim.world).is_empty());
+}
+
+#[test]
+fn loops_through_several_nodes_are_found() {
+    let sim = simulator(&script(
+        &["s", "a", "b", "c", "out"],
+        &[
+            r#"type: "Switch", inputs: [], outputs: [["s"]]"#,
+            r#"type: "Nand", inputs: [["s"], ["c"]], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["b"]]"#,
+            r#"type: "Not", inputs: [["b"]], outputs: [["c"]]"#,
+            r#"type: "Not", inputs: [["c"]], outputs: [["out"]]"#,
+            r#"type: "Not", inputs: [["out"]], outputs: [["out"]]"#,
+        ],
+    ));
+
+    let loops = find_combinational_loops(&sim.world);
+    assert_eq!(
+        loops.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![3, 1],
+        "{:?}",
+        loops
+    );
+}
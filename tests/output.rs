@@ -0,0 +1,56 @@
+use simple_electronics::nodes::{HexDigitNode, LedColor, LedNode, Light};
+use simple_electronics::{Bus, Signal};
+
+fn lit(segments: [Signal; 8]) -> String {
+    segments
+        .iter()
+        .map(|signal| match signal {
+            Signal::High => '1',
+            Signal::Low => '0',
+            _ => '?',
+        })
+        .collect()
+}
+
+#[test]
+fn hex_digits_light_their_segments() {
+    // segments a to g, then the decimal point
+    assert_eq!(lit(HexDigitNode::segments(Bus::from_u64(0, 4))), "11111100");
+    assert_eq!(lit(HexDigitNode::segments(Bus::from_u64(1, 4))), "01100000");
+    assert_eq!(lit(HexDigitNode::segments(Bus::from_u64(8, 4))), "11111110");
+    assert_eq!(
+        lit(HexDigitNode::segments(Bus::from_u64(0xB, 4))),
+        "00111110"
+    );
+    assert_eq!(
+        lit(HexDigitNode::segments(Bus::from_u64(0xF, 4))),
+        "10001110"
+    );
+}
+
+#[test]
+fn unknown_hex_digits_light_nothing() {
+    let mut input = Bus::from_u64(3, 4);
+    input.set_bit(2, Signal::X);
+    assert_eq!(lit(HexDigitNode::segments(input)), "????????");
+}
+
+#[test]
+fn led_colors_cycle() {
+    let mut seen = vec![LedColor::default()];
+    let mut color = LedColor::default().next();
+    while color != LedColor::default() {
+        assert!(!seen.contains(&color));
+        seen.push(color);
+        color = color.next();
+    }
+    assert_eq!(seen.len(), 5);
+}
+
+#[test]
+fn lights_keep_the_color_they_are_set_to() {
+    let mut led = LedNode::default();
+    led.set_color(LedColor::Blue);
+    let shared = &led;
+    assert_eq!(shared.color(), LedColor::Blue);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{script, simulator};
+use simple_electronics::resources::PendingRun;
+use simple_electronics::simulator::{RunProgress, SETTLE_LIMIT};
+
+// allows `n` ticks
+fn budget(mut n: usize) -> impl FnMut() -> bool {
+    move || {
+        let left = n > 0;
+        n = n.saturating_sub(1);
+        left
+    }
+}
+
+fn chain() -> String {
+    script(
+        &["a", "b", "c"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Not", inputs: [["a"]], outputs: [["b"]]"#,
+            r#"type: "Not", inputs: [["b"]], outputs: [["c"]]"#,
+        ],
+    )
+}
+
+#[test]
+fn tick_runs_are_spread_over_frames() {
+    let mut sim = simulator(&chain());
+
+    let progress = sim.continue_run(PendingRun::Ticks(25), budget(10));
+    assert_eq!(progress, RunProgress::Running(PendingRun::Ticks(15)));
+    let progress = sim.continue_run(PendingRun::Ticks(15), budget(10));
+    assert_eq!(progress, RunProgress::Running(PendingRun::Ticks(5)));
+    let progress = sim.continue_run(PendingRun::Ticks(5), budget(10));
+    assert_eq!(progress, RunProgress::Finished("Tick 25".to_string()));
+    assert_eq!(sim.ticks(), 25);
+}
+
+#[test]
+fn runs_until_stable_stop_once_nothing_changes() {
+    let mut sim = simulator(&chain());
+    let ticks = match sim.continue_run(PendingRun::UntilStable(0), budget(1000)) {
+        RunProgress::Finished(status) => status,
+        running => panic!("expected the run to finish, got {:?}", running),
+    };
+    assert_eq!(ticks, format!("Stable after {} ticks", sim.ticks()));
+
+    // a run which doesn't fit in the budget carries on counting
+    sim.set_switch("a", true);
+    let progress = sim.continue_run(PendingRun::UntilStable(0), budget(1));
+    assert_eq!(progress, RunProgress::Running(PendingRun::UntilStable(1)));
+    let progress = sim.continue_run(PendingRun::UntilStable(1), budget(1000));
+    assert!(
+        matches!(progress, RunProgress::Finished(status) if status.starts_with("Stable after"))
+    );
+}
+
+#[test]
+fn runs_until_stable_give_up_on_oscillating_circuits() {
+    let mut sim = simulator(&script(
+        &["r"],
+        &[r#"type: "Not", inputs: [["r"]], outputs: [["r"]]"#],
+    ));
+    let progress = sim.continue_run(PendingRun::UntilStable(SETTLE_LIMIT - 5), budget(100));
+    assert_eq!(
+        progress,
+        RunProgress::Finished(format!("Still changing after {} ticks", SETTLE_LIMIT))
+    );
+    assert_eq!(sim.ticks(), 5);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{pulse, script, settle, simulator};
+use simple_electronics::components::Node;
+use simple_electronics::nodes::{Counter4Node, Register4Node, ShiftRegister4Node};
+use simple_electronics::Signal::{High, Low};
+use simple_electronics::{Bus, Signal};
+
+fn signal(value: bool) -> Bus {
+    Bus::from(Signal::from(value))
+}
+
+// counter inputs are enable, up, clock and reset, outputs are the count and the carry
+fn count(counter: &mut Counter4Node, up: bool, clocks: usize) -> (Option<u64>, Signal) {
+    let mut out = [Bus::default(); 2];
+    for _ in 0..clocks {
+        for clock in [true, false] {
+            out = counter.calculate_bus_state([
+                signal(true),
+                signal(up),
+                signal(clock),
+                signal(false),
+            ]);
+        }
+    }
+    (out[0].to_u64(), out[1].bit(0))
+}
+
+#[test]
+fn counter_counts_both_ways_and_wraps() {
+    let mut counter = Counter4Node::default();
+    assert_eq!(count(&mut counter, true, 3), (Some(3), Low));
+    assert_eq!(count(&mut counter, true, 12), (Some(15), High));
+    assert_eq!(count(&mut counter, true, 1), (Some(0), Low));
+    assert_eq!(count(&mut counter, false, 1), (Some(15), Low));
+    assert_eq!(count(&mut counter, false, 15), (Some(0), High));
+}
+
+#[test]
+fn counter_holds_while_disabled_and_clears_on_reset() {
+    let mut counter = Counter4Node::default();
+    count(&mut counter, true, 5);
+
+    let [value, carry] =
+        counter.calculate_bus_state([signal(false), signal(true), signal(true), signal(false)]);
+    assert_eq!((value.to_u64(), carry.bit(0)), (Some(5), Low));
+
+    counter.calculate_bus_state([signal(false); 4]);
+    let [value, _] =
+        counter.calculate_bus_state([signal(true), signal(true), signal(true), signal(true)]);
+    assert_eq!(value.to_u64(), Some(0));
+}
+
+#[test]
+fn shift_register_shifts_in_at_bit_0() {
+    // inputs are data, enable, clock and reset
+    let mut shift = ShiftRegister4Node::default();
+    for data in [true, false, true, true] {
+        shift.calculate_bus_state([signal(data), signal(true), signal(false), signal(false)]);
+        shift.calculate_bus_state([signal(data), signal(true), signal(true), signal(false)]);
+    }
+    assert_eq!(shift.value().to_u64(), Some(0b1011));
+}
+
+#[test]
+fn register_loads_on_the_clock_edge() {
+    // inputs are data, enable, clock and reset
+    let mut register = Register4Node::default();
+    let data = Bus::from_u64(9, 4);
+    let [value] = register.calculate_bus_state([data, signal(true), signal(false), signal(false)]);
+    assert_eq!(value.to_u64(), Some(0));
+    let [value] = register.calculate_bus_state([data, signal(true), signal(true), signal(false)]);
+    assert_eq!(value.to_u64(), Some(9));
+    let [value] = register.calculate_bus_state([
+        Bus::from_u64(2, 4),
+        signal(true),
+        signal(true),
+        signal(false),
+    ]);
+    assert_eq!(value.to_u64(), Some(9));
+}
+
+#[test]
+fn restarting_clears_counters() {
+    let mut sim = simulator(&script(
+        &["on", "off", "clk", "count", "carry"],
+        &[
+            r#"type: "Switch", name: "on", inputs: [], outputs: [["on"]], node: #{ state: true }"#,
+            r#"type: "Switch", name: "off", inputs: [], outputs: [["off"]]"#,
+            r#"type: "Switch", name: "clk", inputs: [], outputs: [["clk"]]"#,
+            r#"type: "Counter4", inputs: [["on"], ["on"], ["clk"], ["off"]], outputs: [["count"], ["carry"]]"#,
+        ],
+    ));
+    settle(&mut sim);
+    pulse(&mut sim, "clk");
+    pulse(&mut sim, "clk");
+    assert_eq!(sim.wire_state("count").unwrap().to_u64(), Some(2));
+
+    sim.reset();
+    settle(&mut sim);
+    assert_eq!(sim.wire_state("count").unwrap().to_u64(), Some(0));
+}
@@ -0,0 +1,82 @@
+use std::process::{Command, Output};
+
+const CIRCUIT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/test_scripts/basic_circuit.rhai"
+);
+
+fn run_circuit(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_run_circuit"))
+        .arg(CIRCUIT)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn passing_expectations_exit_with_0() {
+    let output = run_circuit(&["set", "switch_b", "1", "step", "4", "expect", "out", "1"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+}
+
+#[test]
+fn failing_expectations_exit_with_1() {
+    let output = run_circuit(&["step", "4", "expect", "out", "1", "expect", "a", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("FAIL tick 4: expected out = 1, got 0"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("expected a"), "{}", stdout);
+}
+
+#[test]
+fn malformed_commands_exit_with_2() {
+    for args in [
+        &["step", "many"][..],
+        &["set", "switch_b", "maybe"],
+        &["expect", "out"],
+        &["jump"],
+        &["set", "nothing", "1"],
+        &["print", "nothing"],
+    ] {
+        let output = run_circuit(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: "));
+    }
+}
+
+#[test]
+fn print_shows_named_wires() {
+    let output = run_circuit(&["step", "4", "print", "a", "print"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "a = 1\ntick 4:\n    a = 1\n    b = 0\n    out = 0\n"
+    );
+}
+
+#[test]
+fn command_files_run_before_the_command_line() {
+    let path = std::env::temp_dir().join(format!("run_circuit_{}.txt", std::process::id()));
+    std::fs::write(&path, "# switch b on\nset switch_b 1\nstep 4\n").unwrap();
+
+    let output = run_circuit(&["--commands", path.to_str().unwrap(), "expect", "out", "1"]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+}
+
+#[test]
+fn settle_runs_until_nothing_changes() {
+    let output = run_circuit(&[
+        "set", "switch_b", "1", "settle", "expect", "out", "1", "print",
+    ]);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert!(!stdout(&output).contains("tick 0:"));
+}
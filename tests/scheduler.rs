@@ -0,0 +1,65 @@
+mod common;
+
+use common::{script, simulator};
+use simple_electronics::resources::SchedulerMode;
+use simple_electronics::{Bus, Simulator};
+
+// a few gates fed by switches, a not gate ring and a chain of not gates
+fn circuit() -> String {
+    script(
+        &["s", "t", "and", "xor", "r", "mix", "n1", "n2", "n3"],
+        &[
+            r#"type: "Switch", name: "s", inputs: [], outputs: [["s"]]"#,
+            r#"type: "Switch", name: "t", inputs: [], outputs: [["t"]]"#,
+            r#"type: "And", inputs: [["s"], ["t"]], outputs: [["and"]]"#,
+            r#"type: "Xor", inputs: [["and"], ["s"]], outputs: [["xor"]]"#,
+            r#"type: "Not", inputs: [["r"]], outputs: [["r"]]"#,
+            r#"type: "Xor", inputs: [["r"], ["xor"]], outputs: [["mix"]]"#,
+            r#"type: "Not", inputs: [["xor"]], outputs: [["n1"]]"#,
+            r#"type: "Not", inputs: [["n1"]], outputs: [["n2"]]"#,
+            r#"type: "Not", inputs: [["n2"]], outputs: [["n3"]]"#,
+        ],
+    )
+}
+
+fn trace(sim: &mut Simulator) -> Vec<Vec<(String, Bus)>> {
+    (0..30)
+        .map(|tick| {
+            match tick {
+                3 => sim.set_switch("s", true),
+                7 => sim.set_switch("t", true),
+                12 => sim.set_switch("s", false),
+                20 => sim.set_switch("t", false),
+                _ => true,
+            };
+            sim.step(1);
+            let mut wires = sim.named_wires();
+            wires.sort_by(|a, b| a.0.cmp(&b.0));
+            wires
+        })
+        .collect()
+}
+
+#[test]
+fn schedulers_give_the_same_trace() {
+    let mut event_driven = simulator(&circuit());
+    let mut sweep = simulator(&circuit());
+    sweep.world.insert(SchedulerMode::Sweep);
+
+    let event_driven = trace(&mut event_driven);
+    assert_ne!(event_driven[2], event_driven[10]);
+    assert_eq!(event_driven, trace(&mut sweep));
+}
+
+#[test]
+fn schedulers_agree_after_a_reset() {
+    let mut event_driven = simulator(&circuit());
+    let mut sweep = simulator(&circuit());
+    sweep.world.insert(SchedulerMode::Sweep);
+    trace(&mut event_driven);
+    trace(&mut sweep);
+
+    event_driven.reset();
+    sweep.reset();
+    assert_eq!(trace(&mut event_driven), trace(&mut sweep));
+}
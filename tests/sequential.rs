@@ -0,0 +1,154 @@
+mod common;
+
+use common::{bit, pulse, script, settle, simulator};
+use simple_electronics::{Signal, Simulator};
+
+// a node of the given type with switches for each input, named after its pins
+fn two_input(ty: &str, a: &str, b: &str) -> Simulator<'static, 'static> {
+    simulator(&script(
+        &[a, b, "q", "nq"],
+        &[
+            &format!(
+                r#"type: "Switch", name: "{0}", inputs: [], outputs: [["{0}"]]"#,
+                a
+            ),
+            &format!(
+                r#"type: "Switch", name: "{0}", inputs: [], outputs: [["{0}"]]"#,
+                b
+            ),
+            &format!(
+                r#"type: "{}", inputs: [["{}"], ["{}"]], outputs: [["q"], ["nq"]]"#,
+                ty, a, b
+            ),
+        ],
+    ))
+}
+
+fn set(sim: &mut Simulator, switch: &str, state: bool) {
+    sim.set_switch(switch, state);
+    settle(sim);
+}
+
+fn q(sim: &Simulator) -> Signal {
+    bit(sim, "q")
+}
+
+#[test]
+fn sr_latch_sets_resets_and_holds() {
+    let mut sim = two_input("SrLatch", "s", "r");
+    settle(&mut sim);
+    assert_eq!(q(&sim), Signal::Low);
+
+    set(&mut sim, "s", true);
+    set(&mut sim, "s", false);
+    assert_eq!(q(&sim), Signal::High);
+    assert_eq!(bit(&sim, "nq"), Signal::Low);
+
+    set(&mut sim, "r", true);
+    set(&mut sim, "r", false);
+    assert_eq!(q(&sim), Signal::Low);
+
+    // setting and resetting at once leaves the state unknown
+    set(&mut sim, "s", true);
+    set(&mut sim, "r", true);
+    assert_eq!(q(&sim), Signal::X);
+    set(&mut sim, "s", false);
+    assert_eq!(q(&sim), Signal::Low);
+}
+
+#[test]
+fn d_latch_follows_data_while_enabled() {
+    let mut sim = two_input("DLatch", "d", "e");
+    set(&mut sim, "d", true);
+    assert_eq!(q(&sim), Signal::Low);
+
+    set(&mut sim, "e", true);
+    assert_eq!(q(&sim), Signal::High);
+    set(&mut sim, "d", false);
+    assert_eq!(q(&sim), Signal::Low);
+
+    set(&mut sim, "e", false);
+    set(&mut sim, "d", true);
+    assert_eq!(q(&sim), Signal::Low);
+}
+
+#[test]
+fn d_flip_flop_takes_input_on_rising_edge() {
+    let mut sim = two_input("DFlipFlop", "d", "clk");
+
+    set(&mut sim, "d", true);
+    assert_eq!(q(&sim), Signal::Low);
+
+    set(&mut sim, "clk", true);
+    assert_eq!(q(&sim), Signal::High);
+    assert_eq!(bit(&sim, "nq"), Signal::Low);
+
+    // nothing changes until the next rising edge
+    set(&mut sim, "d", false);
+    set(&mut sim, "clk", false);
+    assert_eq!(q(&sim), Signal::High);
+
+    set(&mut sim, "clk", true);
+    assert_eq!(q(&sim), Signal::Low);
+}
+
+#[test]
+fn t_flip_flop_toggles_while_t_is_high() {
+    let mut sim = two_input("TFlipFlop", "t", "clk");
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::Low);
+
+    set(&mut sim, "t", true);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::High);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::Low);
+}
+
+#[test]
+fn jk_flip_flop_sets_resets_and_toggles() {
+    let mut sim = simulator(&script(
+        &["j", "clk", "k", "q", "nq"],
+        &[
+            r#"type: "Switch", name: "j", inputs: [], outputs: [["j"]]"#,
+            r#"type: "Switch", name: "clk", inputs: [], outputs: [["clk"]]"#,
+            r#"type: "Switch", name: "k", inputs: [], outputs: [["k"]]"#,
+            r#"type: "JkFlipFlop", inputs: [["j"], ["clk"], ["k"]], outputs: [["q"], ["nq"]]"#,
+        ],
+    ));
+
+    set(&mut sim, "j", true);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::High);
+
+    set(&mut sim, "k", true);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::Low);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::High);
+
+    set(&mut sim, "j", false);
+    pulse(&mut sim, "clk");
+    assert_eq!(q(&sim), Signal::Low);
+}
+
+#[test]
+fn flip_flop_divides_a_clock() {
+    let mut sim = simulator(&script(
+        &["clk", "q", "nq"],
+        &[
+            r#"type: "Clock", node: #{ period: 4 }, inputs: [], outputs: [["clk"]]"#,
+            r#"type: "DFlipFlop", inputs: [["nq"], ["clk"]], outputs: [["q"], ["nq"]]"#,
+        ],
+    ));
+
+    let trace = (0..40)
+        .map(|_| {
+            sim.step(1);
+            q(&sim)
+        })
+        .collect::<Vec<_>>();
+    let edges = trace.windows(2).filter(|w| w[0] != w[1]).count();
+    // q toggles once per clock period
+    assert!((9..=10).contains(&edges), "{:?}", trace);
+}
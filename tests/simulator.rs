@@ -0,0 +1,120 @@
+use simple_electronics::components::Connection;
+use simple_electronics::nodes::{NotNode, SwitchNode};
+use simple_electronics::systems::cleanup_sys::delete_node;
+use simple_electronics::{Bus, Connected, Signal, Simulator, Wire};
+use specs::prelude::*;
+
+const SWITCH_AND_NOT: &str = r#"
+let WIRES = #{ a: #{ bends: [] }, out: #{ bends: [] } };
+let NODES = [
+    #{ type: "Switch", pos: #{ x: 0.0, y: 0.0 }, inputs: [], outputs: [["a"]], node: #{ state: true } },
+    #{ type: "Not", pos: #{ x: 100.0, y: 0.0 }, inputs: [["a"]], outputs: [["out"]] },
+];
+"#;
+
+fn not_output(sim: &Simulator) -> Signal {
+    let nots = sim.world.read_storage::<Connected<NotNode, 1, 1>>();
+    let connections = sim.world.read_storage::<Connection>();
+    let wires = sim.world.read_storage::<Wire>();
+    let output = nots.join().next().unwrap().outputs[0];
+    wires
+        .get(connections.get(output).unwrap().wires[0])
+        .unwrap()
+        .output_state
+        .bit(0)
+}
+
+fn set_switch(sim: &mut Simulator, state: bool) {
+    (&mut sim.world.write_storage::<Connected<SwitchNode, 0, 1>>())
+        .join()
+        .for_each(|switch| switch.node.state = state);
+}
+
+#[test]
+fn runs_a_circuit_without_a_window() {
+    let mut sim = Simulator::new();
+    sim.load_script(SWITCH_AND_NOT).unwrap();
+
+    sim.step(4);
+    assert_eq!(not_output(&sim), Signal::Low);
+
+    set_switch(&mut sim, false);
+    sim.step(4);
+    assert_eq!(not_output(&sim), Signal::High);
+}
+
+#[test]
+fn reset_sets_every_wire_low() {
+    let mut sim = Simulator::new();
+    sim.load_script(SWITCH_AND_NOT).unwrap();
+    sim.step(4);
+
+    sim.reset();
+    assert!((&sim.world.read_storage::<Wire>())
+        .join()
+        .all(|wire| wire.input_state == Bus::default() && wire.output_state == Bus::default()));
+}
+
+#[test]
+fn saved_files_load_into_another_simulator() {
+    let mut sim = Simulator::new();
+    sim.load_script(SWITCH_AND_NOT).unwrap();
+    set_switch(&mut sim, false);
+
+    let path = std::env::temp_dir().join(format!("simulator_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    sim.save_file(path).unwrap();
+
+    let mut loaded = Simulator::new();
+    loaded.load_file(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    loaded.step(4);
+    assert_eq!(not_output(&loaded), Signal::High);
+}
+
+#[test]
+fn loading_a_bad_file_keeps_the_circuit() {
+    let mut sim = Simulator::new();
+    sim.load_script(SWITCH_AND_NOT).unwrap();
+
+    let path = std::env::temp_dir().join(format!("simulator_bad_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, [1, 2, 3]).unwrap();
+    assert!(sim.load_file(path).is_err());
+    std::fs::remove_file(path).unwrap();
+
+    set_switch(&mut sim, false);
+    sim.step(4);
+    assert_eq!(not_output(&sim), Signal::High);
+}
+
+#[test]
+fn deleting_a_node_drops_its_wires_from_other_connections() {
+    let mut sim = Simulator::new();
+    sim.load_script(SWITCH_AND_NOT).unwrap();
+    let switch = (
+        &sim.world.entities(),
+        &sim.world.read_storage::<Connected<SwitchNode, 0, 1>>(),
+    )
+        .join()
+        .next()
+        .unwrap()
+        .0;
+
+    delete_node(switch, &mut sim.world);
+    let connections = sim.world.read_storage::<Connection>();
+    let not_input = sim
+        .world
+        .read_storage::<Connected<NotNode, 1, 1>>()
+        .join()
+        .next()
+        .unwrap()
+        .inputs[0];
+    assert!(connections.get(not_input).unwrap().wires.is_empty());
+    assert_eq!(sim.world.read_storage::<Wire>().count(), 1);
+    std::mem::drop(connections);
+
+    // nothing reads from the deleted wire
+    sim.step(4);
+    assert_eq!(not_output(&sim), Signal::X);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{bit, script, simulator, wire};
+use simple_electronics::resources::History;
+use simple_electronics::{Signal, Simulator};
+
+// two tri-state buffers sharing a bus, read through a not gate
+fn shared_bus() -> Simulator<'static, 'static> {
+    simulator(&script(
+        &["a", "en_a", "b", "en_b", "bus", "out"],
+        &[
+            r#"type: "Switch", name: "a", inputs: [], outputs: [["a"]]"#,
+            r#"type: "Switch", name: "en_a", inputs: [], outputs: [["en_a"]]"#,
+            r#"type: "Switch", name: "b", inputs: [], outputs: [["b"]]"#,
+            r#"type: "Switch", name: "en_b", inputs: [], outputs: [["en_b"]]"#,
+            r#"type: "TriState", inputs: [["a"], ["en_a"]], outputs: [["bus"]]"#,
+            r#"type: "TriState", inputs: [["b"], ["en_b"]], outputs: [["bus"]]"#,
+            r#"type: "Not", inputs: [["bus"]], outputs: [["out"]]"#,
+        ],
+    ))
+}
+
+fn set(sim: &mut Simulator, switches: &[(&str, bool)]) -> (Signal, Signal) {
+    for (name, state) in switches {
+        assert!(sim.set_switch(name, *state));
+    }
+    sim.step(6);
+    (bit(sim, "bus"), bit(sim, "out"))
+}
+
+#[test]
+fn disabled_buffers_leave_the_bus_at_z() {
+    let mut sim = shared_bus();
+    // a gate reading Z sees an unknown value
+    assert_eq!(set(&mut sim, &[("a", true)]), (Signal::Z, Signal::X));
+}
+
+#[test]
+fn the_enabled_buffer_drives_the_bus() {
+    let mut sim = shared_bus();
+    assert_eq!(
+        set(&mut sim, &[("a", true), ("en_a", true)]),
+        (Signal::High, Signal::Low)
+    );
+    assert_eq!(
+        set(&mut sim, &[("en_a", false), ("en_b", true)]),
+        (Signal::Low, Signal::High)
+    );
+}
+
+#[test]
+fn enabling_both_buffers_with_different_data_is_unknown() {
+    let mut sim = shared_bus();
+    assert_eq!(
+        set(&mut sim, &[("a", true), ("en_a", true), ("en_b", true)]),
+        (Signal::X, Signal::X)
+    );
+    assert_eq!(set(&mut sim, &[("b", true)]), (Signal::High, Signal::Low));
+}
+
+#[test]
+fn unknown_enable_makes_the_output_unknown() {
+    let mut sim = simulator(&script(
+        &["en", "out"],
+        &[
+            r#"type: "On", inputs: [], outputs: [["en"]]"#,
+            r#"type: "Switch", inputs: [], outputs: [["en"]]"#,
+            r#"type: "TriState", inputs: [[], ["en"]], outputs: [["out"]]"#,
+        ],
+    ));
+    sim.step(6);
+    assert_eq!(bit(&sim, "en"), Signal::X);
+    assert_eq!(bit(&sim, "out"), Signal::X);
+}
+
+#[test]
+fn only_the_shorted_wire_is_marked_as_a_conflict() {
+    let mut sim = shared_bus();
+    set(&mut sim, &[("a", true), ("en_a", true), ("en_b", true)]);
+    assert!(wire(&sim, "bus").conflict);
+    // the X passed on by the not gate isn't a conflict of its own
+    assert!(!wire(&sim, "out").conflict);
+
+    set(&mut sim, &[("b", true)]);
+    assert!(!wire(&sim, "bus").conflict);
+}
+
+#[test]
+fn stepping_back_restores_the_conflict_flag() {
+    let mut sim = shared_bus();
+    sim.world.insert(History::new(64));
+    set(&mut sim, &[("a", true), ("en_a", true), ("en_b", true)]);
+    assert!(wire(&sim, "bus").conflict);
+
+    set(&mut sim, &[("b", true)]);
+    assert!(!wire(&sim, "bus").conflict);
+    (0..6).for_each(|_| assert!(sim.step_back()));
+    assert!(wire(&sim, "bus").conflict);
+}